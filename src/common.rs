@@ -0,0 +1,103 @@
+// the kernel to synthesize, shared by main_astar.rs, main_pddl.rs and main_minizinc.rs
+// and the variants main_astar_{minmax,mixed,payload,avx,aarch64,riscv}.rs
+// (main_astar_{lanes,memory,arith}.rs only sort, their inputs are no permutations)
+// given via environment variable SPEC:
+// sort (default), desc, min, max, median, topk:K, partial:K, table:FILE
+// NUMBERS is the one of the binary
+use itertools::Itertools;
+use std::collections::HashMap;
+use crate::{NUMBERS, NUMBERS_U8};
+
+pub enum Spec {
+    Sort,
+    SortDescending,
+    Min,
+    Max,
+    Median,
+    // k largest values in descending order
+    TopK(usize),
+    // k smallest values in ascending order
+    PartialSort(usize),
+    // user supplied input -> output table
+    Table(HashMap<Vec<u8>, [u8; NUMBERS]>),
+}
+
+impl Spec {
+    pub fn from_env() -> Spec {
+        let spec = std::env::var("SPEC").unwrap_or("sort".to_string());
+        let (name, arg) = match spec.split_once(':') {
+            Some((name, arg)) => (name, Some(arg)),
+            None => (spec.as_str(), None),
+        };
+        let k = || {
+            let k = arg.expect("Spec needs an argument").parse::<usize>().expect("Invalid k");
+            assert!(k >= 1 && k <= NUMBERS, "k has to be in 1..={}", NUMBERS);
+            k
+        };
+        match name {
+            "sort" => Spec::Sort,
+            "desc" => Spec::SortDescending,
+            "min" => Spec::Min,
+            "max" => Spec::Max,
+            "median" => Spec::Median,
+            "topk" => Spec::TopK(k()),
+            "partial" => Spec::PartialSort(k()),
+            "table" => Spec::Table(read_table(arg.expect("Table spec needs a file"))),
+            _ => panic!("Unknown spec: {}", spec),
+        }
+    }
+
+    // expected content of the output registers for an input (0 = don't care)
+    // None if the input is not constrained (only for tables)
+    pub fn target(&self, input: &[u8]) -> Option<[u8; NUMBERS]> {
+        let mut out = [0; NUMBERS];
+        match self {
+            Spec::Sort => (0..NUMBERS).for_each(|i| out[i] = (i+1) as u8),
+            Spec::SortDescending => (0..NUMBERS).for_each(|i| out[i] = (NUMBERS-i) as u8),
+            Spec::Min => out[0] = 1,
+            Spec::Max => out[0] = NUMBERS_U8,
+            // lower median for even n
+            Spec::Median => out[0] = ((NUMBERS+1)/2) as u8,
+            Spec::TopK(k) => (0..*k).for_each(|i| out[i] = (NUMBERS-i) as u8),
+            Spec::PartialSort(k) => (0..*k).for_each(|i| out[i] = (i+1) as u8),
+            Spec::Table(table) => return table.get(input).cloned(),
+        }
+        Some(out)
+    }
+}
+
+// one entry per line: input -> output
+// e.g. "3 1 2 -> 3 _ _" (_ or 0 = don't care, missing outputs are don't care)
+// inputs not in the table are not constrained
+fn read_table(file: &str) -> HashMap<Vec<u8>, [u8; NUMBERS]> {
+    let content = std::fs::read_to_string(file).expect("Could not read table");
+    let mut table = HashMap::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (input, output) = line.split_once("->").expect("Expected input -> output");
+        let input = input.split_whitespace().map(|x| x.parse::<u8>().unwrap()).collect::<Vec<_>>();
+        if input.iter().copied().sorted().ne(1..=NUMBERS_U8) {
+            panic!("Input {:?} is not a permutation of 1..={}", input, NUMBERS);
+        }
+        let mut out = [0; NUMBERS];
+        for (i, x) in output.split_whitespace().enumerate() {
+            out[i] = if x == "_" { 0 } else { x.parse::<u8>().unwrap() };
+            if out[i] > NUMBERS_U8 {
+                panic!("Output {} is not a value of the input", x);
+            }
+        }
+        table.insert(input, out);
+    }
+    table
+}
+
+// inputs with their expected outputs, unconstrained inputs are dropped
+pub fn inputs(spec: &Spec) -> Vec<(Vec<u8>, [u8; NUMBERS])> {
+    (1..=NUMBERS_U8)
+        .permutations(NUMBERS)
+        .filter_map(|p| spec.target(&p).map(|t| (p, t)))
+        .collect()
+}
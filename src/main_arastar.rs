@@ -0,0 +1,436 @@
+use itertools::Itertools;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::ops::Range;
+// has largest value at the top
+use priority_queue::PriorityQueue;
+use std::cmp::Reverse;
+use std::rc::Rc;
+use std::io::Write;
+
+/*
+Anytime repairing A* (ARA*, Likhachev et al. 2003)
+
+Weighted A* with f = g + w*h and an admissible heuristic finds a program at most w times longer than optimal.
+We start with a large w to find a program fast and decrease w afterwards.
+Instead of restarting, the search effort is reused:
+- states that got a shorter path after they were expanded are collected in INCONS
+- for the next weight, OPEN and INCONS are merged and reordered
+
+The bubble sort network is the initial incumbent.
+After each iteration, the program and its suboptimality bound
+    min(w, length / min_{s in OPEN u INCONS} (g(s) + h(s)))
+are reported. With w = 1 (or empty OPEN and INCONS) the program is optimal.
+The search stops early after TIME_LIMIT seconds (environment variable).
+*/
+
+const NUMBERS: usize = 3; // optimal (11) after 4s
+// const NUMBERS: usize = 4; // > 5GB for w = 3
+// const NUMBERS: usize = 5;
+const MAX_LEN: u8 = 40;
+const SWAPS: usize = 1;
+const REGS: usize = NUMBERS + SWAPS;
+const CMP: usize = 0;
+const MOV: usize = 1;
+const CMOVG: usize = 2;
+const CMOVL: usize = 3;
+const NUMBERS_U8: u8 = NUMBERS as u8;
+
+// heuristic weights in hundredths
+const WEIGHTS: [u32; 7] = [500, 300, 200, 150, 120, 110, 100];
+
+type Command = (usize, usize, usize);
+#[derive(Clone, Eq, PartialEq, Hash, Ord, PartialOrd, Debug, Copy)]
+struct Permutation([u8; REGS + 2]);
+type State = Vec<Permutation>;
+
+use std::ops::{Index, IndexMut};
+
+impl Index<usize> for Permutation {
+    type Output = u8;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+impl IndexMut<usize> for Permutation {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.0[index]
+    }
+}
+
+impl Index<Range<usize>> for Permutation {
+    type Output = [u8];
+
+    fn index(&self, index: Range<usize>) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+impl IndexMut<Range<usize>> for Permutation {
+    fn index_mut(&mut self, index: Range<usize>) -> &mut Self::Output {
+        &mut self.0[index]
+    }
+}
+
+fn possible_commands() -> Vec<Command> {
+    let mut commands = vec![];
+    for instr in &[MOV, CMOVG, CMOVL] {
+        for to in 0..REGS {
+            for from in 0..REGS {
+                if to != from {
+                    commands.push((*instr, to, from));
+                }
+            }
+        }
+    }
+    for i in 0..REGS {
+        for j in (i + 1)..REGS {
+            commands.push((CMP, i, j));
+        }
+    }
+    commands
+}
+
+// transform a permutation according to a command
+fn apply(cmd: &Command, perm: &mut Permutation) {
+    let (instr, to, from) = *cmd;
+    match instr {
+        CMP => {
+            perm[REGS + 0] = (perm[to] < perm[from]) as u8;
+            perm[REGS + 1] = (perm[to] > perm[from]) as u8;
+        }
+        MOV => perm[to] = perm[from],
+        CMOVG => {
+            if perm[REGS + 1] == 1 {
+                perm[to] = perm[from];
+            }
+        }
+        CMOVL => {
+            if perm[REGS + 0] == 1 {
+                perm[to] = perm[from];
+            }
+        }
+        _ => panic!("Unknown instruction"),
+    }
+}
+
+// map a command over all permutations in a state
+fn apply_all(cmd: &Command, state: &State) -> State {
+    let mut new_state = Vec::new();
+    for perm in state {
+        let mut new_perm = perm.clone();
+        apply(cmd, &mut new_perm);
+        new_state.push(new_perm);
+    }
+    new_state.sort();
+    new_state.dedup();
+    new_state
+}
+
+// check if the state can never reach a solution
+// corresponds to delete-relaxed planning check
+fn viable(state: &State) -> bool {
+    for perm in state {
+        for n in 1..=NUMBERS_U8 {
+            if !perm[0..REGS].contains(&n) {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+fn show_command(cmd: &Command) -> String {
+    let (instr, to, from) = *cmd;
+    // 1-indexed to stay consistent with minizinc
+    let to = to+1;
+    let from = from+1;
+    match instr {
+        CMP => format!("CMP {} {}", to, from),
+        MOV => format!("MOV {} {}", to, from),
+        CMOVG => format!("CMOVG {} {}", to, from),
+        CMOVL => format!("CMOVL {} {}", to, from),
+        _ => panic!("Unknown instruction"),
+    }
+}
+
+// linked list to store the commands and pointer to last element
+#[derive(Clone, Eq, PartialEq, Hash)]
+struct Node {
+    cmd: Command,
+    prev: Option<Rc<Node>>,
+}
+
+// for each permutation, take out register values, concat => serializable byte array
+fn state_positions(state: &State) -> Vec<u8> {
+    state.iter().flat_map(|p| p.0).collect()
+}
+
+fn extract_program(node: &Node) -> Vec<Command> {
+    let mut cmds = vec![];
+    let mut node = node;
+    while let Some(prev) = &node.prev {
+        cmds.push(node.cmd);
+        node = prev;
+    }
+    cmds.reverse();
+    cmds
+}
+
+fn is_sorted(perm: &Permutation) -> bool {
+    perm[0..NUMBERS].iter().copied().eq(1..=NUMBERS_U8)
+}
+
+// each instruction writes at most one register
+// => every register that is wrong in at least one permutation needs one more instruction
+fn admissible_heuristic(state: &State) -> u8 {
+    (0..NUMBERS)
+        .filter(|&i| state.iter().any(|p| p[i] != (i+1) as u8))
+        .count() as u8
+}
+
+// comparator (i,j) with i < j: afterwards register i holds the smaller value
+// uses the first swap register as temporary
+fn comparator(i: usize, j: usize) -> Vec<Command> {
+    vec![
+        (MOV, NUMBERS, i),
+        (CMP, i, j),
+        (CMOVG, i, j),
+        (CMOVG, j, NUMBERS),
+    ]
+}
+
+// initial incumbent without any search: bubble sort network (as in main_iterative.rs)
+fn bubble_sort_program() -> Vec<Command> {
+    let mut cmds = vec![];
+    for i in 0..NUMBERS-1 {
+        for j in 0..NUMBERS-1-i {
+            cmds.extend(comparator(j, j+1));
+        }
+    }
+    cmds
+}
+
+// best known path to a state
+struct Entry {
+    g: u8,
+    h: u8,
+    // number of different outputs (the heuristic of main_astar.rs)
+    perm_count: usize,
+    state: Rc<State>,
+    node: Rc<Node>,
+}
+
+// f = g + w*h in hundredths
+// the admissible heuristic is weak (at most NUMBERS)
+// => ties are broken by the permutation count which guides the search much better
+fn priority(entry: &Entry, weight: u32) -> Reverse<(u32, usize)> {
+    Reverse((entry.g as u32 * 100 + weight * entry.h as u32, entry.perm_count))
+}
+
+fn perm_count(state: &State) -> usize {
+    state.iter().map(|p| &p[0..NUMBERS]).unique().count()
+}
+
+fn main() {
+    let possible_cmds = possible_commands();
+    let permutations: Vec<Vec<u8>> = (1..=NUMBERS_U8).permutations(NUMBERS).collect();
+    let time_limit = std::env::var("TIME_LIMIT").ok()
+        .map(|t| std::time::Duration::from_secs(t.parse::<u64>().expect("TIME_LIMIT in seconds")));
+
+    println!("n = {}", NUMBERS);
+    println!("max_len = {}", MAX_LEN);
+    println!("swaps = {}", SWAPS);
+    println!("weights = {:?}", WEIGHTS.iter().map(|w| *w as f32 / 100.0).collect::<Vec<_>>());
+
+    // extend numerical permutations with register for swap and flags
+    let initial_state: Rc<State> = Rc::new(permutations
+        .iter()
+        .map(|p| {
+            let mut perm = Permutation([0; REGS + 2]);
+            for (i, &x) in p.iter().enumerate() {
+                perm[i] = x;
+            }
+            perm
+        })
+        .sorted()
+        .collect());
+
+    let solution_dir = std::env::var("SOLUTION_DIR").ok();
+    let subdir = solution_dir.map(|dir| format!("{}/{}_arastar", dir, NUMBERS));
+    if let Some(subdir) = &subdir {
+        std::fs::create_dir_all(&subdir).unwrap();
+        println!("Storing solutions in: {}", subdir);
+    }
+
+    let mut entries: HashMap<Vec<u8>, Entry> = HashMap::new();
+    let mut open = PriorityQueue::new();
+    let mut closed: HashSet<Vec<u8>> = HashSet::new();
+    let mut incons: HashSet<Vec<u8>> = HashSet::new();
+
+    let key0 = state_positions(&initial_state);
+    let entry0 = Entry {
+        g: 0,
+        h: admissible_heuristic(&initial_state),
+        perm_count: perm_count(&initial_state),
+        state: Rc::clone(&initial_state),
+        node: Rc::new(Node{cmd: (0,0,0), prev: None}),
+    };
+    open.push(key0.clone(), priority(&entry0, WEIGHTS[0]));
+    entries.insert(key0, entry0);
+
+    // incumbent: shortest program found so far
+    // the bubble sort network gives a first bound (and prunes everything that is not shorter)
+    let mut best: Option<Vec<Command>> = None;
+    let mut best_len = MAX_LEN + 1;
+    let network = bubble_sort_program();
+    if network.len() as u8 <= MAX_LEN {
+        let mut state = (*initial_state).clone();
+        for cmd in &network {
+            state = apply_all(cmd, &state);
+        }
+        assert!(state.iter().all(is_sorted), "Bubble sort network does not sort");
+        println!("Bubble sort network of length {}", network.len());
+        best_len = network.len() as u8;
+        best = Some(network);
+    }
+
+    let mut visited : u64 = 0;
+    let mut cut : u64 = 0;
+    let start = std::time::Instant::now();
+    let mut out_of_time = false;
+    for (iteration, &weight) in WEIGHTS.iter().enumerate() {
+        if iteration > 0 {
+            // reuse the previous search: OPEN u INCONS with the new weight, CLOSED is reset
+            let keys = open.into_iter().map(|(k, _)| k).chain(incons.drain()).collect::<HashSet<_>>();
+            open = PriorityQueue::new();
+            for key in keys {
+                let p = priority(&entries[&key], weight);
+                open.push(key, p);
+            }
+            closed.clear();
+        }
+        println!("Weight {}", weight as f32 / 100.0);
+
+        // improve path: expand until no node can lead to a shorter program (w.r.t. the weighted f)
+        while let Some((_, &Reverse((f, _)))) = open.peek() {
+            if best_len as u32 * 100 <= f {
+                break;
+            }
+            if let Some(limit) = time_limit {
+                if start.elapsed() > limit {
+                    out_of_time = true;
+                    break;
+                }
+            }
+            let (key, _) = open.pop().unwrap();
+            closed.insert(key.clone());
+            let (g, state, node) = {
+                let entry = &entries[&key];
+                (entry.g, Rc::clone(&entry.state), Rc::clone(&entry.node))
+            };
+
+            visited += 1;
+            if visited % 100000 == 0 {
+                print!("Open: {}, ", open.len());
+                print!("Incons: {}, ", incons.len());
+                print!("Visited: {}, ", visited);
+                print!("Cut: {}, ", cut);
+                print!("Current length: {}, ", g);
+                print!("Best: {}, ", best_len);
+                print!("Time: {:?}", start.elapsed());
+                println!("");
+            }
+
+            for cmd in &possible_cmds {
+                let new_state = apply_all(cmd, &state);
+                let new_g = g + 1;
+
+                if !viable(&new_state) {
+                    cut += 1;
+                    continue;
+                }
+
+                let new_h = admissible_heuristic(&new_state);
+                // can not lead to a shorter program than the incumbent
+                if new_g + new_h >= best_len {
+                    cut += 1;
+                    continue;
+                }
+
+                let new_key = state_positions(&new_state);
+                if let Some(entry) = entries.get(&new_key) {
+                    if entry.g <= new_g {
+                        continue;
+                    }
+                }
+                let new_node = Rc::new(Node{cmd: *cmd, prev: Some(Rc::clone(&node))});
+
+                if new_state.iter().all(is_sorted) {
+                    // h = 0 and nothing left to do => new incumbent
+                    best_len = new_g;
+                    best = Some(extract_program(&new_node));
+                }
+
+                let entry = Entry {
+                    g: new_g,
+                    h: new_h,
+                    perm_count: perm_count(&new_state),
+                    state: Rc::new(new_state),
+                    node: new_node,
+                };
+                if closed.contains(&new_key) {
+                    // expanded before with a longer path
+                    incons.insert(new_key.clone());
+                } else {
+                    open.push(new_key.clone(), priority(&entry, weight));
+                }
+                entries.insert(new_key, entry);
+            }
+        }
+
+        // lower bound: min g + h over all states that still might be on a shorter path
+        let lower_bound = open.iter().map(|(k, _)| k).chain(incons.iter())
+            .map(|k| entries[k].g + entries[k].h)
+            .filter(|&f| f < best_len)
+            .min()
+            .unwrap_or(best_len);
+        match &best {
+            Some(cmds) => {
+                let bound = (weight as f32 / 100.0).min(best_len as f32 / lower_bound as f32);
+                println!("Program of length {}, lower bound {}, suboptimality bound {:.3}, Time: {:?}",
+                    best_len, lower_bound, bound, start.elapsed());
+                if let Some(subdir) = &subdir {
+                    let file = format!("{}/solution_{}.txt", subdir, best_len);
+                    let mut file = std::fs::File::create(file).unwrap();
+                    for cmd in cmds {
+                        writeln!(file, "{}", show_command(cmd)).unwrap();
+                    }
+                }
+                if lower_bound == best_len {
+                    println!("Proven optimal");
+                    break;
+                }
+            }
+            None => println!("No program found yet, lower bound {}, Time: {:?}", lower_bound, start.elapsed()),
+        }
+        if out_of_time {
+            println!("Time limit reached");
+            break;
+        }
+    }
+
+    match best {
+        Some(cmds) => {
+            println!("Program:");
+            for cmd in cmds {
+                println!("{}", show_command(&cmd));
+            }
+        }
+        None => println!("No program of length <= {}", MAX_LEN),
+    }
+    println!("Visited: {}, Cut: {}", visited, cut);
+    println!("Elapsed: {:?}", start.elapsed());
+}
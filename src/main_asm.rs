@@ -0,0 +1,422 @@
+use itertools::Itertools;
+use std::io::Write;
+use std::ops::Range;
+
+mod x86;
+use x86::*;
+
+/*
+Import x86 assembly sorting kernels (e.g. AlphaDev sort_functions_test.cc) as programs
+
+Usage:
+  asm FILE [OUT]   parses FILE, verifies the program on all permutations,
+                   writes the program in our format to OUT (show_command of x86.rs like the minizinc and smt
+                   binaries: 1-indexed registers, normal registers first, then xmm registers)
+
+Supported: mov, cmp, cmovg, cmovl, pminud, pmaxud, movd, movdqa
+in AT&T syntax (`cmovg %ecx, %eax`, also with the %% and quotes of inline assembly)
+or Intel syntax (`cmovg eax, ecx`).
+The syntax is detected by the % in front of registers (override with SYNTAX=att|intel).
+
+Inputs and outputs:
+- kernels with memory operands (loads/stores) work on a buffer of n 32-bit values:
+  the cells are registers holding the input and output (loads and stores are movs)
+- otherwise the input is in eax, ecx, edx, r8d, ... and xmm0, xmm1, ... (like main_astar_mixed.rs)
+  and the output is expected in the class of the last written register
+All other registers are scratch registers (in order of appearance, start with 0).
+The numbering is the one of the smallest ISA with the used register classes:
+cmov and minmax kernels are numbered like in all exporters,
+mixed kernels have their xmm registers after the REGS = NUMBERS + SWAPS normal registers
+(smt needs the same SWAPS to read them).
+*/
+
+const NUMBERS: usize = 3;
+// const NUMBERS: usize = 4;
+// const NUMBERS: usize = 5;
+// room for the registers of published kernels (REGS of x86.rs)
+const SWAPS: usize = 8;
+const NUMBERS_U8: u8 = NUMBERS as u8;
+
+// registers holding the input if the kernel does not use memory
+const INPUT_REGS: [&str; 8] = ["eax", "ecx", "edx", "r8d", "r9d", "r10d", "r11d", "esi"];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Syntax {
+    Att,
+    Intel,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Operand {
+    // normalized register name (eax, r8d, xmm0, ...)
+    Reg(String),
+    // index of the 32-bit cell in the buffer
+    Mem(usize),
+}
+
+// one instruction of the source
+struct Line {
+    number: usize,
+    text: String,
+    mnemonic: String,
+    operands: Vec<Operand>,
+}
+
+// 32-bit name of a general purpose register, xmm registers as they are
+fn normalize_reg(name: &str) -> Option<String> {
+    let name = name.trim().trim_start_matches('%').to_lowercase();
+    let legacy = ["ax", "bx", "cx", "dx", "si", "di", "bp"];
+    for reg in legacy {
+        if name == format!("e{}", reg) || name == format!("r{}", reg) {
+            return Some(format!("e{}", reg));
+        }
+    }
+    if let Some(index) = name.strip_prefix("xmm") {
+        return index.parse::<usize>().ok().map(|i| format!("xmm{}", i));
+    }
+    if let Some(index) = name.strip_prefix('r') {
+        let index = index.trim_end_matches('d');
+        if let Ok(i) = index.parse::<usize>() {
+            if (8..16).contains(&i) {
+                return Some(format!("r{}d", i));
+            }
+        }
+    }
+    None
+}
+
+fn parse_number(text: &str) -> Option<i64> {
+    let text = text.trim();
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest.trim()),
+        None => (false, text),
+    };
+    let value = match text.strip_prefix("0x") {
+        Some(hex) => i64::from_str_radix(hex, 16).ok()?,
+        None => text.parse::<i64>().ok()?,
+    };
+    Some(if negative { -value } else { value })
+}
+
+// byte offset => cell of the buffer (only the base register is allowed besides the offset)
+fn memory_cell(offset: i64) -> Result<usize, String> {
+    if offset < 0 || offset % 4 != 0 || offset / 4 >= NUMBERS as i64 {
+        return Err(format!("Offset {} is not one of the {} 32-bit cells", offset, NUMBERS));
+    }
+    Ok((offset / 4) as usize)
+}
+
+fn parse_operand(text: &str, syntax: Syntax) -> Result<Operand, String> {
+    let text = text.trim();
+    match syntax {
+        // 0x4(%rdi), (%0)
+        Syntax::Att if text.contains('(') => {
+            let offset = &text[..text.find('(').unwrap()];
+            let offset = if offset.is_empty() { 0 } else {
+                parse_number(offset).ok_or(format!("Invalid offset: {}", offset))?
+            };
+            memory_cell(offset).map(Operand::Mem)
+        }
+        // dword ptr [rdi + 0x4], [rdi]
+        Syntax::Intel if text.contains('[') => {
+            let inner = &text[text.find('[').unwrap()+1..text.find(']').ok_or("Missing ]")?];
+            let mut offset = 0;
+            for term in inner.replace('-', "+-").split('+') {
+                if let Some(value) = parse_number(term) {
+                    offset += value;
+                } else if normalize_reg(term).is_none() {
+                    return Err(format!("Unsupported address: {}", text));
+                }
+            }
+            memory_cell(offset).map(Operand::Mem)
+        }
+        _ => normalize_reg(text).map(Operand::Reg).ok_or(format!("Unknown register: {}", text)),
+    }
+}
+
+// instructions of a line, without comments, labels, directives and inline assembly decoration
+// inline assembly: only the instruction strings ("cmp %%eax, %%ecx \n\t"), the C code around is ignored
+fn instructions(line: &str, syntax: Syntax, inline: bool) -> Vec<String> {
+    let mut line = line.to_string();
+    if inline {
+        let mut parts = line.split('"');
+        line = match (parts.next(), parts.next()) {
+            // constraints and clobbers ("+r", "memory") have no operands
+            (Some(_), Some(string)) if string.trim().contains(char::is_whitespace) => string.to_string(),
+            _ => return vec![],
+        };
+    }
+    let line = line.replace("\\n", "\n").replace("\\t", " ").replace("%%", "%");
+    let line = line.split("//").next().unwrap();
+    let comment = if syntax == Syntax::Att { '#' } else { ';' };
+    let line = line.split(comment).next().unwrap();
+    line.split(|c| c == '\n' || c == ';')
+        .map(|instr| instr.trim())
+        .map(|instr| match instr.rfind(':') {
+            // label
+            Some(colon) if !instr[..colon].contains(' ') => instr[colon+1..].trim(),
+            _ => instr,
+        })
+        .filter(|instr| !instr.is_empty() && !instr.starts_with('.'))
+        .map(|instr| instr.to_string())
+        .collect()
+}
+
+fn detect_syntax(content: &str) -> Syntax {
+    match std::env::var("SYNTAX").ok().as_deref() {
+        Some("att") => Syntax::Att,
+        Some("intel") => Syntax::Intel,
+        Some(syntax) => panic!("Unknown syntax: {}", syntax),
+        None if content.contains('%') => Syntax::Att,
+        None => Syntax::Intel,
+    }
+}
+
+fn parse_lines(content: &str, syntax: Syntax) -> Result<Vec<Line>, String> {
+    let known = ["mov", "cmp", "cmovg", "cmovl", "pminud", "pmaxud", "movd", "movdqa"];
+    let inline = content.contains('"');
+    let mut lines = vec![];
+    for (number, line) in content.lines().enumerate() {
+        let number = number + 1;
+        for text in instructions(line, syntax, inline) {
+            let (mnemonic, rest) = text.split_once(char::is_whitespace).unwrap_or((&text, ""));
+            let mut mnemonic = mnemonic.to_lowercase();
+            // AT&T size suffix (movl, cmpl)
+            if !known.contains(&mnemonic.as_str()) && syntax == Syntax::Att {
+                if let Some(base) = mnemonic.strip_suffix('l').or(mnemonic.strip_suffix('q')) {
+                    if known.contains(&base) {
+                        mnemonic = base.to_string();
+                    }
+                }
+            }
+            if !known.contains(&mnemonic.as_str()) {
+                return Err(format!("Line {}: unsupported instruction: {}", number, text));
+            }
+            // split at commas outside of parentheses/brackets
+            let mut operands = vec![];
+            let mut depth = 0;
+            let mut current = String::new();
+            for c in rest.chars() {
+                match c {
+                    '(' | '[' => depth += 1,
+                    ')' | ']' => depth -= 1,
+                    _ => {}
+                }
+                if c == ',' && depth == 0 {
+                    operands.push(current.clone());
+                    current.clear();
+                } else {
+                    current.push(c);
+                }
+            }
+            operands.push(current);
+            let mut operands = operands
+                .iter()
+                .map(|op| parse_operand(op, syntax))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| format!("Line {}: {}", number, e))?;
+            if operands.len() != 2 {
+                return Err(format!("Line {}: expected two operands: {}", number, text));
+            }
+            // our commands are (instr, to, from) like Intel syntax
+            if syntax == Syntax::Att {
+                operands.reverse();
+            }
+            lines.push(Line { number, text, mnemonic, operands });
+        }
+    }
+    Ok(lines)
+}
+
+// imported program with the names of the used registers
+struct Program {
+    cmds: Vec<Command>,
+    names: Vec<(usize, String)>,
+    uses_memory: bool,
+    memory_moves: usize,
+}
+
+impl Program {
+    // the smallest ISA of x86.rs with the used register classes (numbering of the registers)
+    fn isa(&self) -> Isa {
+        let gp = self.names.iter().any(|(r, _)| *r < REGS);
+        let xmm = self.names.iter().any(|(r, _)| *r >= XMMOFFSET);
+        match (gp, xmm) {
+            (_, false) => Isa::Cmov,
+            (false, true) => Isa::MinMax,
+            (true, true) => Isa::Mixed,
+        }
+    }
+
+    // the input is in the buffer (normal registers) or in both classes (like main_astar_mixed.rs)
+    fn input_isa(&self) -> Isa {
+        if self.uses_memory { Isa::Cmov } else { Isa::Mixed }
+    }
+
+    fn input_regs(&self) -> Vec<Range<usize>> {
+        self.input_isa().input_regs()
+    }
+
+    fn output_regs(&self) -> Range<usize> {
+        match self.cmds.iter().rev().find(|cmd| cmd.0 != CMP) {
+            Some(&(_, to, _)) if to >= XMMOFFSET && !self.uses_memory => XMMOFFSET..XMMOFFSET+NUMBERS,
+            _ => 0..NUMBERS,
+        }
+    }
+}
+
+// assign our registers to the registers (and cells) of the kernel
+fn to_program(lines: &[Line]) -> Result<Program, String> {
+    let uses_memory = lines.iter().any(|l| l.operands.iter().any(|op| matches!(op, Operand::Mem(_))));
+    let mut names = vec![];
+    if uses_memory {
+        for i in 0..NUMBERS {
+            names.push((i, format!("mem[{}]", i)));
+        }
+    } else {
+        for i in 0..NUMBERS {
+            names.push((i, INPUT_REGS[i].to_string()));
+            names.push((XMMOFFSET + i, format!("xmm{}", i)));
+        }
+    }
+
+    let mut cmds = vec![];
+    let mut memory_moves = 0;
+    for line in lines {
+        let error = |msg: &str| format!("Line {}: {}: {}", line.number, msg, line.text);
+        let mut regs = vec![];
+        for op in &line.operands {
+            let (name, xmm) = match op {
+                Operand::Mem(cell) => (format!("mem[{}]", cell), false),
+                Operand::Reg(name) => (name.clone(), name.starts_with("xmm")),
+            };
+            let reg = match names.iter().find(|(_, n)| *n == name) {
+                Some((reg, _)) => *reg,
+                None => {
+                    let (class, offset, count) = if xmm { (XMMOFFSET..XMMOFFSET+XMMREGS, XMMOFFSET, XMMREGS) } else { (0..REGS, 0, REGS) };
+                    let reg = class.clone().find(|r| names.iter().all(|(used, _)| used != r))
+                        .ok_or(error(&format!("more than {} registers of a class ({} at {})", count, name, offset)))?;
+                    names.push((reg, name));
+                    reg
+                }
+            };
+            regs.push((reg, xmm, matches!(op, Operand::Mem(_))));
+        }
+        let (to, to_xmm, to_mem) = regs[0];
+        let (from, from_xmm, from_mem) = regs[1];
+        if to_mem && from_mem {
+            return Err(error("two memory operands"));
+        }
+        let instr = match line.mnemonic.as_str() {
+            "mov" | "cmp" | "cmovg" | "cmovl" => {
+                if to_xmm || from_xmm {
+                    return Err(error("xmm register in a general purpose instruction"));
+                }
+                if to_mem && line.mnemonic.starts_with("cmov") {
+                    return Err(error("cmov into memory"));
+                }
+                match line.mnemonic.as_str() {
+                    "mov" => MOV,
+                    "cmp" => CMP,
+                    "cmovg" => CMOVG,
+                    _ => CMOVL,
+                }
+            }
+            "pminud" | "pmaxud" | "movdqa" => {
+                if !to_xmm || !from_xmm {
+                    return Err(error("only xmm registers are supported"));
+                }
+                match line.mnemonic.as_str() {
+                    "pminud" => MIN,
+                    "pmaxud" => MAX,
+                    _ => MOVDQA,
+                }
+            }
+            "movd" => {
+                if to_xmm == from_xmm {
+                    return Err(error("movd between registers of the same class"));
+                }
+                MOVD
+            }
+            _ => return Err(error("unsupported instruction")),
+        };
+        if to_mem || from_mem {
+            memory_moves += 1;
+        }
+        cmds.push((instr, to, from));
+    }
+    names.sort();
+    // only keep used registers (and the buffer)
+    names.retain(|(reg, _)| (uses_memory && *reg < NUMBERS)
+        || cmds.iter().any(|&(_, to, from)| to == *reg || from == *reg));
+    Ok(Program { cmds, names, uses_memory, memory_moves })
+}
+
+// run the program on all permutations (scratch registers 0), returns the inputs with a wrong output
+fn verify(program: &Program) -> Vec<(Vec<u8>, Vec<u8>)> {
+    let outputs = program.output_regs();
+    let mut wrong = vec![];
+    for input in (1..=NUMBERS_U8).permutations(NUMBERS) {
+        let mut perm = initial_perm(program.input_isa(), &input);
+        for cmd in &program.cmds {
+            apply(cmd, &mut perm);
+        }
+        if !perm[outputs.clone()].iter().copied().eq(1..=NUMBERS_U8) {
+            wrong.push((input, perm[outputs.clone()].to_vec()));
+        }
+    }
+    wrong
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let file = args.get(1).expect("Usage: asm FILE [OUT]");
+    let content = std::fs::read_to_string(file).expect("Could not read assembly");
+    let syntax = detect_syntax(&content);
+
+    println!("n = {}", NUMBERS);
+    println!("syntax = {:?}", syntax);
+
+    let program = match parse_lines(&content, syntax).and_then(|lines| to_program(&lines)) {
+        Ok(program) => program,
+        Err(e) => {
+            println!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    println!("Registers:");
+    for (reg, name) in &program.names {
+        println!("{} = {}", program_reg(program.isa(), *reg), name);
+    }
+    println!("Program:");
+    for cmd in &program.cmds {
+        println!("{}", show_command(cmd, program.isa()));
+    }
+    println!("Length: {}", program.cmds.len());
+    if program.uses_memory {
+        println!("Length without loads/stores: {}", program.cmds.len() - program.memory_moves);
+    }
+    let scratch = program.names.iter().filter(|(reg, _)| !program.input_regs().iter().any(|r| r.contains(reg))).count();
+    println!("Scratch registers: {}", scratch);
+
+    let wrong = verify(&program);
+    if wrong.is_empty() {
+        println!("Program sorts all {} permutations", (1..=NUMBERS).product::<usize>());
+    } else {
+        for (input, output) in &wrong {
+            println!("Wrong output for input {:?}: {:?}", input, output);
+        }
+        println!("Program is wrong on {} permutations", wrong.len());
+    }
+
+    if let Some(out) = args.get(2) {
+        let mut out = std::fs::File::create(out).unwrap();
+        for cmd in &program.cmds {
+            writeln!(out, "{}", show_command(cmd, program.isa())).unwrap();
+        }
+    }
+    if !wrong.is_empty() {
+        std::process::exit(1);
+    }
+}
@@ -15,6 +15,9 @@ use std::io::Write;
 use std::cmp::min;
 use serde::{Serialize, Deserialize};
 
+mod common;
+use common::{inputs, Spec};
+
 
 /*
 For large memory:
@@ -57,11 +60,24 @@ const CMP: usize = 0;
 const MOV: usize = 1;
 const CMOVG: usize = 2;
 const CMOVL: usize = 3;
+// ISA=full: remaining conditional moves and xchg
+const CMOVGE: usize = 4;
+const CMOVLE: usize = 5;
+const CMOVE: usize = 6;
+const CMOVNE: usize = 7;
+const XCHG: usize = 8;
+// unsigned conditions (INPUTS=unsigned)
+const CMOVA: usize = 9;
+const CMOVB: usize = 10;
+const CMOVAE: usize = 11;
+const CMOVBE: usize = 12;
 const NUMBERS_U8: u8 = NUMBERS as u8;
+// after registers and flags: expected content of the registers 0..NUMBERS (0 = don't care)
+const TARGET: usize = REGS + 2;
 
 type Command = (usize, usize, usize);
 #[derive(Clone, Eq, PartialEq, Hash, Ord, PartialOrd, Debug, Copy)]
-struct Permutation([u8; REGS + 2]);
+struct Permutation([u8; REGS + 2 + NUMBERS]);
 type State = Vec<Permutation>;
 
 use std::ops::{Index, IndexMut};
@@ -103,9 +119,39 @@ impl std::fmt::Display for Permutation {
     }
 }
 
+// instruction set via environment variable ISA:
+// cmov (default): cmp, mov, cmovg, cmovl (INPUTS=unsigned: cmova, cmovb)
+// full: additionally cmovge, cmovle (cmovae, cmovbe), cmove, cmovne and xchg
+fn full_isa() -> bool {
+    match std::env::var("ISA").unwrap_or("cmov".to_string()).as_str() {
+        "cmov" => false,
+        "full" => true,
+        isa => panic!("Unknown ISA: {}", isa),
+    }
+}
+
+// order of the inputs via environment variable INPUTS:
+// signed (default): the values 1..n are the ranks in the signed order => signed conditions
+// unsigned: the ranks in the unsigned order => unsigned conditions
+// the other order is not determined by the ranks (inputs of different sign) => its conditions are not available
+// n=3: unsigned 11 with cmova (ISA=full too), the same search as signed
+fn unsigned_inputs() -> bool {
+    match std::env::var("INPUTS").unwrap_or("signed".to_string()).as_str() {
+        "signed" => false,
+        "unsigned" => true,
+        inputs => panic!("Unknown INPUTS: {}", inputs),
+    }
+}
+
 fn possible_commands() -> Vec<Command> {
     let mut commands = vec![];
-    for instr in &[MOV, CMOVG, CMOVL] {
+    let unsigned = unsigned_inputs();
+    let mut instrs = if unsigned { vec![MOV, CMOVA, CMOVB] } else { vec![MOV, CMOVG, CMOVL] };
+    if full_isa() {
+        instrs.extend(if unsigned { [CMOVAE, CMOVBE] } else { [CMOVGE, CMOVLE] });
+        instrs.extend([CMOVE, CMOVNE]);
+    }
+    for instr in &instrs {
         for to in 0..REGS {
             for from in 0..REGS {
                 if to != from {
@@ -117,11 +163,33 @@ fn possible_commands() -> Vec<Command> {
     for i in 0..REGS {
         for j in (i + 1)..REGS {
             commands.push((CMP, i, j));
+            // symmetric => only one order
+            if full_isa() {
+                commands.push((XCHG, i, j));
+            }
         }
     }
     commands
 }
 
+// x86 condition codes after cmp to, from (flags of to - from):
+// ZF = to == from, SF != OF = to < from (signed), CF = to < from (unsigned)
+// the lt and gt flags (equal = neither) compare the ranks => the conditions of the order of the inputs (see unsigned_inputs):
+// signed: lt = SF != OF, gt = !ZF && SF == OF; unsigned: lt = CF (below), gt = !CF && !ZF (above)
+fn condition(instr: usize, perm: &Permutation) -> bool {
+    let lt = perm[REGS + 0] == 1;
+    let gt = perm[REGS + 1] == 1;
+    match instr {
+        CMOVG | CMOVA => gt,
+        CMOVL | CMOVB => lt,
+        CMOVGE | CMOVAE => !lt,
+        CMOVLE | CMOVBE => !gt,
+        CMOVE => !lt && !gt,
+        CMOVNE => lt || gt,
+        _ => panic!("Not a conditional move"),
+    }
+}
+
 // transform a permutation according to a command
 fn apply(cmd: &Command, perm: &mut Permutation) {
     let (instr, to, from) = *cmd;
@@ -131,16 +199,12 @@ fn apply(cmd: &Command, perm: &mut Permutation) {
             perm[REGS + 1] = (perm[to] > perm[from]) as u8;
         }
         MOV => perm[to] = perm[from],
-        CMOVG => {
-            if perm[REGS + 1] == 1 {
-                perm[to] = perm[from];
-            }
-        }
-        CMOVL => {
-            if perm[REGS + 0] == 1 {
+        CMOVG | CMOVL | CMOVGE | CMOVLE | CMOVE | CMOVNE | CMOVA | CMOVB | CMOVAE | CMOVBE => {
+            if condition(instr, perm) {
                 perm[to] = perm[from];
             }
         }
+        XCHG => perm.0.swap(to, from),
         _ => panic!("Unknown instruction"),
     }
 }
@@ -183,23 +247,19 @@ fn apply_invers(cmd: &Command, perm: &Permutation) -> Vec<Permutation> {
                 new_perm
             }).collect::<Vec<_>>();
         }
-        CMOVG => {
-            let gt_flag = perm[REGS + 1];
-            if gt_flag == 0 {
-                // flag not set => noop
+        CMOVG | CMOVL | CMOVGE | CMOVLE | CMOVE | CMOVNE | CMOVA | CMOVB | CMOVAE | CMOVBE => {
+            // the flags are not changed by the move
+            if !condition(instr, perm) {
+                // condition not met => noop
                 return vec![perm.clone()];
             }
-            // flag set => was overwrite (same as with MOV)
+            // condition met => was overwrite (same as with MOV)
             return apply_invers(&(MOV, to, from), perm);
         }
-        CMOVL => {
-            let lt_flag = perm[REGS + 0];
-            if lt_flag == 0 {
-                // flag not set => noop
-                return vec![perm.clone()];
-            }
-            // flag set => was overwrite (same as with MOV)
-            return apply_invers(&(MOV, to, from), perm);
+        XCHG => {
+            let mut new_perm = perm.clone();
+            new_perm.0.swap(to, from);
+            return vec![new_perm];
         }
         _ => panic!("Unknown instruction"),
     }
@@ -220,10 +280,11 @@ fn apply_all(cmd: &Command, state: &State) -> State {
 
 // check if the state can never reach a solution
 // corresponds to delete-relaxed planning check
+// every value required by the spec has to be in some register
 fn viable(state: &State) -> bool {
     for perm in state {
-        for n in 1..=NUMBERS_U8 {
-            if !perm[0..REGS].contains(&n) {
+        for &n in &perm[TARGET..TARGET+NUMBERS] {
+            if n != 0 && !perm[0..REGS].contains(&n) {
                 return false;
             }
         }
@@ -231,6 +292,349 @@ fn viable(state: &State) -> bool {
     true
 }
 
+// the registers contain the output expected by the spec
+fn reached(perm: &Permutation) -> bool {
+    (0..NUMBERS).all(|i| perm[TARGET+i] == 0 || perm[i] == perm[TARGET+i])
+}
+
+// registers that are relevant for the spec (others are set to 0)
+fn output_part(perm: &Permutation) -> [u8; NUMBERS] {
+    let mut out = [0; NUMBERS];
+    for i in 0..NUMBERS {
+        if perm[TARGET+i] != 0 {
+            out[i] = perm[i];
+        }
+    }
+    out
+}
+
+// delete-relaxed planning heuristics
+// facts: register r holds value v, lt flag is b, gt flag is b
+// in the relaxation nothing is overwritten (a register can hold multiple values at once)
+// each permutation is its own relaxed task, the program has to solve all of them
+const FACTS: usize = REGS * (NUMBERS + 1) + 4;
+const LT0: usize = FACTS - 4;
+const LT1: usize = FACTS - 3;
+const GT0: usize = FACTS - 2;
+const GT1: usize = FACTS - 1;
+const UNREACHABLE: u8 = u8::MAX;
+
+fn reg_fact(reg: usize, value: u8) -> usize {
+    reg * (NUMBERS + 1) + value as usize
+}
+
+// a command instantiated for concrete values
+struct GroundAction {
+    cmd: Command,
+    pre: Vec<usize>,
+    add: Vec<usize>,
+}
+
+fn ground_actions() -> Vec<GroundAction> {
+    let mut actions = vec![];
+    for cmd in possible_commands() {
+        let (instr, to, from) = cmd;
+        for a in 0..=NUMBERS_U8 {
+            match instr {
+                MOV => actions.push(GroundAction{cmd, pre: vec![reg_fact(from, a)], add: vec![reg_fact(to, a)]}),
+                // the not-taken case does not add anything
+                CMOVG | CMOVA => actions.push(GroundAction{cmd, pre: vec![reg_fact(from, a), GT1], add: vec![reg_fact(to, a)]}),
+                CMOVL | CMOVB => actions.push(GroundAction{cmd, pre: vec![reg_fact(from, a), LT1], add: vec![reg_fact(to, a)]}),
+                CMOVGE | CMOVAE => actions.push(GroundAction{cmd, pre: vec![reg_fact(from, a), LT0], add: vec![reg_fact(to, a)]}),
+                CMOVLE | CMOVBE => actions.push(GroundAction{cmd, pre: vec![reg_fact(from, a), GT0], add: vec![reg_fact(to, a)]}),
+                CMOVE => actions.push(GroundAction{cmd, pre: vec![reg_fact(from, a), LT0, GT0], add: vec![reg_fact(to, a)]}),
+                // disjunctive condition => one action per flag
+                CMOVNE => {
+                    actions.push(GroundAction{cmd, pre: vec![reg_fact(from, a), LT1], add: vec![reg_fact(to, a)]});
+                    actions.push(GroundAction{cmd, pre: vec![reg_fact(from, a), GT1], add: vec![reg_fact(to, a)]});
+                }
+                XCHG => {
+                    for b in 0..=NUMBERS_U8 {
+                        actions.push(GroundAction{cmd, pre: vec![reg_fact(from, a), reg_fact(to, b)], add: vec![reg_fact(to, a), reg_fact(from, b)]});
+                    }
+                }
+                CMP => {
+                    for b in 0..=NUMBERS_U8 {
+                        let lt = if a < b { LT1 } else { LT0 };
+                        let gt = if a > b { GT1 } else { GT0 };
+                        actions.push(GroundAction{cmd, pre: vec![reg_fact(to, a), reg_fact(from, b)], add: vec![lt, gt]});
+                    }
+                }
+                _ => panic!("Unknown instruction"),
+            }
+        }
+    }
+    actions
+}
+
+// relaxed analysis of a single permutation
+#[derive(Clone)]
+struct Relaxed {
+    // admissible: cost of the most expensive goal fact
+    hmax: u8,
+    // sum of the goal fact costs
+    hadd: u8,
+    // relaxed plan extracted via the h_add best supporters (for h_FF)
+    plan: Vec<Command>,
+}
+
+fn relaxed_analysis(perm: &Permutation, actions: &[GroundAction]) -> Relaxed {
+    // fixpoint over the fact costs (h_max and h_add in parallel)
+    let mut cost_max = [UNREACHABLE; FACTS];
+    let mut cost_add = [UNREACHABLE; FACTS];
+    let mut supporter: [Option<usize>; FACTS] = [None; FACTS];
+    for r in 0..REGS {
+        cost_max[reg_fact(r, perm[r])] = 0;
+        cost_add[reg_fact(r, perm[r])] = 0;
+    }
+    for fact in [if perm[REGS + 0] == 1 { LT1 } else { LT0 }, if perm[REGS + 1] == 1 { GT1 } else { GT0 }] {
+        cost_max[fact] = 0;
+        cost_add[fact] = 0;
+    }
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (i, action) in actions.iter().enumerate() {
+            if action.pre.iter().any(|&f| cost_max[f] == UNREACHABLE) {
+                continue;
+            }
+            let pre_max = action.pre.iter().map(|&f| cost_max[f]).max().unwrap();
+            let pre_add = action.pre.iter().map(|&f| cost_add[f] as u32).sum::<u32>().min(UNREACHABLE as u32 - 1) as u8;
+            for &f in &action.add {
+                if pre_max + 1 < cost_max[f] {
+                    cost_max[f] = pre_max + 1;
+                    changed = true;
+                }
+                if pre_add + 1 < cost_add[f] {
+                    cost_add[f] = pre_add + 1;
+                    supporter[f] = Some(i);
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    let goals = (0..NUMBERS)
+        .filter(|&i| perm[TARGET+i] != 0)
+        .map(|i| reg_fact(i, perm[TARGET+i]))
+        .collect::<Vec<_>>();
+    if goals.iter().any(|&g| cost_max[g] == UNREACHABLE) {
+        return Relaxed{hmax: UNREACHABLE, hadd: UNREACHABLE, plan: vec![]};
+    }
+    let hmax = goals.iter().map(|&g| cost_max[g]).max().unwrap_or(0);
+    let hadd = goals.iter().map(|&g| cost_add[g] as u32).sum::<u32>().min(UNREACHABLE as u32 - 1) as u8;
+
+    // backchain from the goals over the best supporters
+    let mut plan = vec![];
+    let mut open = goals;
+    let mut done = [false; FACTS];
+    while let Some(f) = open.pop() {
+        if done[f] {
+            continue;
+        }
+        done[f] = true;
+        if let Some(i) = supporter[f] {
+            plan.push(actions[i].cmd);
+            open.extend(actions[i].pre.iter());
+        }
+    }
+    plan.sort();
+    plan.dedup();
+    Relaxed{hmax, hadd, plan}
+}
+
+// heuristic for the A* search
+// given via environment variable HEURISTIC: perm (default), hmax, hadd, hff
+// hmax turns off the min_perm_count cut => the first solution is optimal
+// n=3: hmax no solution after 5min (memory exhausted with 3.6M open states), SEARCH=greedy with hadd or hff 11 in <1s
+#[derive(PartialEq)]
+enum Heuristic {
+    // number of different outputs
+    PermCount,
+    // admissible
+    HMax,
+    HAdd,
+    HFF,
+}
+
+impl Heuristic {
+    fn from_env() -> Heuristic {
+        match std::env::var("HEURISTIC").unwrap_or("perm".to_string()).as_str() {
+            "perm" => Heuristic::PermCount,
+            "hmax" => Heuristic::HMax,
+            "hadd" => Heuristic::HAdd,
+            "hff" => Heuristic::HFF,
+            h => panic!("Unknown heuristic: {}", h),
+        }
+    }
+
+    // only h_max never overestimates
+    fn admissible(&self) -> bool {
+        *self == Heuristic::HMax
+    }
+}
+
+// order of the queue
+// given via environment variable SEARCH: astar (default), greedy
+#[derive(PartialEq)]
+enum SearchMode {
+    // length + heuristic
+    AStar,
+    // heuristic only (fast, programs are not necessarily optimal)
+    Greedy,
+}
+
+impl SearchMode {
+    fn from_env() -> SearchMode {
+        match std::env::var("SEARCH").unwrap_or("astar".to_string()).as_str() {
+            "astar" => SearchMode::AStar,
+            "greedy" => SearchMode::Greedy,
+            m => panic!("Unknown search mode: {}", m),
+        }
+    }
+}
+
+// combine the relaxed analysis of all permutations in a state
+// h_max, h_add: the hardest permutation
+// h_FF: a command in the relaxed plans of several permutations is only counted once
+// permutations reappear in many states => cache the analysis
+fn relaxed_heuristic(heuristic: &Heuristic, state: &State, actions: &[GroundAction], cache: &mut HashMap<Permutation, Relaxed>) -> u8 {
+    let mut plan = HashSet::new();
+    let mut h = 0;
+    for perm in state {
+        let relaxed = cache.entry(*perm).or_insert_with(|| relaxed_analysis(perm, actions));
+        match heuristic {
+            Heuristic::HMax => h = h.max(relaxed.hmax),
+            Heuristic::HAdd => h = h.max(relaxed.hadd),
+            Heuristic::HFF => {
+                if relaxed.hmax == UNREACHABLE {
+                    return UNREACHABLE;
+                }
+                plan.extend(relaxed.plan.iter().copied());
+            }
+            Heuristic::PermCount => panic!("Not a relaxed heuristic"),
+        }
+    }
+    if *heuristic == Heuristic::HFF {
+        h = plan.len().min(UNREACHABLE as usize - 1) as u8;
+    }
+    h
+}
+
+// partial program the search has to complete (SKETCH environment variable: file with one item per line)
+//   MOV 4 1          fixed instruction
+//   CMOVG|CMOVL ? 4  hole: alternatives (|) or anything (?) for opcode and registers
+//   ?                any instruction
+//   * 3              up to 3 arbitrary instructions
+//   *                any number of arbitrary instructions
+// registers are 1-indexed, cmp and xchg only with the smaller register first (see possible_commands)
+// without a sketch the whole program is free
+enum SketchItem {
+    Slot(Vec<Command>),
+    Free(Option<u8>),
+}
+
+// (item, instructions used in a bounded free range)
+type SketchPos = (u8, u8);
+
+struct Sketch {
+    items: Vec<SketchItem>,
+}
+
+impl Sketch {
+    fn from_env(possible_cmds: &[Command]) -> Sketch {
+        match std::env::var("SKETCH") {
+            Ok(file) => Sketch::read(&file, possible_cmds),
+            Err(_) => Sketch { items: vec![SketchItem::Free(None)] },
+        }
+    }
+
+    fn read(file: &str, possible_cmds: &[Command]) -> Sketch {
+        let content = std::fs::read_to_string(file).expect("Could not read sketch");
+        let mut items = vec![];
+        for line in content.lines() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let parts = line.split_whitespace().collect::<Vec<_>>();
+            let item = match parts.as_slice() {
+                ["*"] => SketchItem::Free(None),
+                ["*", max] => SketchItem::Free(Some(max.parse::<u8>().expect("Invalid range length"))),
+                ["?"] => SketchItem::Slot(possible_cmds.to_vec()),
+                [instr, to, from] => {
+                    let instrs = instr.to_uppercase().split('|').map(|i| match i {
+                        "?" => None,
+                        "CMP" => Some(CMP),
+                        "MOV" => Some(MOV),
+                        "CMOVG" => Some(CMOVG),
+                        "CMOVL" => Some(CMOVL),
+                        "CMOVGE" => Some(CMOVGE),
+                        "CMOVLE" => Some(CMOVLE),
+                        "CMOVE" => Some(CMOVE),
+                        "CMOVNE" => Some(CMOVNE),
+                        "XCHG" => Some(XCHG),
+                        "CMOVA" => Some(CMOVA),
+                        "CMOVB" => Some(CMOVB),
+                        "CMOVAE" => Some(CMOVAE),
+                        "CMOVBE" => Some(CMOVBE),
+                        _ => panic!("Unknown instruction in sketch: {}", line),
+                    }).collect::<Vec<_>>();
+                    let regs = |pattern: &str| pattern.split('|').map(|r| match r {
+                        "?" => None,
+                        _ => Some(r.parse::<usize>().ok().filter(|&r| r >= 1 && r <= REGS)
+                            .unwrap_or_else(|| panic!("Unknown register in sketch: {}", line)) - 1),
+                    }).collect::<Vec<_>>();
+                    let (tos, froms) = (regs(to), regs(from));
+                    let matches = |pattern: &[Option<usize>], x: usize| pattern.iter().any(|p| p.map_or(true, |p| p == x));
+                    let cmds = possible_cmds.iter()
+                        .filter(|&&(i, t, f)| matches(&instrs, i) && matches(&tos, t) && matches(&froms, f))
+                        .copied()
+                        .collect::<Vec<_>>();
+                    if cmds.is_empty() {
+                        panic!("No instruction matches: {}", line);
+                    }
+                    SketchItem::Slot(cmds)
+                }
+                _ => panic!("Invalid sketch line: {}", line),
+            };
+            items.push(item);
+        }
+        Sketch { items }
+    }
+
+    // positions reachable without an instruction (skipping free ranges)
+    fn closure(&self, pos: SketchPos) -> Vec<SketchPos> {
+        let mut positions = vec![pos];
+        let (mut item, _) = pos;
+        while let Some(SketchItem::Free(_)) = self.items.get(item as usize) {
+            item += 1;
+            positions.push((item, 0));
+        }
+        positions
+    }
+
+    // allowed commands at a position together with the position after them
+    fn successors<'a>(&'a self, pos: SketchPos, possible_cmds: &'a [Command]) -> Vec<(&'a [Command], SketchPos)> {
+        let mut successors = vec![];
+        for (item, used) in self.closure(pos) {
+            match self.items.get(item as usize) {
+                Some(SketchItem::Slot(cmds)) => successors.push((cmds.as_slice(), (item + 1, 0))),
+                // unbounded ranges do not count (keeps the number of positions finite)
+                Some(SketchItem::Free(None)) => successors.push((possible_cmds, (item, 0))),
+                Some(SketchItem::Free(Some(max))) if used < *max => successors.push((possible_cmds, (item, used + 1))),
+                _ => {}
+            }
+        }
+        successors
+    }
+
+    // the rest of the sketch can be empty
+    fn accepts(&self, pos: SketchPos) -> bool {
+        self.closure(pos).iter().any(|&(item, _)| item as usize == self.items.len())
+    }
+}
+
 fn show_command(cmd: &Command) -> String {
     let (instr, to, from) = *cmd;
     // 1-indexed to stay consistent with minizinc
@@ -241,6 +645,15 @@ fn show_command(cmd: &Command) -> String {
         MOV => format!("MOV {} {}", to, from),
         CMOVG => format!("CMOVG {} {}", to, from),
         CMOVL => format!("CMOVL {} {}", to, from),
+        CMOVGE => format!("CMOVGE {} {}", to, from),
+        CMOVLE => format!("CMOVLE {} {}", to, from),
+        CMOVE => format!("CMOVE {} {}", to, from),
+        CMOVNE => format!("CMOVNE {} {}", to, from),
+        XCHG => format!("XCHG {} {}", to, from),
+        CMOVA => format!("CMOVA {} {}", to, from),
+        CMOVB => format!("CMOVB {} {}", to, from),
+        CMOVAE => format!("CMOVAE {} {}", to, from),
+        CMOVBE => format!("CMOVBE {} {}", to, from),
         _ => panic!("Unknown instruction"),
     }
 }
@@ -362,7 +775,14 @@ fn extract_program(node: &Node) -> Vec<Command> {
 
 fn main() {
     let possible_cmds = possible_commands();
-    let permutations: Vec<Vec<u8>> = (1..=NUMBERS_U8).permutations(NUMBERS).collect(); 
+    let sketch = Sketch::from_env(&possible_cmds);
+    let spec = Spec::from_env();
+    let heuristic_kind = Heuristic::from_env();
+    let search_mode = SearchMode::from_env();
+    let ground = ground_actions();
+    let mut relaxed_cache = HashMap::new();
+    // only inputs that are constrained by the spec
+    let permutations = inputs(&spec);
     let init_perm_count = permutations.len();
 
     // let perm_count = 6;
@@ -394,7 +814,7 @@ fn main() {
             }
         }
         println!("Computed swaps for {} permutations", swaps_needed.len());
-        if swaps_needed.len() != init_perm_count {
+        if swaps_needed.len() != (1..=NUMBERS).product::<usize>() {
             panic!("Not all permutations found");
         }
     }
@@ -407,7 +827,7 @@ fn main() {
     let mut useful_instructions = HashMap::new();
     {
         let mut frontier = VecDeque::new();
-        let mut init_perm = Permutation([0; REGS + 2]);
+        let mut init_perm = Permutation([0; REGS + 2 + NUMBERS]);
         for (i, x) in init_perm[0..NUMBERS].iter_mut().enumerate() {
             *x = (i+1) as u8;
         }
@@ -475,6 +895,13 @@ fn main() {
     println!("n = {}", NUMBERS);
     println!("max_len = {}", MAX_LEN);
     println!("swaps = {}", SWAPS);
+    println!("isa = {}", std::env::var("ISA").unwrap_or("cmov".to_string()));
+    println!("order = {}", if unsigned_inputs() { "unsigned" } else { "signed" });
+    println!("spec = {}", std::env::var("SPEC").unwrap_or("sort".to_string()));
+    println!("heuristic = {}", std::env::var("HEURISTIC").unwrap_or("perm".to_string()));
+    println!("search = {}", std::env::var("SEARCH").unwrap_or("astar".to_string()));
+    println!("sketch = {} ({} items)", std::env::var("SKETCH").unwrap_or("none".to_string()), sketch.items.len());
+    println!("inputs = {}", init_perm_count);
 
 
     let length_map = sled::open(path).unwrap();
@@ -483,19 +910,27 @@ fn main() {
     // we use RC to avoid cloning the state
     let initial_state: Rc<State> = Rc::new(permutations
         .iter()
-        .map(|p| {
-            let mut perm = Permutation([0; REGS + 2]);
+        .map(|(p, target)| {
+            let mut perm = Permutation([0; REGS + 2 + NUMBERS]);
             for (i, &x) in p.iter().enumerate() {
                 perm[i] = x;
             }
+            perm[TARGET..TARGET+NUMBERS].copy_from_slice(target);
             perm
         })
         .collect());
 
-    length_map.insert(state_positions(&initial_state), vec![0 as u8]).unwrap();
+    // the position in the sketch is part of the search state
+    let state_key = |state: &State, pos: SketchPos| {
+        let mut key = state_positions(state);
+        key.extend([pos.0, pos.1]);
+        key
+    };
+
+    length_map.insert(state_key(&initial_state, (0,0)), vec![0 as u8]).unwrap();
 
     let node0 = Node{cmd: (0,0,0), prev: None};
-    queue.push((node0,Rc::clone(&initial_state),0 as u8), Reverse(0));
+    queue.push((node0,Rc::clone(&initial_state),0 as u8,(0 as u8,0 as u8)), Reverse(0));
 
     let mut visited : u64 = 0;
     let mut duplicate : u64 = 0;
@@ -547,7 +982,7 @@ fn main() {
     let mut min_perm_count = [init_perm_count; (MAX_LEN as usize)+1];
 
     let start = std::time::Instant::now();
-    while let Some(((prg,state,length), _)) = queue.pop() {
+    while let Some(((prg,state,length,pos), _)) = queue.pop() {
         visited += 1;
         if visited % 100000 == 0 {
             print!("Open: {}, ", queue.len());
@@ -581,7 +1016,7 @@ fn main() {
         // but heuristic is useful overall
         // for only one solution we could cut for <= if the = case is another predecessor
         // TODO: possible solution: keep track of queue, store length separately
-        let state_repr = state_positions(&state);
+        let state_repr = state_key(&state, pos);
         if let Some(state_len_vec) = length_map.get(&state_repr).unwrap() {
             if state_len_vec[0] < length {
                 duplicate += 1;
@@ -591,7 +1026,7 @@ fn main() {
 
 
         // if state.iter().all(|p| p[0..NUMBERS] == state[0][0..NUMBERS]) {
-        if state.iter().all(|p| p[0..NUMBERS] == (1..=NUMBERS_U8).collect::<Vec<_>>()) {
+        if state.iter().all(reached) && sketch.accepts(pos) {
             // println!("Found solution: {:?} of length: {}", state, length);
             if solution_count == 0 {
                 println!("Found first solution: {:?} of length: {}", state, length);
@@ -638,15 +1073,18 @@ fn main() {
 
         let prev_box = Some(Box::new(prg));
 
-        let commands = &possible_cmds;
         // let commands = 
         //     state.iter().flat_map(|p| useful_instructions.get(p).unwrap_or(&possible_cmds).iter())
         //     .unique()
         //     // .cloned()
         //     .collect::<Vec<_>>();
 
+        // only completions consistent with the sketch
         // for cmd in &possible_cmds {
-        for cmd in commands {
+        let commands = sketch.successors(pos, &possible_cmds)
+            .into_iter()
+            .flat_map(|(cmds, new_pos)| cmds.iter().map(move |cmd| (cmd, new_pos)));
+        for (cmd, new_pos) in commands {
             let new_state = Rc::new(apply_all(&cmd, &state));
             let new_length = length + 1;
 
@@ -668,7 +1106,7 @@ fn main() {
                 continue;
             }
 
-            let new_perm_count = new_state.iter().map(|p| &p[0..NUMBERS]).unique().count();
+            let new_perm_count = new_state.iter().map(output_part).unique().count();
 
             // TODO: why is this not subsumed by a*
             // why is it so good
@@ -717,7 +1155,8 @@ fn main() {
         // *5/4  4.88s
         // *1    2.22s  (689s for n=5)
         // *4    > 140s
-        if min_perm_count[length as usize] < new_perm_count {
+        // not valid in general => only without an admissible heuristic
+        if !heuristic_kind.admissible() && min_perm_count[length as usize] < new_perm_count {
             cut += 1;
             continue;
         }
@@ -750,7 +1189,7 @@ fn main() {
 
 
             // if already found with smaller length, skip
-            let state_repr = state_positions(&new_state);
+            let state_repr = state_key(&new_state, new_pos);
             if let Some(old_length_vec) = length_map.get(&state_repr).unwrap() {
                 let old_length = old_length_vec[0];
                 // <= is much faster and valid to find one solution
@@ -777,19 +1216,35 @@ fn main() {
                 - use the number of instructions needed per permutation (precomputed -- relaxed plan ignoring dependencies)
 
                 However, these seem to be slower (or not much faster) than the permutation count heuristic
+
+                The delete-relaxed heuristics (h_max, h_add, h_FF) can be selected via HEURISTIC
              */
 
 
-            let heuristic = new_perm_count as u8;
+            let heuristic = match heuristic_kind {
+                // saturate below UNREACHABLE (n=6 has 720 permutations)
+                Heuristic::PermCount => new_perm_count.min(UNREACHABLE as usize - 1) as u8,
+                _ => {
+                    let h = relaxed_heuristic(&heuristic_kind, &new_state, &ground, &mut relaxed_cache);
+                    if h == UNREACHABLE {
+                        cut += 1;
+                        continue;
+                    }
+                    h
+                }
+            };
             // let heuristic = (new_state.len()) as u8;
             // try with instruction heuristic instead
             // let heuristic = new_state.iter().map(|p| instructions_needed[p]).max().unwrap();
             // let heuristic = 0;
 
-            let new_score = new_length + heuristic;
             // we can use A* (f+h) or Dijkstra (f) or greedy (h)
+            let new_score = match search_mode {
+                SearchMode::AStar => new_length.saturating_add(heuristic),
+                SearchMode::Greedy => heuristic,
+            };
             let prg = Node{cmd: *cmd, prev: prev_box.clone()};
-            queue.push((prg,Rc::clone(&new_state),new_length), Reverse(new_score));
+            queue.push((prg,Rc::clone(&new_state),new_length,new_pos), Reverse(new_score));
         }
     }
 
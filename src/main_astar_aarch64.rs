@@ -0,0 +1,429 @@
+use itertools::Itertools;
+use std::ops::Range;
+// has largest value at the top
+use priority_queue::PriorityQueue;
+use std::cmp::Reverse;
+use std::rc::Rc;
+use std::io::Write;
+mod common;
+use common::{inputs, Spec};
+
+/*
+AArch64 instruction model
+
+scalar (general purpose registers w0, w1, ...):
+  cmp wn, wm              sets the flags (lt, gt like the x86 model)
+  csel wd, wn, wm, cond   wd = cond ? wn : wm (three operands, wd may be any register)
+  mov wd, wn
+  conditions: lt and gt suffice (le = !gt => csel wd, wn, wm, le = csel wd, wm, wn, gt)
+neon (vector registers v0, v1, ..., one value per lane => lanes of a register sort independently):
+  umin vd.4s, vn.4s, vm.4s
+  umax vd.4s, vn.4s, vm.4s
+  mov vd.16b, vn.16b
+mixed: both + fmov between lane 0 of a vector register and a general purpose register
+
+ISA environment variable: scalar (default) | neon | mixed
+The input is in w0.. and v0.. (AAPCS: arguments in w0-w7), the output in the same registers
+(mixed: either class like main_astar_mixed.rs), what is expected there is given by SPEC (see common.rs).
+
+A* with admissible heuristic (see main_iterative.rs) => the first solution is optimal.
+Commands have three operands: (instr, d, n, m), two-operand commands repeat n.
+*/
+
+// n=3: scalar 9 (5s), neon 6, mixed 6 (11s)
+const NUMBERS: usize = 3;
+const MAX_LEN: u8 = 12;
+// const NUMBERS: usize = 4;
+// const MAX_LEN: u8 = 20;
+const SWAPS: usize = 1;
+const REGS: usize = NUMBERS + SWAPS;
+const VECREGS: usize = NUMBERS + SWAPS;
+const VECOFFSET: usize = REGS + 2; // register + flags
+
+const CMP: usize = 0; // only general purpose registers
+const MOV: usize = 1; // only general purpose registers
+const CSELLT: usize = 2; // only general purpose registers
+const CSELGT: usize = 3; // only general purpose registers
+
+const UMIN: usize = 4; // only vector registers
+const UMAX: usize = 5; // only vector registers
+const VMOV: usize = 6; // between vector registers
+const FMOV: usize = 7; // general purpose register <-> vector register
+const NUMBERS_U8: u8 = NUMBERS as u8;
+// after the vector registers: expected content of the output registers (0 = don't care)
+const TARGET: usize = VECOFFSET + VECREGS;
+
+type Command = (usize, usize, usize, usize);
+#[derive(Clone, Eq, PartialEq, Hash, Ord, PartialOrd, Debug, Copy)]
+struct Permutation([u8; REGS + 2 + VECREGS + NUMBERS]);
+type State = Vec<Permutation>;
+
+use std::ops::{Index, IndexMut};
+
+impl Index<usize> for Permutation {
+    type Output = u8;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+impl IndexMut<usize> for Permutation {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.0[index]
+    }
+}
+
+impl Index<Range<usize>> for Permutation {
+    type Output = [u8];
+
+    fn index(&self, index: Range<usize>) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+impl IndexMut<Range<usize>> for Permutation {
+    fn index_mut(&mut self, index: Range<usize>) -> &mut Self::Output {
+        &mut self.0[index]
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Isa {
+    Scalar,
+    Neon,
+    Mixed,
+}
+
+impl Isa {
+    fn from_env() -> Isa {
+        let isa = std::env::var("ISA").unwrap_or("scalar".to_string());
+        match isa.as_str() {
+            "scalar" => Isa::Scalar,
+            "neon" => Isa::Neon,
+            "mixed" => Isa::Mixed,
+            _ => panic!("Unknown ISA: {}", isa),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Isa::Scalar => "scalar",
+            Isa::Neon => "neon",
+            Isa::Mixed => "mixed",
+        }
+    }
+
+    fn gp_regs(&self) -> Range<usize> {
+        match self {
+            Isa::Neon => 0..0,
+            _ => 0..REGS,
+        }
+    }
+
+    fn vec_regs(&self) -> Range<usize> {
+        match self {
+            Isa::Scalar => 0..0,
+            _ => VECOFFSET..VECOFFSET+VECREGS,
+        }
+    }
+
+    // registers holding the input and (one of them) the output
+    fn output_regs(&self) -> Vec<Range<usize>> {
+        match self {
+            Isa::Scalar => vec![0..NUMBERS],
+            Isa::Neon => vec![VECOFFSET..VECOFFSET+NUMBERS],
+            Isa::Mixed => vec![0..NUMBERS, VECOFFSET..VECOFFSET+NUMBERS],
+        }
+    }
+}
+
+fn possible_commands(isa: Isa) -> Vec<Command> {
+    let gp = isa.gp_regs();
+    let vec = isa.vec_regs();
+    let mut commands = vec![];
+    for n in gp.clone() {
+        for m in (n + 1)..gp.end {
+            commands.push((CMP, n, n, m));
+        }
+    }
+    for d in gp.clone() {
+        for n in gp.clone() {
+            if d != n {
+                commands.push((MOV, d, n, n));
+            }
+            // n == m is a mov
+            for m in gp.clone() {
+                if n != m {
+                    commands.push((CSELLT, d, n, m));
+                    commands.push((CSELGT, d, n, m));
+                }
+            }
+        }
+    }
+    for d in vec.clone() {
+        for n in vec.clone() {
+            if d != n {
+                commands.push((VMOV, d, n, n));
+            }
+            // umin/umax are commutative
+            for m in (n + 1)..vec.end {
+                commands.push((UMIN, d, n, m));
+                commands.push((UMAX, d, n, m));
+            }
+        }
+    }
+    if isa == Isa::Mixed {
+        for reg in gp.clone() {
+            for vec_reg in vec.clone() {
+                commands.push((FMOV, reg, vec_reg, vec_reg));
+                commands.push((FMOV, vec_reg, reg, reg));
+            }
+        }
+    }
+    commands
+}
+
+// transform a permutation according to a command
+fn apply(cmd: &Command, perm: &mut Permutation) {
+    let (instr, d, n, m) = *cmd;
+    match instr {
+        CMP => {
+            perm[REGS + 0] = (perm[n] < perm[m]) as u8;
+            perm[REGS + 1] = (perm[n] > perm[m]) as u8;
+        }
+        MOV | VMOV | FMOV => perm[d] = perm[n],
+        CSELLT => perm[d] = if perm[REGS + 0] == 1 { perm[n] } else { perm[m] },
+        CSELGT => perm[d] = if perm[REGS + 1] == 1 { perm[n] } else { perm[m] },
+        UMIN => perm[d] = perm[n].min(perm[m]),
+        UMAX => perm[d] = perm[n].max(perm[m]),
+        _ => panic!("Unknown instruction"),
+    }
+}
+
+// map a command over all permutations in a state
+fn apply_all(cmd: &Command, state: &State) -> State {
+    let mut new_state = Vec::new();
+    for perm in state {
+        let mut new_perm = perm.clone();
+        apply(cmd, &mut new_perm);
+        new_state.push(new_perm);
+    }
+    new_state.sort();
+    new_state.dedup();
+    new_state
+}
+
+// check if the state can never reach a solution
+// corresponds to delete-relaxed planning check
+// (registers outside of the ISA stay 0, flags are 0 or 1)
+// every value required by the spec has to be in some register
+fn viable(state: &State) -> bool {
+    for perm in state {
+        for &n in &perm[TARGET..TARGET+NUMBERS] {
+            if n != 0 && !perm[0..REGS].contains(&n) && !perm[VECOFFSET..VECOFFSET+VECREGS].contains(&n) {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+fn reg_name(reg: usize) -> String {
+    if reg < REGS {
+        format!("w{}", reg)
+    } else {
+        format!("v{}", reg - VECOFFSET)
+    }
+}
+
+// GNU assembler syntax (destination first)
+fn show_command(cmd: &Command) -> String {
+    let (instr, d_reg, n_reg, m_reg) = *cmd;
+    let (d, n, m) = (reg_name(d_reg), reg_name(n_reg), reg_name(m_reg));
+    match instr {
+        CMP => format!("cmp {}, {}", n, m),
+        MOV => format!("mov {}, {}", d, n),
+        CSELLT => format!("csel {}, {}, {}, lt", d, n, m),
+        CSELGT => format!("csel {}, {}, {}, gt", d, n, m),
+        UMIN => format!("umin {}.4s, {}.4s, {}.4s", d, n, m),
+        UMAX => format!("umax {}.4s, {}.4s, {}.4s", d, n, m),
+        VMOV => format!("mov {}.16b, {}.16b", d, n),
+        // lane 0 of the vector register is the s register
+        FMOV if d_reg < REGS => format!("fmov {}, s{}", d, n_reg - VECOFFSET),
+        FMOV => format!("fmov s{}, {}", d_reg - VECOFFSET, n),
+        _ => panic!("Unknown instruction"),
+    }
+}
+
+// linked list to store the commands and pointer to last element
+// shared prefixes (Rc instead of Box) as in main_registers.rs
+#[derive(Clone, Eq, PartialEq, Hash)]
+struct Node {
+    cmd: Command,
+    prev: Option<Rc<Node>>,
+}
+
+// for each permutation, take out register values, concat => serializable byte array
+fn state_positions(state: &State) -> Vec<u8> {
+    state.iter().flat_map(|p| p.0).collect()
+}
+
+fn extract_program(node: &Node) -> Vec<Command> {
+    let mut cmds = vec![];
+    let mut node = node;
+    while let Some(prev) = &node.prev {
+        cmds.push(node.cmd);
+        node = prev;
+    }
+    cmds.reverse();
+    cmds
+}
+
+// the output registers regs contain the output expected by the spec
+fn reached(state: &State, regs: &Range<usize>) -> bool {
+    state.iter().all(|p| regs.clone().enumerate().all(|(i, r)| p[TARGET+i] == 0 || p[r] == p[TARGET+i]))
+}
+
+// each instruction writes at most one register
+// => every output register that is wrong in at least one permutation needs one more instruction
+// (mixed: the better of both register classes)
+fn admissible_heuristic(state: &State, isa: Isa) -> u8 {
+    isa.output_regs()
+        .iter()
+        .map(|regs| regs.clone().enumerate().filter(|&(i, r)| state.iter().any(|p| p[TARGET+i] != 0 && p[r] != p[TARGET+i])).count())
+        .min()
+        .unwrap() as u8
+}
+
+// find unused sled-mapX file in a temporary directory (_CONDOR_SCRATCH_DIR or /tmp/ else)
+fn open_length_map() -> sled::Db {
+    let tmp_dir = std::env::var("_CONDOR_SCRATCH_DIR").unwrap_or("/tmp".to_string());
+    let mut i = 0;
+    let mut path = format!("{}/sled-map{}", tmp_dir, i);
+    while std::path::Path::new(&path).exists() {
+        i += 1;
+        path = format!("{}/sled-map{}", tmp_dir, i);
+    }
+    println!("Using sled map: {}", path);
+    sled::open(path).unwrap()
+}
+
+fn main() {
+    let isa = Isa::from_env();
+    let possible_cmds = possible_commands(isa);
+    let spec = Spec::from_env();
+    // only inputs that are constrained by the spec
+    let permutations = inputs(&spec);
+
+    println!("n = {}", NUMBERS);
+    println!("max_len = {}", MAX_LEN);
+    println!("swaps = {}", SWAPS);
+    println!("isa = {}", isa.name());
+    println!("spec = {}", std::env::var("SPEC").unwrap_or("sort".to_string()));
+    println!("inputs = {}", permutations.len());
+    println!("instruction count = {}", possible_cmds.len());
+
+    // input in the registers of the ISA (mixed: both classes)
+    let initial_state: Rc<State> = Rc::new(permutations
+        .iter()
+        .map(|(p, target)| {
+            let mut perm = Permutation([0; REGS + 2 + VECREGS + NUMBERS]);
+            for regs in isa.output_regs() {
+                for (i, &x) in p.iter().enumerate() {
+                    perm[regs.start + i] = x;
+                }
+            }
+            perm[TARGET..TARGET+NUMBERS].copy_from_slice(target);
+            perm
+        })
+        .sorted()
+        .collect());
+
+    let length_map = open_length_map();
+    length_map.insert(state_positions(&initial_state), vec![0 as u8]).unwrap();
+    let mut queue = PriorityQueue::new();
+    let node0 = Node{cmd: (0,0,0,0), prev: None};
+    queue.push((node0,Rc::clone(&initial_state),0 as u8), Reverse(admissible_heuristic(&initial_state, isa)));
+
+    let mut visited : u64 = 0;
+    let mut duplicate : u64 = 0;
+    let mut cut : u64 = 0;
+    let mut solution = None;
+    let start = std::time::Instant::now();
+    while let Some(((prg,state,length), Reverse(score))) = queue.pop() {
+        visited += 1;
+        if visited % 100000 == 0 {
+            print!("Open: {}, ", queue.len());
+            print!("Visited: {}, ", visited);
+            print!("Duplicate: {}, ", duplicate);
+            print!("Cut: {}, ", cut);
+            print!("Current length: {}, ", length);
+            print!("Lower bound: {}, ", score);
+            print!("Time: {:?}", start.elapsed());
+            println!("");
+        }
+
+        if let Some(state_len_vec) = length_map.get(state_positions(&state)).unwrap() {
+            if state_len_vec[0] < length {
+                duplicate += 1;
+                continue;
+            }
+        }
+
+        if isa.output_regs().iter().any(|regs| reached(&state, regs)) {
+            solution = Some(extract_program(&prg));
+            break;
+        }
+
+        let prev_rc = Some(Rc::new(prg));
+        for cmd in &possible_cmds {
+            let new_state = Rc::new(apply_all(cmd, &state));
+            let new_length = length + 1;
+
+            if !viable(&new_state) {
+                cut += 1;
+                continue;
+            }
+
+            let new_score = new_length + admissible_heuristic(&new_state, isa);
+            if new_score > MAX_LEN {
+                cut += 1;
+                continue;
+            }
+
+            let state_repr = state_positions(&new_state);
+            if let Some(old_length_vec) = length_map.get(&state_repr).unwrap() {
+                if old_length_vec[0] <= new_length {
+                    duplicate += 1;
+                    continue;
+                }
+            }
+            length_map.insert(state_repr, vec![new_length]).unwrap();
+
+            let prg = Node{cmd: *cmd, prev: prev_rc.clone()};
+            queue.push((prg,Rc::clone(&new_state),new_length), Reverse(new_score));
+        }
+    }
+    println!("Visited: {}, Duplicate: {}, Cut: {}", visited, duplicate, cut);
+
+    match solution {
+        Some(cmds) => {
+            println!("Found program of length {}:", cmds.len());
+            for cmd in &cmds {
+                println!("{}", show_command(cmd));
+            }
+            if let Ok(dir) = std::env::var("SOLUTION_DIR") {
+                let subdir = format!("{}/{}_aarch64_{}", dir, NUMBERS, isa.name());
+                std::fs::create_dir_all(&subdir).unwrap();
+                let mut file = std::fs::File::create(format!("{}/solution.s", subdir)).unwrap();
+                for cmd in &cmds {
+                    writeln!(file, "{}", show_command(cmd)).unwrap();
+                }
+                println!("Stored solution in: {}", subdir);
+            }
+        }
+        None => println!("No program of length <= {}", MAX_LEN),
+    }
+    println!("Elapsed: {:?}", start.elapsed());
+}
@@ -0,0 +1,489 @@
+use itertools::Itertools;
+use std::collections::HashMap;
+use std::ops::Range;
+use rand::Rng;
+// has largest value at the top
+use priority_queue::PriorityQueue;
+use std::cmp::Reverse;
+use std::rc::Rc;
+use std::io::Write;
+
+/*
+Branchless sorting with arithmetic instead of cmov (x86, 32 bit signed, destructive two-operand forms)
+
+  mov d, s     add d, s     sub d, s     and d, s     or d, s     xor d, s
+  neg d        sar d, 31    cmp d, s     setl d      setg d
+e.g. min/max via the sign of the difference (sub + sar + and) or via a mask (cmp + setg + neg + and + xor).
+
+The values are no longer a permutation of 1..n (x + y is not an input value)
+=> a register holds a concrete i32 for each test vector.
+Only sorting: the kernels of SPEC (common.rs) are given as positions in a permutation of 1..n,
+the test vectors here are random i32 (a table could not be checked on them).
+Flags as in main_astar.rs (lt, gt of the last flag-setting instruction, exact even on overflow like SF != OF),
+sar leaves them undefined (the count is not 1) and a setcc on undefined flags is cut.
+setcc only writes the low byte (like on x86, zero the register first).
+Scratch registers start with a different value in every test vector (undefined content),
+a candidate is checked with several scratch contents per random input.
+
+Counterexample guided like main_cegis.rs: the search starts with the permutations of few values,
+a candidate is checked on RANDOM_TESTS random inputs, a failing input (all its orders, with the failing scratch content)
+is added to the test vectors.
+TESTS environment variable:
+  small (default): random inputs in -2^30..2^30 => the differences do not overflow (sub + sar tricks are valid)
+    the program is only correct on such inputs (the verdict and solution.s say so)
+  full: random inputs over the full i32 range, i32::MIN and i32::MAX in the first test vectors
+
+A* with admissible heuristic => each candidate is optimal for its test vectors
+=> no shorter program sorts all inputs (the final candidate is only checked on random inputs).
+*/
+
+// n=2: small 7 in ~12min (2 rounds, needs 1 scratch register, the 5 from round 1 reads the scratch register):
+//   sub %ecx, %eax; mov %eax, %edx; sar $31, %eax; and %edx, %eax; sub %eax, %edx; add %ecx, %eax; add %edx, %ecx
+//   full (MAX_LEN = 8): lower bound 6 after 5min (memory exhausted with 5GB)
+const NUMBERS: usize = 2;
+const MAX_LEN: u8 = 7;
+const SWAPS: usize = 2;
+const REGS: usize = NUMBERS + SWAPS;
+const RANDOM_TESTS: usize = 100000;
+// scratch contents for the check of a candidate (in addition to a random one per input)
+const SCRATCH_VALUES: [i32; 4] = [0, -1, i32::MIN, 0x5a5a5a5a];
+
+const MOV: usize = 0;
+const ADD: usize = 1;
+const SUB: usize = 2;
+const AND: usize = 3;
+const OR: usize = 4;
+const XOR: usize = 5;
+const NEG: usize = 6;
+const SAR: usize = 7; // by 31 (sign mask)
+const CMP: usize = 8;
+const SETL: usize = 9;
+const SETG: usize = 10;
+
+const UNKNOWN_FLAG: i32 = 2;
+
+const SMALL_VALUES: [i32; 2] = [1, 2];
+const FULL_VALUES: [i32; 4] = [i32::MIN, 1, 2, i32::MAX];
+// random inputs of TESTS=small: the differences do not overflow
+const SMALL_RANGE: i32 = 1 << 30;
+
+type Command = (usize, usize, usize);
+// registers, lt and gt flag for one test vector
+#[derive(Clone, Eq, PartialEq, Hash, Ord, PartialOrd, Debug, Copy)]
+struct Permutation([i32; REGS + 2]);
+type State = Vec<Permutation>;
+// input values and the content of the scratch registers
+type TestVector = (Vec<i32>, i32);
+
+use std::ops::{Index, IndexMut};
+
+impl Index<usize> for Permutation {
+    type Output = i32;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+impl IndexMut<usize> for Permutation {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.0[index]
+    }
+}
+
+impl Index<Range<usize>> for Permutation {
+    type Output = [i32];
+
+    fn index(&self, index: Range<usize>) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Tests {
+    Small,
+    Full,
+}
+
+impl Tests {
+    fn from_env() -> Tests {
+        let tests = std::env::var("TESTS").unwrap_or("small".to_string());
+        match tests.as_str() {
+            "small" => Tests::Small,
+            "full" => Tests::Full,
+            _ => panic!("Unknown TESTS: {}", tests),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Tests::Small => "small",
+            Tests::Full => "full",
+        }
+    }
+
+    // values of the first test vectors
+    fn values(&self) -> &'static [i32] {
+        match self {
+            Tests::Small => &SMALL_VALUES,
+            Tests::Full => &FULL_VALUES,
+        }
+    }
+
+    // inputs the random check covers
+    fn range(&self) -> &'static str {
+        match self {
+            Tests::Small => "-2^30..=2^30, not valid if a difference of two inputs overflows",
+            Tests::Full => "the full i32 range",
+        }
+    }
+}
+
+fn possible_commands() -> Vec<Command> {
+    let mut commands = vec![];
+    for d in 0..REGS {
+        for s in 0..REGS {
+            if d == s {
+                // xor d, d zeroes a register (sub d, d is the same, add d, d, and d, d, ... are left out)
+                commands.push((XOR, d, d));
+                continue;
+            }
+            for instr in &[MOV, ADD, SUB, AND, OR, XOR] {
+                commands.push((*instr, d, s));
+            }
+            // cmp s, d only swaps lt and gt
+            if d < s {
+                commands.push((CMP, d, s));
+            }
+        }
+        for instr in &[NEG, SAR, SETL, SETG] {
+            commands.push((*instr, d, d));
+        }
+    }
+    commands
+}
+
+// flags of the exact (not wrapped) result compared to 0
+fn set_flags(perm: &mut Permutation, result: i64) {
+    perm[REGS + 0] = (result < 0) as i32;
+    perm[REGS + 1] = (result > 0) as i32;
+}
+
+// transform a permutation according to a command
+// false if the command reads undefined flags
+fn apply(cmd: &Command, perm: &mut Permutation) -> bool {
+    let (instr, d, s) = *cmd;
+    let (a, b) = (perm[d], perm[s]);
+    match instr {
+        MOV => perm[d] = b,
+        ADD => {
+            set_flags(perm, a as i64 + b as i64);
+            perm[d] = a.wrapping_add(b);
+        }
+        SUB => {
+            set_flags(perm, a as i64 - b as i64);
+            perm[d] = a.wrapping_sub(b);
+        }
+        AND | OR | XOR => {
+            let result = match instr {
+                AND => a & b,
+                OR => a | b,
+                _ => a ^ b,
+            };
+            set_flags(perm, result as i64);
+            perm[d] = result;
+        }
+        NEG => {
+            set_flags(perm, -(a as i64));
+            perm[d] = a.wrapping_neg();
+        }
+        SAR => {
+            perm[REGS + 0] = UNKNOWN_FLAG;
+            perm[REGS + 1] = UNKNOWN_FLAG;
+            perm[d] = a >> 31;
+        }
+        CMP => set_flags(perm, a as i64 - b as i64),
+        SETL | SETG => {
+            let flag = if instr == SETL { perm[REGS + 0] } else { perm[REGS + 1] };
+            if flag == UNKNOWN_FLAG {
+                return false;
+            }
+            perm[d] = (a & !0xff) | flag;
+        }
+        _ => panic!("Unknown instruction"),
+    }
+    true
+}
+
+// map a command over all permutations in a state
+fn apply_all(cmd: &Command, state: &State) -> Option<State> {
+    let mut new_state = Vec::new();
+    for perm in state {
+        let mut new_perm = perm.clone();
+        if !apply(cmd, &mut new_perm) {
+            return None;
+        }
+        new_state.push(new_perm);
+    }
+    // no sort/dedup: the order of the test vectors gives the expected output
+    Some(new_state)
+}
+
+fn reg_name(reg: usize) -> &'static str {
+    ["eax", "ecx", "edx", "esi", "r8d", "r9d", "r10d", "r11d"][reg]
+}
+
+// low byte for setcc
+fn byte_name(reg: usize) -> &'static str {
+    ["al", "cl", "dl", "sil", "r8b", "r9b", "r10b", "r11b"][reg]
+}
+
+// AT&T syntax (source first)
+fn show_command(cmd: &Command) -> String {
+    let (instr, d, s) = *cmd;
+    let byte = byte_name(d);
+    let (d, s) = (reg_name(d), reg_name(s));
+    match instr {
+        MOV => format!("mov %{}, %{}", s, d),
+        ADD => format!("add %{}, %{}", s, d),
+        SUB => format!("sub %{}, %{}", s, d),
+        AND => format!("and %{}, %{}", s, d),
+        OR => format!("or %{}, %{}", s, d),
+        XOR => format!("xor %{}, %{}", s, d),
+        NEG => format!("neg %{}", d),
+        SAR => format!("sar $31, %{}", d),
+        CMP => format!("cmp %{}, %{}", s, d),
+        SETL => format!("setl %{}", byte),
+        SETG => format!("setg %{}", byte),
+        _ => panic!("Unknown instruction"),
+    }
+}
+
+// linked list to store the commands and pointer to last element
+// shared prefixes (Rc instead of Box) as in main_registers.rs
+#[derive(Clone, Eq, PartialEq, Hash)]
+struct Node {
+    cmd: Command,
+    prev: Option<Rc<Node>>,
+}
+
+fn extract_program(node: &Node) -> Vec<Command> {
+    let mut cmds = vec![];
+    let mut node = node;
+    while let Some(prev) = &node.prev {
+        cmds.push(node.cmd);
+        node = prev;
+    }
+    cmds.reverse();
+    cmds
+}
+
+// the output register i is correct for all test vectors
+fn correct(state: &State, targets: &[Vec<i32>], i: usize) -> bool {
+    state.iter().zip(targets).all(|(p, t)| p[i] == t[i])
+}
+
+// each instruction writes at most one register
+// => every register that is wrong for at least one test vector needs one more instruction (its last write)
+// if no single instruction makes any wrong register correct, the first of these last writes needs an instruction before it
+fn admissible_heuristic(state: &State, targets: &[Vec<i32>], possible_cmds: &[Command]) -> u8 {
+    let wrong = (0..NUMBERS).filter(|&i| !correct(state, targets, i)).collect::<Vec<_>>();
+    if wrong.is_empty() {
+        return 0;
+    }
+    let one_step = possible_cmds
+        .iter()
+        .filter(|&&(_, d, _)| wrong.contains(&d))
+        .any(|cmd| match apply_all(cmd, state) {
+            Some(new_state) => correct(&new_state, targets, cmd.1),
+            None => false,
+        });
+    wrong.len() as u8 + if one_step { 0 } else { 1 }
+}
+
+fn initial_perm(input: &[i32], garbage: i32) -> Permutation {
+    let mut perm = Permutation([garbage; REGS + 2]);
+    perm[REGS + 0] = UNKNOWN_FLAG;
+    perm[REGS + 1] = UNKNOWN_FLAG;
+    for (i, &x) in input.iter().enumerate() {
+        perm[i] = x;
+    }
+    perm
+}
+
+fn sorts(cmds: &[Command], input: &[i32], scratch: i32) -> bool {
+    let mut perm = initial_perm(input, scratch);
+    for cmd in cmds {
+        if !apply(cmd, &mut perm) {
+            return false;
+        }
+    }
+    perm[0..NUMBERS].iter().copied().eq(input.iter().copied().sorted())
+}
+
+// all permutations of n values out of the given ones (with repetition)
+// distinct undefined scratch contents per test vector
+fn test_vectors(values: &[i32]) -> Vec<TestVector> {
+    values
+        .iter()
+        .copied()
+        .combinations_with_replacement(NUMBERS)
+        .flat_map(|c| c.into_iter().permutations(NUMBERS))
+        .unique()
+        .enumerate()
+        .map(|(j, input)| (input, 0x1234567i32.wrapping_mul(j as i32 + 1)))
+        .collect()
+}
+
+fn random_input(rng: &mut impl Rng, tests: Tests) -> Vec<i32> {
+    (0..NUMBERS)
+        .map(|_| match tests {
+            Tests::Small => rng.gen_range(-SMALL_RANGE..=SMALL_RANGE),
+            Tests::Full => rng.gen::<i32>(),
+        })
+        .collect()
+}
+
+fn search(possible_cmds: &[Command], inputs: &[TestVector]) -> Option<Vec<Command>> {
+    let targets: Vec<Vec<i32>> = inputs.iter().map(|(i, _)| i.iter().copied().sorted().collect()).collect();
+    let initial_state: Rc<State> = Rc::new(inputs
+        .iter()
+        .map(|(input, scratch)| initial_perm(input, *scratch))
+        .collect());
+
+    // the states are small => an in-memory map is sufficient (see main_cegis.rs)
+    // keys shared with the queue (Rc)
+    let mut length_map: HashMap<Rc<State>, u8> = HashMap::new();
+    length_map.insert(Rc::clone(&initial_state), 0);
+    let mut queue = PriorityQueue::new();
+    let node0 = Node{cmd: (0,0,0), prev: None};
+    queue.push((node0,Rc::clone(&initial_state),0 as u8), Reverse(admissible_heuristic(&initial_state, &targets, possible_cmds)));
+
+    let mut visited : u64 = 0;
+    let mut duplicate : u64 = 0;
+    let mut cut : u64 = 0;
+    let mut solution = None;
+    let start = std::time::Instant::now();
+    while let Some(((prg,state,length), Reverse(score))) = queue.pop() {
+        visited += 1;
+        if visited % 100000 == 0 {
+            print!("Open: {}, ", queue.len());
+            print!("Visited: {}, ", visited);
+            print!("Duplicate: {}, ", duplicate);
+            print!("Cut: {}, ", cut);
+            print!("Current length: {}, ", length);
+            print!("Lower bound: {}, ", score);
+            print!("Time: {:?}", start.elapsed());
+            println!("");
+        }
+        if length_map[&state] < length {
+            duplicate += 1;
+            continue;
+        }
+        if (0..NUMBERS).all(|i| correct(&state, &targets, i)) {
+            solution = Some(extract_program(&prg));
+            break;
+        }
+
+        let prev_rc = Some(Rc::new(prg));
+        for cmd in possible_cmds {
+            let new_length = length + 1;
+            let new_state = match apply_all(cmd, &state) {
+                Some(new_state) => new_state,
+                None => {
+                    cut += 1;
+                    continue;
+                }
+            };
+            let new_score = new_length + admissible_heuristic(&new_state, &targets, possible_cmds);
+            if new_score > MAX_LEN {
+                cut += 1;
+                continue;
+            }
+            if let Some(&old_length) = length_map.get(&new_state) {
+                if old_length <= new_length {
+                    duplicate += 1;
+                    continue;
+                }
+            }
+            let new_state = Rc::new(new_state);
+            length_map.insert(Rc::clone(&new_state), new_length);
+            let prg = Node{cmd: *cmd, prev: prev_rc.clone()};
+            queue.push((prg,new_state,new_length), Reverse(new_score));
+        }
+    }
+    println!("Visited: {}, Duplicate: {}, Cut: {}", visited, duplicate, cut);
+    solution
+}
+
+fn main() {
+    let possible_cmds = possible_commands();
+    let tests = Tests::from_env();
+    let mut inputs = test_vectors(tests.values());
+
+    println!("n = {}", NUMBERS);
+    println!("max_len = {}", MAX_LEN);
+    println!("swaps = {}", SWAPS);
+    println!("tests = {}: {}", tests.name(), tests.range());
+    println!("instruction count = {}", possible_cmds.len());
+
+    let mut rng = rand::thread_rng();
+    let start = std::time::Instant::now();
+    let mut round = 0;
+    loop {
+        round += 1;
+        println!("Round {}: searching with {} test vectors", round, inputs.len());
+        let cmds = match search(&possible_cmds, &inputs) {
+            Some(cmds) => cmds,
+            None => {
+                // a program for all inputs would also sort the test vectors
+                println!("No program of length <= {}", MAX_LEN);
+                break;
+            }
+        };
+        println!("Candidate of length {} after {:?}", cmds.len(), start.elapsed());
+
+        let failing = (0..RANDOM_TESTS)
+            .map(|_| (random_input(&mut rng, tests), rng.gen::<i32>()))
+            .find_map(|(input, random_scratch)| {
+                SCRATCH_VALUES
+                    .iter()
+                    .copied()
+                    .chain([random_scratch])
+                    .find(|&scratch| !sorts(&cmds, &input, scratch))
+                    .map(|scratch| (input, scratch))
+            });
+        if let Some((input, scratch)) = failing {
+            println!("Candidate fails on {:?} (scratch {:#x})", input, scratch);
+            for permutation in input.iter().copied().permutations(NUMBERS).unique() {
+                let test = (permutation, scratch);
+                if !inputs.contains(&test) {
+                    inputs.push(test);
+                }
+            }
+            continue;
+        }
+
+        let verdict = format!("Verified on {} random inputs ({} scratch contents each) in {}",
+            RANDOM_TESTS, SCRATCH_VALUES.len() + 1, tests.range());
+        println!("{}", verdict);
+        println!("Program:");
+        for cmd in &cmds {
+            println!("{}", show_command(cmd));
+        }
+        if let Ok(dir) = std::env::var("SOLUTION_DIR") {
+            let subdir = format!("{}/{}_arith_{}", dir, NUMBERS, tests.name());
+            std::fs::create_dir_all(&subdir).unwrap();
+            let mut file = std::fs::File::create(format!("{}/solution.s", subdir)).unwrap();
+            writeln!(file, "# {}", verdict).unwrap();
+            for cmd in &cmds {
+                writeln!(file, "{}", show_command(cmd)).unwrap();
+            }
+            println!("Stored solution in: {}", subdir);
+        }
+        break;
+    }
+
+    println!("Rounds: {}", round);
+    println!("Elapsed: {:?}", start.elapsed());
+}
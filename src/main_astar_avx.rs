@@ -0,0 +1,372 @@
+use itertools::Itertools;
+use std::ops::Range;
+// has largest value at the top
+use priority_queue::PriorityQueue;
+use std::cmp::Reverse;
+use std::rc::Rc;
+use std::io::Write;
+mod common;
+use common::{inputs, Spec};
+
+/*
+AVX2/AVX-512 instruction model (VEX/EVEX encoded, non-destructive three-operand forms)
+
+avx2:
+  vpminud d, a, b          d = min(a, b)
+  vpmaxud d, a, b          d = max(a, b)
+  vmovdqa d, a
+avx512: additionally mask registers k1..
+  vpcmpltud k, a, b        k = a < b (unsigned)
+  vpblendmd d {k}, a, b    d = k ? b : a
+  (only lt: the other predicates are blends with swapped operands)
+
+ISA environment variable: avx2 (default) | avx512
+The SSE forms (pminud xmm0, xmm1 => xmm0 = min(xmm0, xmm1)) are in main_astar_minmax.rs and main_astar_mixed.rs,
+there a copy (movdqa) is needed whenever both inputs are used again.
+
+The input is in xmm0.., the output in the same registers (what is expected there is given by SPEC, see common.rs).
+A* with admissible heuristic (see main_iterative.rs) => the first solution is optimal.
+Commands are (instr, d, a, b, k), vmovdqa repeats a, k is the mask register of vpblendmd (0 otherwise).
+*/
+
+// n=3: avx2 6, avx512 6 (SSE in main_astar_minmax.rs: 8)
+// n=4: avx2 10 in 1s, avx512 > 8min
+const NUMBERS: usize = 3;
+const MAX_LEN: u8 = 9;
+// const NUMBERS: usize = 4;
+// const MAX_LEN: u8 = 10;
+const SWAPS: usize = 1;
+const REGS: usize = NUMBERS + SWAPS;
+const MASKS: usize = 1;
+const MASKOFFSET: usize = REGS;
+
+const VPMINUD: usize = 0;
+const VPMAXUD: usize = 1;
+const VMOVDQA: usize = 2;
+const VPCMPLTUD: usize = 3; // only avx512, writes a mask register
+const VPBLENDMD: usize = 4; // only avx512, reads a mask register
+const NUMBERS_U8: u8 = NUMBERS as u8;
+// after the mask registers: expected content of the registers 0..NUMBERS (0 = don't care)
+const TARGET: usize = REGS + MASKS;
+
+// (instr, d, a, b, k) with the mask register k of vpblendmd
+type Command = (usize, usize, usize, usize, usize);
+#[derive(Clone, Eq, PartialEq, Hash, Ord, PartialOrd, Debug, Copy)]
+struct Permutation([u8; REGS + MASKS + NUMBERS]);
+type State = Vec<Permutation>;
+
+use std::ops::{Index, IndexMut};
+
+impl Index<usize> for Permutation {
+    type Output = u8;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+impl IndexMut<usize> for Permutation {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.0[index]
+    }
+}
+
+impl Index<Range<usize>> for Permutation {
+    type Output = [u8];
+
+    fn index(&self, index: Range<usize>) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+impl IndexMut<Range<usize>> for Permutation {
+    fn index_mut(&mut self, index: Range<usize>) -> &mut Self::Output {
+        &mut self.0[index]
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Isa {
+    Avx2,
+    Avx512,
+}
+
+impl Isa {
+    fn from_env() -> Isa {
+        let isa = std::env::var("ISA").unwrap_or("avx2".to_string());
+        match isa.as_str() {
+            "avx2" => Isa::Avx2,
+            "avx512" => Isa::Avx512,
+            _ => panic!("Unknown ISA: {}", isa),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Isa::Avx2 => "avx2",
+            Isa::Avx512 => "avx512",
+        }
+    }
+}
+
+fn possible_commands(isa: Isa) -> Vec<Command> {
+    let mut commands = vec![];
+    for d in 0..REGS {
+        for a in 0..REGS {
+            if d != a {
+                commands.push((VMOVDQA, d, a, a, 0));
+            }
+            // vpminud/vpmaxud are commutative
+            for b in (a + 1)..REGS {
+                commands.push((VPMINUD, d, a, b, 0));
+                commands.push((VPMAXUD, d, a, b, 0));
+            }
+        }
+    }
+    if isa == Isa::Avx512 {
+        for k in 0..MASKS {
+            for a in 0..REGS {
+                for b in 0..REGS {
+                    if a == b {
+                        continue;
+                    }
+                    commands.push((VPCMPLTUD, MASKOFFSET + k, a, b, 0));
+                    for d in 0..REGS {
+                        commands.push((VPBLENDMD, d, a, b, MASKOFFSET + k));
+                    }
+                }
+            }
+        }
+    }
+    commands
+}
+
+// transform a permutation according to a command
+fn apply(cmd: &Command, perm: &mut Permutation) {
+    let (instr, d, a, b, k) = *cmd;
+    perm[d] = match instr {
+        VPMINUD => perm[a].min(perm[b]),
+        VPMAXUD => perm[a].max(perm[b]),
+        VMOVDQA => perm[a],
+        VPCMPLTUD => (perm[a] < perm[b]) as u8,
+        VPBLENDMD => if perm[k] == 1 { perm[b] } else { perm[a] },
+        _ => panic!("Unknown instruction"),
+    };
+}
+
+// map a command over all permutations in a state
+fn apply_all(cmd: &Command, state: &State) -> State {
+    let mut new_state = Vec::new();
+    for perm in state {
+        let mut new_perm = perm.clone();
+        apply(cmd, &mut new_perm);
+        new_state.push(new_perm);
+    }
+    new_state.sort();
+    new_state.dedup();
+    new_state
+}
+
+// check if the state can never reach a solution
+// corresponds to delete-relaxed planning check
+// (mask registers hold flags, not values)
+// every value required by the spec has to be in some register
+fn viable(state: &State) -> bool {
+    for perm in state {
+        for &n in &perm[TARGET..TARGET+NUMBERS] {
+            if n != 0 && !perm[0..REGS].contains(&n) {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+fn reg_name(reg: usize) -> String {
+    if reg < REGS {
+        format!("xmm{}", reg)
+    } else {
+        // k0 can not be used as write mask
+        format!("k{}", reg - MASKOFFSET + 1)
+    }
+}
+
+// source operands first like show_command in main_astar_mixed.rs (AT&T order)
+fn show_command(cmd: &Command) -> String {
+    let (instr, d, a, b, k) = *cmd;
+    let (d, a, b, k) = (reg_name(d), reg_name(a), reg_name(b), reg_name(k));
+    match instr {
+        VPMINUD => format!("vpminud {}, {}, {}", b, a, d),
+        VPMAXUD => format!("vpmaxud {}, {}, {}", b, a, d),
+        VMOVDQA => format!("vmovdqa {}, {}", a, d),
+        VPCMPLTUD => format!("vpcmpltud {}, {}, {}", b, a, d),
+        VPBLENDMD => format!("vpblendmd {}, {}, {}{{{}}}", b, a, d, k),
+        _ => panic!("Unknown instruction"),
+    }
+}
+
+// linked list to store the commands and pointer to last element
+// shared prefixes (Rc instead of Box) as in main_registers.rs
+#[derive(Clone, Eq, PartialEq, Hash)]
+struct Node {
+    cmd: Command,
+    prev: Option<Rc<Node>>,
+}
+
+// for each permutation, take out register values, concat => serializable byte array
+fn state_positions(state: &State) -> Vec<u8> {
+    state.iter().flat_map(|p| p.0).collect()
+}
+
+fn extract_program(node: &Node) -> Vec<Command> {
+    let mut cmds = vec![];
+    let mut node = node;
+    while let Some(prev) = &node.prev {
+        cmds.push(node.cmd);
+        node = prev;
+    }
+    cmds.reverse();
+    cmds
+}
+
+// the registers contain the output expected by the spec
+fn reached(perm: &Permutation) -> bool {
+    (0..NUMBERS).all(|i| perm[TARGET+i] == 0 || perm[i] == perm[TARGET+i])
+}
+
+// each instruction writes at most one register
+// => every register that is wrong in at least one permutation needs one more instruction
+fn admissible_heuristic(state: &State) -> u8 {
+    (0..NUMBERS)
+        .filter(|&i| state.iter().any(|p| p[TARGET+i] != 0 && p[i] != p[TARGET+i]))
+        .count() as u8
+}
+
+// find unused sled-mapX file in a temporary directory (_CONDOR_SCRATCH_DIR or /tmp/ else)
+fn open_length_map() -> sled::Db {
+    let tmp_dir = std::env::var("_CONDOR_SCRATCH_DIR").unwrap_or("/tmp".to_string());
+    let mut i = 0;
+    let mut path = format!("{}/sled-map{}", tmp_dir, i);
+    while std::path::Path::new(&path).exists() {
+        i += 1;
+        path = format!("{}/sled-map{}", tmp_dir, i);
+    }
+    println!("Using sled map: {}", path);
+    sled::open(path).unwrap()
+}
+
+fn main() {
+    let isa = Isa::from_env();
+    let possible_cmds = possible_commands(isa);
+    let spec = Spec::from_env();
+    // only inputs that are constrained by the spec
+    let permutations = inputs(&spec);
+
+    println!("n = {}", NUMBERS);
+    println!("max_len = {}", MAX_LEN);
+    println!("swaps = {}", SWAPS);
+    println!("isa = {}", isa.name());
+    println!("spec = {}", std::env::var("SPEC").unwrap_or("sort".to_string()));
+    println!("inputs = {}", permutations.len());
+    println!("instruction count = {}", possible_cmds.len());
+
+    let initial_state: Rc<State> = Rc::new(permutations
+        .iter()
+        .map(|(p, target)| {
+            let mut perm = Permutation([0; REGS + MASKS + NUMBERS]);
+            for (i, &x) in p.iter().enumerate() {
+                perm[i] = x;
+            }
+            perm[TARGET..TARGET+NUMBERS].copy_from_slice(target);
+            perm
+        })
+        .sorted()
+        .collect());
+
+    let length_map = open_length_map();
+    length_map.insert(state_positions(&initial_state), vec![0 as u8]).unwrap();
+    let mut queue = PriorityQueue::new();
+    let node0 = Node{cmd: (0,0,0,0,0), prev: None};
+    queue.push((node0,Rc::clone(&initial_state),0 as u8), Reverse(admissible_heuristic(&initial_state)));
+
+    let mut visited : u64 = 0;
+    let mut duplicate : u64 = 0;
+    let mut cut : u64 = 0;
+    let mut solution = None;
+    let start = std::time::Instant::now();
+    while let Some(((prg,state,length), Reverse(score))) = queue.pop() {
+        visited += 1;
+        if visited % 100000 == 0 {
+            print!("Open: {}, ", queue.len());
+            print!("Visited: {}, ", visited);
+            print!("Duplicate: {}, ", duplicate);
+            print!("Cut: {}, ", cut);
+            print!("Current length: {}, ", length);
+            print!("Lower bound: {}, ", score);
+            print!("Time: {:?}", start.elapsed());
+            println!("");
+        }
+
+        if let Some(state_len_vec) = length_map.get(state_positions(&state)).unwrap() {
+            if state_len_vec[0] < length {
+                duplicate += 1;
+                continue;
+            }
+        }
+
+        if state.iter().all(reached) {
+            solution = Some(extract_program(&prg));
+            break;
+        }
+
+        let prev_rc = Some(Rc::new(prg));
+        for cmd in &possible_cmds {
+            let new_state = Rc::new(apply_all(cmd, &state));
+            let new_length = length + 1;
+
+            if !viable(&new_state) {
+                cut += 1;
+                continue;
+            }
+
+            let new_score = new_length + admissible_heuristic(&new_state);
+            if new_score > MAX_LEN {
+                cut += 1;
+                continue;
+            }
+
+            let state_repr = state_positions(&new_state);
+            if let Some(old_length_vec) = length_map.get(&state_repr).unwrap() {
+                if old_length_vec[0] <= new_length {
+                    duplicate += 1;
+                    continue;
+                }
+            }
+            length_map.insert(state_repr, vec![new_length]).unwrap();
+
+            let prg = Node{cmd: *cmd, prev: prev_rc.clone()};
+            queue.push((prg,Rc::clone(&new_state),new_length), Reverse(new_score));
+        }
+    }
+    println!("Visited: {}, Duplicate: {}, Cut: {}", visited, duplicate, cut);
+
+    match solution {
+        Some(cmds) => {
+            println!("Found program of length {}:", cmds.len());
+            for cmd in &cmds {
+                println!("{}", show_command(cmd));
+            }
+            if let Ok(dir) = std::env::var("SOLUTION_DIR") {
+                let subdir = format!("{}/{}_{}", dir, NUMBERS, isa.name());
+                std::fs::create_dir_all(&subdir).unwrap();
+                let mut file = std::fs::File::create(format!("{}/solution.s", subdir)).unwrap();
+                for cmd in &cmds {
+                    writeln!(file, "{}", show_command(cmd)).unwrap();
+                }
+                println!("Stored solution in: {}", subdir);
+            }
+        }
+        None => println!("No program of length <= {}", MAX_LEN),
+    }
+    println!("Elapsed: {:?}", start.elapsed());
+}
@@ -0,0 +1,446 @@
+use itertools::Itertools;
+use std::collections::{HashMap, HashSet};
+// has largest value at the top
+use priority_queue::PriorityQueue;
+use std::cmp::Reverse;
+use std::rc::Rc;
+use std::io::Write;
+
+/*
+In-register SIMD sorting: the keys are the lanes of one or two xmm registers (4 x 32 bit lanes)
+
+SSE instructions (destructive, d = d op s, lanes 0..3):
+  movdqa d, s
+  pminud d, s            d[i] = min(d[i], s[i])
+  pmaxud d, s            d[i] = max(d[i], s[i])
+  pshufd d, s, imm       d[i] = s[imm_i]        (imm: any selection of lanes, including broadcasts)
+  shufps d, s, imm       d = [d[imm_0], d[imm_1], s[imm_2], s[imm_3]]
+  blendps d, s, imm      d[i] = imm_i ? s[i] : d[i]
+(imm_i = lane selected by the i-th 2-bit field (pshufd, shufps) or bit i (blendps) of the immediate)
+ISA environment variable: sse41 (default, without shufps) | shufps
+
+All instructions are monotone (min, max and moving lanes)
+=> 0-1 principle (see main_network.rs): a state holds the 2^n binary inputs instead of the n! permutations
+   a permutation is the bit vector of all lanes + the number of ones of the input
+The input is in lanes 0.. of xmm0 (and xmm1 for n > 4), the output sorted ascending in the same lanes.
+Only sorting: the 0-1 principle does not hold for the other kernels of SPEC (common.rs), e.g. a table
+(the binary inputs are no permutations).
+Unused lanes and scratch registers are 0 at the start (like the scratch registers of the other models),
+the search may use them as the smallest value.
+
+A* with admissible heuristic => the first solution is optimal:
+- each instruction writes one register => every wrong output register needs an instruction
+- on bits, pminud/pmaxud are a lanewise and/or, the other instructions only move lanes
+  => the output lanes (threshold functions of the input) have to be built from the functions in the lanes
+  => the and/or depth to reach them is a lower bound for the remaining pminud/pmaxud
+Scratch registers are interchangeable => duplicate check modulo their order (see main_registers.rs).
+
+Proven so far: only n=2 (optimal 4). n=4 and n=8 are open:
+- upper bound for n=4 in one register: 15, one layer of the network (1,2)(3,4) / (1,3)(2,4) / (2,3)
+  in 5 instructions (2 scratch registers), checked on all permutations:
+    pshufd $imm, x, t; movdqa x, u; pminud t, u; pmaxud t, x; blendps $min_lanes, u, x
+  (imm swaps the lanes of each comparator: 0xb1, 0x4e, 0xd8; min_lanes: 0x5, 0x3, 0x2),
+  the same for n=3 with the network (1,2) / (2,3) / (1,2)
+- blocker: about 2400 (n=8: 4300) instructions per state, the heuristic only bounds the written registers and
+  the and/or depth => A* has to close every state below the optimum,
+  n=4: 7M open states (4GB) at a lower bound of 6 after 4min, n=8: 5GB exhausted before 100000 visited states
+*/
+
+// n=2: 4 in 22s, ISA=shufps 4 in 38s (optimal, the default because it is the size the search finishes)
+const NUMBERS: usize = 2;
+const MAX_LEN: u8 = 15;
+const SWAPS: usize = 2;
+// n=3, SWAPS=1: lower bound 7 after 5min (8M open states)
+// const NUMBERS: usize = 3;
+// const MAX_LEN: u8 = 12;
+// const SWAPS: usize = 1;
+// n=4 (one register): lower bound 6 after 4min (7M open states), upper bound 15 (see above)
+// const NUMBERS: usize = 4;
+// const MAX_LEN: u8 = 15;
+// const SWAPS: usize = 2;
+// n=8 (two registers): memory exhausted with 5GB before 100000 visited states
+// const NUMBERS: usize = 8;
+// const MAX_LEN: u8 = 40;
+// const SWAPS: usize = 2;
+const LANES: usize = 4;
+const INPUT_REGS: usize = (NUMBERS + LANES - 1) / LANES;
+const REGS: usize = INPUT_REGS + SWAPS;
+const INPUTS: usize = 1 << NUMBERS;
+const WORDS: usize = (INPUTS + 63) / 64;
+// bits of a permutation: lanes at 0..REGS*LANES, number of ones from COUNT_SHIFT on
+const COUNT_SHIFT: usize = 24;
+
+const MOVDQA: usize = 0;
+const PMINUD: usize = 1;
+const PMAXUD: usize = 2;
+const PSHUFD: usize = 3;
+const SHUFPS: usize = 4;
+const BLENDPS: usize = 5;
+
+// (instr, to, from, imm)
+type Command = (usize, usize, usize, u8);
+type Permutation = u32;
+type State = Vec<Permutation>;
+// boolean function over the permutations of a state (bit j = value for the j-th permutation)
+type Function = [u64; WORDS];
+
+// lane selected by the i-th 2-bit field of an immediate
+fn field(imm: u8, i: usize) -> usize {
+    ((imm >> (2 * i)) & 3) as usize
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Isa {
+    // movdqa, pminud, pmaxud, pshufd, blendps
+    Sse41,
+    // additionally shufps (256 immediates per register pair)
+    Shufps,
+}
+
+impl Isa {
+    fn from_env() -> Isa {
+        let isa = std::env::var("ISA").unwrap_or("sse41".to_string());
+        match isa.as_str() {
+            "sse41" => Isa::Sse41,
+            "shufps" => Isa::Shufps,
+            _ => panic!("Unknown ISA: {}", isa),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Isa::Sse41 => "sse41",
+            Isa::Shufps => "shufps",
+        }
+    }
+}
+
+fn possible_commands(isa: Isa) -> Vec<Command> {
+    let mut commands = vec![];
+    // all immediates except the identity (a movdqa)
+    let shuffles = (0..=255u8)
+        .filter(|&imm| imm != 0b11_10_01_00)
+        .collect::<Vec<_>>();
+    for to in 0..REGS {
+        for from in 0..REGS {
+            for &imm in &shuffles {
+                commands.push((PSHUFD, to, from, imm));
+            }
+            if to == from {
+                continue;
+            }
+            // shufps d, d is a pshufd
+            if isa == Isa::Shufps {
+                for imm in 0..=255u8 {
+                    commands.push((SHUFPS, to, from, imm));
+                }
+            }
+            commands.push((MOVDQA, to, from, 0));
+            commands.push((PMINUD, to, from, 0));
+            commands.push((PMAXUD, to, from, 0));
+            // 0 = nothing, 15 = movdqa
+            for imm in 1..15u8 {
+                commands.push((BLENDPS, to, from, imm));
+            }
+        }
+    }
+    commands
+}
+
+fn lane(perm: Permutation, reg: usize, i: usize) -> u32 {
+    (perm >> (reg * LANES + i)) & 1
+}
+
+// transform a permutation according to a command
+fn apply(cmd: &Command, perm: Permutation) -> Permutation {
+    let (instr, to, from, imm) = *cmd;
+    let mut lanes = [0; LANES];
+    for i in 0..LANES {
+        lanes[i] = match instr {
+            MOVDQA => lane(perm, from, i),
+            // on bits: min = and, max = or
+            PMINUD => lane(perm, to, i) & lane(perm, from, i),
+            PMAXUD => lane(perm, to, i) | lane(perm, from, i),
+            PSHUFD => lane(perm, from, field(imm, i)),
+            SHUFPS if i < 2 => lane(perm, to, field(imm, i)),
+            SHUFPS => lane(perm, from, field(imm, i)),
+            BLENDPS if (imm >> i) & 1 == 1 => lane(perm, from, i),
+            BLENDPS => lane(perm, to, i),
+            _ => panic!("Unknown instruction"),
+        };
+    }
+    let mut new_perm = perm & !(((1 << LANES) - 1) << (to * LANES));
+    for i in 0..LANES {
+        new_perm |= lanes[i] << (to * LANES + i);
+    }
+    new_perm
+}
+
+// map a command over all permutations in a state
+fn apply_all(cmd: &Command, state: &State) -> State {
+    let mut new_state = state.iter().map(|&p| apply(cmd, p)).collect::<Vec<_>>();
+    new_state.sort();
+    new_state.dedup();
+    new_state
+}
+
+fn ones(perm: Permutation) -> usize {
+    (perm >> COUNT_SHIFT) as usize
+}
+
+// sorted output (zeros first) for k ones in the first NUMBERS lanes
+fn sorted_bits(ones: usize) -> u32 {
+    ((1 << NUMBERS) - 1) ^ ((1 << (NUMBERS - ones)) - 1)
+}
+
+fn is_sorted(perm: &Permutation) -> bool {
+    perm & ((1 << NUMBERS) - 1) == sorted_bits(ones(*perm))
+}
+
+// no instruction creates values
+// => a one (zero) has to be in some lane as long as the output needs one
+fn viable(state: &State) -> bool {
+    let all = (1 << (REGS * LANES)) - 1;
+    state.iter().all(|&p| {
+        let lanes = p & all;
+        (ones(p) == 0 || lanes != 0) && (ones(p) == NUMBERS || lanes != all)
+    })
+}
+
+// each instruction writes one register
+// => every input register with a wrong lane in at least one permutation needs one more instruction
+fn register_heuristic(state: &State) -> u8 {
+    (0..INPUT_REGS)
+        .filter(|&r| {
+            let mask = ((1 << LANES) - 1) << (r * LANES);
+            state.iter().any(|&p| (p ^ sorted_bits(ones(p))) & mask & ((1 << NUMBERS) - 1) != 0)
+        })
+        .count() as u8
+}
+
+fn function(state: &State, value: impl Fn(Permutation) -> u32) -> Function {
+    let mut f = [0; WORDS];
+    for (j, &p) in state.iter().enumerate() {
+        f[j / 64] |= (value(p) as u64) << (j % 64);
+    }
+    f
+}
+
+// and/or depth of the output lanes over the functions in all lanes
+// (lane moves keep the set of functions => cached by functions and targets)
+fn depth_heuristic(state: &State, cache: &mut HashMap<Vec<Function>, u8>) -> u8 {
+    let mut key = (0..REGS)
+        .flat_map(|r| (0..LANES).map(move |i| (r, i)))
+        .map(|(r, i)| function(state, |p| lane(p, r, i)))
+        .sorted()
+        .dedup()
+        .collect::<Vec<_>>();
+    let lanes = key.len();
+    key.extend((0..NUMBERS).map(|i| function(state, |p| (sorted_bits(ones(p)) >> i) & 1)));
+    if let Some(&depth) = cache.get(&key) {
+        return depth;
+    }
+    let mut known = key[..lanes].iter().copied().collect::<HashSet<_>>();
+    let mut functions = key[..lanes].to_vec();
+    let mut missing = key[lanes..].iter().filter(|f| !known.contains(*f)).copied().collect::<Vec<_>>();
+    let mut depth = 0;
+    while !missing.is_empty() && depth < MAX_LEN {
+        depth += 1;
+        let mut new_functions = vec![];
+        for (f, g) in functions.iter().tuple_combinations() {
+            let (mut and, mut or) = (*f, *f);
+            for w in 0..WORDS {
+                and[w] &= g[w];
+                or[w] |= g[w];
+            }
+            for h in [and, or] {
+                if known.insert(h) {
+                    new_functions.push(h);
+                }
+            }
+        }
+        functions.extend(new_functions);
+        missing.retain(|f| !known.contains(f));
+    }
+    cache.insert(key, depth);
+    depth
+}
+
+// scratch registers are interchangeable => smallest representation over their orders
+fn canonical(state: &State) -> State {
+    let scratch_mask = !0u32 << (INPUT_REGS * LANES) & ((1 << (REGS * LANES)) - 1);
+    (INPUT_REGS..REGS)
+        .permutations(SWAPS)
+        .map(|order| {
+            let mut renamed = state.iter().map(|&p| {
+                let mut new_perm = p & !scratch_mask;
+                for (k, &reg) in order.iter().enumerate() {
+                    let bits = (p >> (reg * LANES)) & ((1 << LANES) - 1);
+                    new_perm |= bits << ((INPUT_REGS + k) * LANES);
+                }
+                new_perm
+            }).collect::<Vec<_>>();
+            renamed.sort();
+            renamed
+        })
+        .min()
+        .unwrap()
+}
+
+fn admissible_heuristic(state: &State, cache: &mut HashMap<Vec<Function>, u8>) -> u8 {
+    register_heuristic(state).max(depth_heuristic(state, cache))
+}
+
+fn show_command(cmd: &Command) -> String {
+    let (instr, to, from, imm) = *cmd;
+    match instr {
+        MOVDQA => format!("movdqa %%xmm{}, %%xmm{}", from, to),
+        PMINUD => format!("pminud %%xmm{}, %%xmm{}", from, to),
+        PMAXUD => format!("pmaxud %%xmm{}, %%xmm{}", from, to),
+        PSHUFD => format!("pshufd ${:#04x}, %%xmm{}, %%xmm{}", imm, from, to),
+        SHUFPS => format!("shufps ${:#04x}, %%xmm{}, %%xmm{}", imm, from, to),
+        BLENDPS => format!("blendps ${:#04x}, %%xmm{}, %%xmm{}", imm, from, to),
+        _ => panic!("Unknown instruction"),
+    }
+}
+
+// linked list to store the commands and pointer to last element
+// shared prefixes (Rc instead of Box) as in main_registers.rs
+#[derive(Clone, Eq, PartialEq, Hash)]
+struct Node {
+    cmd: Command,
+    prev: Option<Rc<Node>>,
+}
+
+fn extract_program(node: &Node) -> Vec<Command> {
+    let mut cmds = vec![];
+    let mut node = node;
+    while let Some(prev) = &node.prev {
+        cmds.push(node.cmd);
+        node = prev;
+    }
+    cmds.reverse();
+    cmds
+}
+
+// the real check: all permutations of 1..n on u32 lanes
+fn sorts_permutations(cmds: &[Command]) -> bool {
+    (1..=NUMBERS as u32).permutations(NUMBERS).all(|input| {
+        let mut regs = [[0u32; LANES]; REGS];
+        for (i, &x) in input.iter().enumerate() {
+            regs[i / LANES][i % LANES] = x;
+        }
+        for &(instr, to, from, imm) in cmds {
+            let (d, s) = (regs[to], regs[from]);
+            regs[to] = match instr {
+                MOVDQA => s,
+                PMINUD => [0, 1, 2, 3].map(|i| d[i].min(s[i])),
+                PMAXUD => [0, 1, 2, 3].map(|i| d[i].max(s[i])),
+                PSHUFD => [0, 1, 2, 3].map(|i| s[field(imm, i)]),
+                SHUFPS => [0, 1, 2, 3].map(|i| if i < 2 { d[field(imm, i)] } else { s[field(imm, i)] }),
+                BLENDPS => [0, 1, 2, 3].map(|i| if (imm >> i) & 1 == 1 { s[i] } else { d[i] }),
+                _ => panic!("Unknown instruction"),
+            };
+        }
+        (0..NUMBERS).all(|i| regs[i / LANES][i % LANES] == (i + 1) as u32)
+    })
+}
+
+fn main() {
+    let isa = Isa::from_env();
+    let possible_cmds = possible_commands(isa);
+
+    println!("n = {}", NUMBERS);
+    println!("max_len = {}", MAX_LEN);
+    println!("swaps = {}", SWAPS);
+    println!("isa = {}", isa.name());
+    println!("instruction count = {}", possible_cmds.len());
+
+    // all 0/1 inputs in the first lanes, scratch registers 0
+    let initial_state: Rc<State> = Rc::new((0..INPUTS as u32)
+        .map(|v| v | (v.count_ones() << COUNT_SHIFT))
+        .collect());
+    println!("inputs = {} (instead of {} permutations)", initial_state.len(), (1..=NUMBERS).product::<usize>());
+
+    let mut length_map: HashMap<State, u8> = HashMap::new();
+    length_map.insert(canonical(&initial_state), 0);
+    let mut heuristic_cache = HashMap::new();
+    let mut queue = PriorityQueue::new();
+    let node0 = Node{cmd: (0,0,0,0), prev: None};
+    queue.push((node0,Rc::clone(&initial_state),0 as u8), Reverse(admissible_heuristic(&initial_state, &mut heuristic_cache)));
+
+    let mut visited : u64 = 0;
+    let mut duplicate : u64 = 0;
+    let mut cut : u64 = 0;
+    let mut solution = None;
+    let start = std::time::Instant::now();
+    while let Some(((prg,state,length), Reverse(score))) = queue.pop() {
+        visited += 1;
+        if visited % 100000 == 0 {
+            print!("Open: {}, ", queue.len());
+            print!("Visited: {}, ", visited);
+            print!("Duplicate: {}, ", duplicate);
+            print!("Cut: {}, ", cut);
+            print!("Current length: {}, ", length);
+            print!("Lower bound: {}, ", score);
+            print!("Time: {:?}", start.elapsed());
+            println!("");
+        }
+        if length_map[&canonical(&state)] < length {
+            duplicate += 1;
+            continue;
+        }
+        if state.iter().all(is_sorted) {
+            solution = Some(extract_program(&prg));
+            break;
+        }
+
+        let prev_rc = Some(Rc::new(prg));
+        for cmd in &possible_cmds {
+            let new_state = apply_all(cmd, &state);
+            let new_length = length + 1;
+            if !viable(&new_state) {
+                cut += 1;
+                continue;
+            }
+            let state_repr = canonical(&new_state);
+            if let Some(&old_length) = length_map.get(&state_repr) {
+                if old_length <= new_length {
+                    duplicate += 1;
+                    continue;
+                }
+            }
+            // after the duplicate check (the depth heuristic is expensive)
+            let new_score = new_length + admissible_heuristic(&new_state, &mut heuristic_cache);
+            if new_score > MAX_LEN {
+                cut += 1;
+                continue;
+            }
+            length_map.insert(state_repr, new_length);
+            let prg = Node{cmd: *cmd, prev: prev_rc.clone()};
+            queue.push((prg,Rc::new(new_state),new_length), Reverse(new_score));
+        }
+    }
+    println!("Visited: {}, Duplicate: {}, Cut: {}", visited, duplicate, cut);
+
+    match solution {
+        Some(cmds) => {
+            println!("Found program of length {}:", cmds.len());
+            for cmd in &cmds {
+                println!("{}", show_command(cmd));
+            }
+            println!("Sorts all permutations of 1..{}: {}", NUMBERS, sorts_permutations(&cmds));
+            if let Ok(dir) = std::env::var("SOLUTION_DIR") {
+                let subdir = format!("{}/{}_lanes_{}", dir, NUMBERS, isa.name());
+                std::fs::create_dir_all(&subdir).unwrap();
+                let mut file = std::fs::File::create(format!("{}/solution.txt", subdir)).unwrap();
+                for cmd in &cmds {
+                    writeln!(file, "{}", show_command(cmd)).unwrap();
+                }
+                println!("Stored solution in: {}", subdir);
+            }
+        }
+        None => println!("No program of length <= {}", MAX_LEN),
+    }
+    println!("Elapsed: {:?}", start.elapsed());
+}
@@ -0,0 +1,422 @@
+use itertools::Itertools;
+use std::ops::Range;
+// has largest value at the top
+use priority_queue::PriorityQueue;
+use std::cmp::Reverse;
+use std::rc::Rc;
+use std::io::Write;
+
+/*
+x86 cmov model with the input in memory (like sort3 in libc++ and AlphaDev: void sort3(int *p))
+
+The input is in the memory slots 0(%rdi), 4(%rdi), .. and the sorted output has to be stored back into them.
+All registers are undefined at the start (not only the scratch registers).
+Instructions:
+  mov r, r' / mov r, [m] (load) / mov [m], r (store)
+  cmp r, r' / cmp r, [m] / cmp [m], r
+  cmovg r, r' / cmovl r, r'  (and with a memory source under ISA=operands)
+There is no memory to memory instruction.
+
+ISA environment variable:
+  loadstore: only mov accesses memory (load, operate on registers, store)
+  operands (default): cmp and cmovcc may take a memory operand (source of cmov, either side of cmp)
+Loads and stores count as instructions => the length compares directly to the published kernels.
+
+Undefined registers hold 0 (not an input value).
+A cmp with an undefined operand makes the flags undefined and a cmov under undefined flags makes its target undefined
+=> a program found here does not depend on the initial register contents.
+With undefined registers, equal keys matter (e.g. cmovg and cmovl both skip a register that is never written)
+=> the inputs are all n^n tuples over 1..n instead of the n! permutations.
+Only sorting: the kernels of SPEC (common.rs) are defined on permutations (a table has no entry for a tuple with equal keys).
+
+A* with admissible heuristic (see main_iterative.rs) => the first solution is optimal.
+*/
+
+// n=2: loadstore 8, operands 7 in <1s
+const NUMBERS: usize = 2;
+const MAX_LEN: u8 = 10;
+const REGS: usize = 3;
+// n=3, ISA=loadstore: lower bound 13 after 11min on the permutations alone (memory exhausted with 5GB)
+// const NUMBERS: usize = 3;
+// const MAX_LEN: u8 = 18;
+// const REGS: usize = 4;
+const FLAGS: usize = REGS;
+const MEMOFFSET: usize = REGS + 2;
+const TARGETOFFSET: usize = MEMOFFSET + NUMBERS; // sorted input (constant)
+
+const CMP: usize = 0;
+const MOV: usize = 1; // register <- register, load and store
+const CMOVG: usize = 2;
+const CMOVL: usize = 3;
+const NUMBERS_U8: u8 = NUMBERS as u8;
+
+const UNDEFINED: u8 = 0;
+const UNKNOWN_FLAG: u8 = 2;
+
+type Command = (usize, usize, usize);
+// registers, lt and gt flag, memory slots, expected memory slots
+#[derive(Clone, Eq, PartialEq, Hash, Ord, PartialOrd, Debug, Copy)]
+struct Permutation([u8; REGS + 2 + 2 * NUMBERS]);
+type State = Vec<Permutation>;
+
+use std::ops::{Index, IndexMut};
+
+impl Index<usize> for Permutation {
+    type Output = u8;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+impl IndexMut<usize> for Permutation {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.0[index]
+    }
+}
+
+impl Index<Range<usize>> for Permutation {
+    type Output = [u8];
+
+    fn index(&self, index: Range<usize>) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Isa {
+    LoadStore,
+    Operands,
+}
+
+impl Isa {
+    fn from_env() -> Isa {
+        let isa = std::env::var("ISA").unwrap_or("operands".to_string());
+        match isa.as_str() {
+            "loadstore" => Isa::LoadStore,
+            "operands" => Isa::Operands,
+            _ => panic!("Unknown ISA: {}", isa),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Isa::LoadStore => "loadstore",
+            Isa::Operands => "operands",
+        }
+    }
+}
+
+fn is_memory(operand: usize) -> bool {
+    operand >= MEMOFFSET
+}
+
+fn possible_commands(isa: Isa) -> Vec<Command> {
+    let regs = 0..REGS;
+    let memory = MEMOFFSET..MEMOFFSET + NUMBERS;
+    let mut commands = vec![];
+    for to in regs.clone() {
+        for from in regs.clone() {
+            if to == from {
+                continue;
+            }
+            commands.push((MOV, to, from));
+            commands.push((CMOVG, to, from));
+            commands.push((CMOVL, to, from));
+            // cmp r, r' and cmp r', r only swap lt and gt
+            if to < from {
+                commands.push((CMP, to, from));
+            }
+        }
+        for m in memory.clone() {
+            commands.push((MOV, to, m));
+            commands.push((MOV, m, to));
+            if isa == Isa::Operands {
+                commands.push((CMOVG, to, m));
+                commands.push((CMOVL, to, m));
+                // cmp [m], r is cmp r, [m] with swapped flags
+                commands.push((CMP, to, m));
+            }
+        }
+    }
+    commands
+}
+
+// transform a permutation according to a command
+fn apply(cmd: &Command, perm: &mut Permutation) {
+    let (instr, to, from) = *cmd;
+    match instr {
+        CMP => {
+            if perm[to] == UNDEFINED || perm[from] == UNDEFINED {
+                perm[FLAGS + 0] = UNKNOWN_FLAG;
+                perm[FLAGS + 1] = UNKNOWN_FLAG;
+            } else {
+                perm[FLAGS + 0] = (perm[to] < perm[from]) as u8;
+                perm[FLAGS + 1] = (perm[to] > perm[from]) as u8;
+            }
+        }
+        MOV => perm[to] = perm[from],
+        CMOVG | CMOVL => {
+            let flag = if instr == CMOVG { perm[FLAGS + 1] } else { perm[FLAGS + 0] };
+            if flag == UNKNOWN_FLAG {
+                if perm[to] != perm[from] {
+                    perm[to] = UNDEFINED;
+                }
+            } else if flag == 1 {
+                perm[to] = perm[from];
+            }
+        }
+        _ => panic!("Unknown instruction"),
+    }
+}
+
+// map a command over all permutations in a state
+fn apply_all(cmd: &Command, state: &State) -> State {
+    let mut new_state = Vec::new();
+    for perm in state {
+        let mut new_perm = perm.clone();
+        apply(cmd, &mut new_perm);
+        new_state.push(new_perm);
+    }
+    new_state.sort();
+    new_state.dedup();
+    new_state
+}
+
+// check if the state can never reach a solution
+// corresponds to delete-relaxed planning check
+// (every value has to survive in a register or memory slot)
+fn viable(state: &State) -> bool {
+    for perm in state {
+        for i in 0..NUMBERS {
+            let n = perm[TARGETOFFSET + i];
+            if !perm[0..REGS].contains(&n) && !perm[MEMOFFSET..MEMOFFSET + NUMBERS].contains(&n) {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+// System V argument/scratch registers (32 bit)
+const REG_NAMES: [&str; 7] = ["eax", "ecx", "edx", "esi", "r8d", "r9d", "r10d"];
+
+// AT&T syntax: memory slots relative to the pointer in rdi
+fn operand_name(operand: usize) -> String {
+    if is_memory(operand) {
+        format!("{}(%rdi)", 4 * (operand - MEMOFFSET))
+    } else {
+        format!("%{}", REG_NAMES[operand])
+    }
+}
+
+// source first (AT&T), cmp to, from sets the flags of to - from
+fn show_command(cmd: &Command) -> String {
+    let (instr, to, from) = *cmd;
+    let (to, from) = (operand_name(to), operand_name(from));
+    match instr {
+        CMP => format!("cmp {}, {}", from, to),
+        MOV => format!("mov {}, {}", from, to),
+        CMOVG => format!("cmovg {}, {}", from, to),
+        CMOVL => format!("cmovl {}, {}", from, to),
+        _ => panic!("Unknown instruction"),
+    }
+}
+
+// linked list to store the commands and pointer to last element
+// shared prefixes (Rc instead of Box) as in main_registers.rs
+#[derive(Clone, Eq, PartialEq, Hash)]
+struct Node {
+    cmd: Command,
+    prev: Option<Rc<Node>>,
+}
+
+// for each permutation, take out register values, concat => serializable byte array
+fn state_positions(state: &State) -> Vec<u8> {
+    state.iter().flat_map(|p| p.0).collect()
+}
+
+// all registers start undefined => they are interchangeable
+// => smallest representation over all register orders (see canonical in main_astar_lanes.rs)
+fn canonical(state: &State) -> State {
+    (0..REGS)
+        .permutations(REGS)
+        .map(|order| {
+            let mut renamed = state.iter().map(|p| {
+                let mut new_perm = *p;
+                for (k, &reg) in order.iter().enumerate() {
+                    new_perm[k] = p[reg];
+                }
+                new_perm
+            }).collect::<Vec<_>>();
+            renamed.sort();
+            renamed
+        })
+        .min()
+        .unwrap()
+}
+
+fn extract_program(node: &Node) -> Vec<Command> {
+    let mut cmds = vec![];
+    let mut node = node;
+    while let Some(prev) = &node.prev {
+        cmds.push(node.cmd);
+        node = prev;
+    }
+    cmds.reverse();
+    cmds
+}
+
+fn is_sorted(perm: &Permutation) -> bool {
+    perm[MEMOFFSET..MEMOFFSET + NUMBERS] == perm[TARGETOFFSET..TARGETOFFSET + NUMBERS]
+}
+
+// every wrong memory slot needs a store
+// + in each permutation, every value of a wrong slot that is in no register has to be read from memory first
+//   (one register written per instruction => one read per value,
+//    a value needed in several slots (equal keys) is read once)
+fn admissible_heuristic(state: &State) -> u8 {
+    let wrong = (0..NUMBERS)
+        .filter(|&i| state.iter().any(|p| p[MEMOFFSET + i] != p[TARGETOFFSET + i]))
+        .collect::<Vec<_>>();
+    let reads = state
+        .iter()
+        .map(|p| wrong.iter().map(|&i| p[TARGETOFFSET + i]).filter(|v| !p[0..REGS].contains(v)).unique().count())
+        .max()
+        .unwrap_or(0);
+    (wrong.len() + reads) as u8
+}
+
+// find unused sled-mapX file in a temporary directory (_CONDOR_SCRATCH_DIR or /tmp/ else)
+fn open_length_map() -> sled::Db {
+    let tmp_dir = std::env::var("_CONDOR_SCRATCH_DIR").unwrap_or("/tmp".to_string());
+    let mut i = 0;
+    let mut path = format!("{}/sled-map{}", tmp_dir, i);
+    while std::path::Path::new(&path).exists() {
+        i += 1;
+        path = format!("{}/sled-map{}", tmp_dir, i);
+    }
+    println!("Using sled map: {}", path);
+    sled::open(path).unwrap()
+}
+
+fn main() {
+    let isa = Isa::from_env();
+    let possible_cmds = possible_commands(isa);
+    // all n^n inputs over 1..n (not only the permutations, see above)
+    let inputs: Vec<Vec<u8>> = (0..NUMBERS).map(|_| 1..=NUMBERS_U8).multi_cartesian_product().collect();
+
+    println!("n = {}", NUMBERS);
+    println!("max_len = {}", MAX_LEN);
+    println!("registers = {}", REGS);
+    println!("isa = {}", isa.name());
+    println!("instruction count = {}", possible_cmds.len());
+    println!("inputs = {} (with equal values)", inputs.len());
+
+    // the input in memory, registers and flags undefined
+    let initial_state: Rc<State> = Rc::new(inputs
+        .iter()
+        .map(|p| {
+            let mut perm = Permutation([UNDEFINED; REGS + 2 + 2 * NUMBERS]);
+            perm[FLAGS + 0] = UNKNOWN_FLAG;
+            perm[FLAGS + 1] = UNKNOWN_FLAG;
+            for (i, &x) in p.iter().enumerate() {
+                perm[MEMOFFSET + i] = x;
+            }
+            for (i, x) in p.iter().sorted().enumerate() {
+                perm[TARGETOFFSET + i] = *x;
+            }
+            perm
+        })
+        .sorted()
+        .collect());
+
+    let length_map = open_length_map();
+    length_map.insert(state_positions(&canonical(&initial_state)), vec![0 as u8]).unwrap();
+    let mut queue = PriorityQueue::new();
+    let node0 = Node{cmd: (0,0,0), prev: None};
+    queue.push((node0,Rc::clone(&initial_state),0 as u8), Reverse(admissible_heuristic(&initial_state)));
+
+    let mut visited : u64 = 0;
+    let mut duplicate : u64 = 0;
+    let mut cut : u64 = 0;
+    let mut solution = None;
+    let start = std::time::Instant::now();
+    while let Some(((prg,state,length), Reverse(score))) = queue.pop() {
+        visited += 1;
+        if visited % 100000 == 0 {
+            print!("Open: {}, ", queue.len());
+            print!("Visited: {}, ", visited);
+            print!("Duplicate: {}, ", duplicate);
+            print!("Cut: {}, ", cut);
+            print!("Current length: {}, ", length);
+            print!("Lower bound: {}, ", score);
+            print!("Time: {:?}", start.elapsed());
+            println!("");
+        }
+
+        if let Some(state_len_vec) = length_map.get(state_positions(&canonical(&state))).unwrap() {
+            if state_len_vec[0] < length {
+                duplicate += 1;
+                continue;
+            }
+        }
+
+        if state.iter().all(is_sorted) {
+            solution = Some(extract_program(&prg));
+            break;
+        }
+
+        let prev_rc = Some(Rc::new(prg));
+        for cmd in &possible_cmds {
+            let new_state = Rc::new(apply_all(cmd, &state));
+            let new_length = length + 1;
+
+            if !viable(&new_state) {
+                cut += 1;
+                continue;
+            }
+
+            let new_score = new_length + admissible_heuristic(&new_state);
+            if new_score > MAX_LEN {
+                cut += 1;
+                continue;
+            }
+
+            let state_repr = state_positions(&canonical(&new_state));
+            if let Some(old_length_vec) = length_map.get(&state_repr).unwrap() {
+                if old_length_vec[0] <= new_length {
+                    duplicate += 1;
+                    continue;
+                }
+            }
+            length_map.insert(state_repr, vec![new_length]).unwrap();
+
+            let prg = Node{cmd: *cmd, prev: prev_rc.clone()};
+            queue.push((prg,Rc::clone(&new_state),new_length), Reverse(new_score));
+        }
+    }
+    println!("Visited: {}, Duplicate: {}, Cut: {}", visited, duplicate, cut);
+
+    match solution {
+        Some(cmds) => {
+            let reads = cmds.iter().filter(|&&(_, _, from)| is_memory(from)).count();
+            let stores = cmds.iter().filter(|&&(_, to, _)| is_memory(to)).count();
+            println!("Found program of length {} ({} memory reads, {} stores):", cmds.len(), reads, stores);
+            for cmd in &cmds {
+                println!("{}", show_command(cmd));
+            }
+            if let Ok(dir) = std::env::var("SOLUTION_DIR") {
+                let subdir = format!("{}/{}_memory_{}", dir, NUMBERS, isa.name());
+                std::fs::create_dir_all(&subdir).unwrap();
+                let mut file = std::fs::File::create(format!("{}/solution.s", subdir)).unwrap();
+                for cmd in &cmds {
+                    writeln!(file, "{}", show_command(cmd)).unwrap();
+                }
+                println!("Stored solution in: {}", subdir);
+            }
+        }
+        None => println!("No program of length <= {}", MAX_LEN),
+    }
+    println!("Elapsed: {:?}", start.elapsed());
+}
@@ -14,6 +14,8 @@ use std::rc::Rc;
 use std::io::Write;
 use std::cmp::min;
 use serde::{Serialize, Deserialize};
+mod common;
+use common::{inputs, Spec};
 
 
 /*
@@ -57,10 +59,12 @@ const MOV: usize = 0; // movdqa
 const MIN: usize = 1; // pminud => compare first and second, move smaller to first
 const MAX: usize = 2;
 const NUMBERS_U8: u8 = NUMBERS as u8;
+// after registers: expected content of the registers 0..NUMBERS (0 = don't care)
+const TARGET: usize = REGS;
 
 type Command = (usize, usize, usize);
 #[derive(Clone, Eq, PartialEq, Hash, Ord, PartialOrd, Debug, Copy)]
-struct Permutation([u8; REGS]);
+struct Permutation([u8; REGS + NUMBERS]);
 type State = Vec<Permutation>;
 
 use std::ops::{Index, IndexMut};
@@ -216,10 +220,11 @@ fn apply_all(cmd: &Command, state: &State) -> State {
 
 // check if the state can never reach a solution
 // corresponds to delete-relaxed planning check
+// every value required by the spec has to be in some register
 fn viable(state: &State) -> bool {
     for perm in state {
-        for n in 1..=NUMBERS_U8 {
-            if !perm[0..REGS].contains(&n) {
+        for &n in &perm[TARGET..TARGET+NUMBERS] {
+            if n != 0 && !perm[0..REGS].contains(&n) {
                 return false;
             }
         }
@@ -227,6 +232,22 @@ fn viable(state: &State) -> bool {
     true
 }
 
+// the registers contain the output expected by the spec
+fn reached(perm: &Permutation) -> bool {
+    (0..NUMBERS).all(|i| perm[TARGET+i] == 0 || perm[i] == perm[TARGET+i])
+}
+
+// registers that are relevant for the spec (others are set to 0)
+fn output_part(perm: &Permutation) -> [u8; NUMBERS] {
+    let mut out = [0; NUMBERS];
+    for i in 0..NUMBERS {
+        if perm[TARGET+i] != 0 {
+            out[i] = perm[i];
+        }
+    }
+    out
+}
+
 fn show_command_human(cmd: &Command) -> String {
     let (instr, to, from) = *cmd;
     // 1-indexed to stay consistent with minizinc
@@ -372,7 +393,9 @@ fn extract_program(node: &Node) -> Vec<Command> {
 
 fn main() {
     let possible_cmds = possible_commands();
-    let permutations: Vec<Vec<u8>> = (1..=NUMBERS_U8).permutations(NUMBERS).collect(); 
+    let spec = Spec::from_env();
+    // only inputs that are constrained by the spec
+    let permutations = inputs(&spec);
     let init_perm_count = permutations.len();
 
     // let perm_count = 6;
@@ -485,6 +508,8 @@ fn main() {
     println!("n = {}", NUMBERS);
     println!("max_len = {}", MAX_LEN);
     println!("swaps = {}", SWAPS);
+    println!("spec = {}", std::env::var("SPEC").unwrap_or("sort".to_string()));
+    println!("inputs = {}", init_perm_count);
 
 
     let length_map = sled::open(path).unwrap();
@@ -493,11 +518,12 @@ fn main() {
     // we use RC to avoid cloning the state
     let initial_state: Rc<State> = Rc::new(permutations
         .iter()
-        .map(|p| {
-            let mut perm = Permutation([0; REGS]);
+        .map(|(p, target)| {
+            let mut perm = Permutation([0; REGS + NUMBERS]);
             for (i, &x) in p.iter().enumerate() {
                 perm[i] = x;
             }
+            perm[TARGET..TARGET+NUMBERS].copy_from_slice(target);
             perm
         })
         .collect());
@@ -590,7 +616,7 @@ fn main() {
 
 
         // if state.iter().all(|p| p[0..NUMBERS] == state[0][0..NUMBERS]) {
-        if state.iter().all(|p| p[0..NUMBERS] == (1..=NUMBERS_U8).collect::<Vec<_>>()) {
+        if state.iter().all(reached) {
             // println!("Found solution: {:?} of length: {}", state, length);
             if solution_count == 0 {
                 println!("Found first solution: {:?} of length: {}", state, length);
@@ -667,7 +693,7 @@ fn main() {
                 continue;
             }
 
-            let new_perm_count = new_state.iter().map(|p| &p[0..NUMBERS]).unique().count();
+            let new_perm_count = new_state.iter().map(output_part).unique().count();
 
             // TODO: why is this not subsumed by a*
             // why is it so good
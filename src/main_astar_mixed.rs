@@ -14,6 +14,8 @@ use std::rc::Rc;
 use std::io::Write;
 use std::cmp::min;
 use serde::{Serialize, Deserialize};
+mod common;
+use common::{inputs, Spec};
 
 
 /*
@@ -66,10 +68,12 @@ const MAX: usize = 5; // only xmm register
 const MOVD: usize = 6; // normal register <-> xmm register
 const MOVDQA: usize = 7; // between xmm registers
 const NUMBERS_U8: u8 = NUMBERS as u8;
+// after the xmm registers: expected content of the first NUMBERS registers of a class (0 = don't care)
+const TARGET: usize = XMMOFFSET + XMMREGS;
 
 type Command = (usize, usize, usize);
 #[derive(Clone, Eq, PartialEq, Hash, Ord, PartialOrd, Debug, Copy)]
-struct Permutation([u8; REGS + 2 + XMMREGS]);
+struct Permutation([u8; REGS + 2 + XMMREGS + NUMBERS]);
 type State = Vec<Permutation>;
 
 use std::ops::{Index, IndexMut};
@@ -286,10 +290,11 @@ fn apply_all(cmd: &Command, state: &State) -> State {
 
 // check if the state can never reach a solution
 // corresponds to delete-relaxed planning check
+// every value required by the spec has to be in some register
 fn viable(state: &State) -> bool {
     for perm in state {
-        for n in 1..=NUMBERS_U8 {
-            if !perm[0..REGS].contains(&n) {
+        for &n in &perm[TARGET..TARGET+NUMBERS] {
+            if n != 0 && !perm[0..REGS].contains(&n) {
                 return false;
             }
         }
@@ -297,6 +302,22 @@ fn viable(state: &State) -> bool {
     true
 }
 
+// the registers of the class starting at offset contain the output expected by the spec
+fn reached(perm: &Permutation, offset: usize) -> bool {
+    (0..NUMBERS).all(|i| perm[TARGET+i] == 0 || perm[offset+i] == perm[TARGET+i])
+}
+
+// registers that are relevant for the spec (others are set to 0)
+fn output_part(perm: &Permutation) -> [u8; NUMBERS] {
+    let mut out = [0; NUMBERS];
+    for i in 0..NUMBERS {
+        if perm[TARGET+i] != 0 {
+            out[i] = perm[i];
+        }
+    }
+    out
+}
+
 fn show_command_human(cmd: &Command) -> String {
     let (instr, to, from) = *cmd;
     // 1-indexed to stay consistent with minizinc
@@ -317,16 +338,14 @@ fn show_command_human(cmd: &Command) -> String {
 
 fn show_command(cmd: &Command) -> String {
     let (instr, to, from) = *cmd;
-    fn reg_name(reg: usize) -> &'static str {
+    // the xmm registers start at XMMOFFSET (after the flags)
+    fn reg_name(reg: usize) -> String {
         match reg {
-            0 => "eax",
-            1 => "ecx",
-            2 => "edx",
-            3 => "r8d",
-            4 => "xmm0",
-            5 => "xmm1",
-            6 => "xmm2",
-            7 => "xmm3",
+            0 => "eax".to_string(),
+            1 => "ecx".to_string(),
+            2 => "edx".to_string(),
+            3 => "r8d".to_string(),
+            _ if reg >= XMMOFFSET && reg < XMMOFFSET + XMMREGS => format!("xmm{}", reg - XMMOFFSET),
             _ => panic!("Unknown register"),
         }
     }
@@ -462,7 +481,9 @@ fn extract_program(node: &Node) -> Vec<Command> {
 
 fn main() {
     let possible_cmds = possible_commands();
-    let permutations: Vec<Vec<u8>> = (1..=NUMBERS_U8).permutations(NUMBERS).collect(); 
+    let spec = Spec::from_env();
+    // only inputs that are constrained by the spec
+    let permutations = inputs(&spec);
     let init_perm_count = permutations.len();
 
     // let perm_count = 6;
@@ -494,7 +515,7 @@ fn main() {
             }
         }
         println!("Computed swaps for {} permutations", swaps_needed.len());
-        if swaps_needed.len() != init_perm_count {
+        if swaps_needed.len() != (1..=NUMBERS).product::<usize>() {
             panic!("Not all permutations found");
         }
     }
@@ -575,6 +596,8 @@ fn main() {
     println!("n = {}", NUMBERS);
     println!("max_len = {}", MAX_LEN);
     println!("swaps = {}", SWAPS);
+    println!("spec = {}", std::env::var("SPEC").unwrap_or("sort".to_string()));
+    println!("inputs = {}", init_perm_count);
     println!("instruction count = {}", possible_cmds.len());
 
 
@@ -584,12 +607,13 @@ fn main() {
     // we use RC to avoid cloning the state
     let initial_state: Rc<State> = Rc::new(permutations
         .iter()
-        .map(|p| {
-            let mut perm = Permutation([0; REGS + 2 + XMMREGS]);
+        .map(|(p, target)| {
+            let mut perm = Permutation([0; REGS + 2 + XMMREGS + NUMBERS]);
             for (i, &x) in p.iter().enumerate() {
                 perm[i] = x;
                 perm[i + XMMOFFSET] = x;
             }
+            perm[TARGET..TARGET+NUMBERS].copy_from_slice(target);
             perm
         })
         .collect());
@@ -697,8 +721,8 @@ fn main() {
         //     p[0..NUMBERS] == (1..=NUMBERS_U8).collect::<Vec<_>>() ||
         //     // p[XMMOFFSET..XMMOFFSET+NUMBERS] == (1..=NUMBERS_U8).collect::<Vec<_>>()
         // ) {
-        if state.iter().all(|p| p[0..NUMBERS] == (1..=NUMBERS_U8).collect::<Vec<_>>()) ||
-           state.iter().all(|p| p[XMMOFFSET..XMMOFFSET+NUMBERS] == (1..=NUMBERS_U8).collect::<Vec<_>>()) 
+        if state.iter().all(|p| reached(p, 0)) ||
+           state.iter().all(|p| reached(p, XMMOFFSET))
         {
 
             // println!("Found solution: {:?} of length: {}", state, length);
@@ -777,7 +801,7 @@ fn main() {
                 continue;
             }
 
-            let new_perm_count = new_state.iter().map(|p| &p[0..NUMBERS]).unique().count();
+            let new_perm_count = new_state.iter().map(output_part).unique().count();
 
             // TODO: why is this not subsumed by a*
             // why is it so good
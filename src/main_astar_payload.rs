@@ -0,0 +1,383 @@
+use itertools::Itertools;
+use std::ops::Range;
+// has largest value at the top
+use priority_queue::PriorityQueue;
+use std::cmp::Reverse;
+use std::rc::Rc;
+use std::io::Write;
+mod common;
+use common::{inputs, Spec};
+
+/*
+Sorting records (key + payload) instead of bare keys
+
+Each key register i has a payload register i.
+The payload is identified with the key it belongs to
+=> initially payload register i holds the same value as key register i
+=> in the end, key register i and payload register i have to hold i+1
+(or whatever the spec, SPEC environment variable, requires in key register i)
+
+Keys and payloads are separate register classes (like regs and xmm in main_astar_mixed.rs):
+- cmp only compares keys (payloads are arbitrary data)
+- mov, cmovg, cmovl stay within a class (the flags set by a key cmp are used for both)
+- each class has its own scratch registers
+
+Argsort (ARGSORT environment variable) is the same search:
+the index registers start with 0, ..., n-1 and follow their keys exactly like payloads.
+Only the register names in the output change.
+(Loading the initial indices is not counted.)
+*/
+
+// const NUMBERS: usize = 2;
+// const MAX_LEN: u8 = 7; // found in < 1s
+const NUMBERS: usize = 3;
+const MAX_LEN: u8 = 20; // > 5 min
+// const NUMBERS: usize = 4;
+// const MAX_LEN: u8 = 32;
+const SWAPS: usize = 1;
+const PAYLOAD_SWAPS: usize = 1;
+const REGS: usize = NUMBERS + SWAPS;
+const PAYLOAD_REGS: usize = NUMBERS + PAYLOAD_SWAPS;
+const PAYLOAD_OFFSET: usize = REGS + 2; // key registers + flags
+const CMP: usize = 0;
+const MOV: usize = 1;
+const CMOVG: usize = 2;
+const CMOVL: usize = 3;
+const NUMBERS_U8: u8 = NUMBERS as u8;
+// after the payload registers: expected key in the registers 0..NUMBERS (0 = don't care)
+const TARGET: usize = PAYLOAD_OFFSET + PAYLOAD_REGS;
+
+type Command = (usize, usize, usize);
+#[derive(Clone, Eq, PartialEq, Hash, Ord, PartialOrd, Debug, Copy)]
+struct Permutation([u8; REGS + 2 + PAYLOAD_REGS + NUMBERS]);
+type State = Vec<Permutation>;
+
+use std::ops::{Index, IndexMut};
+
+impl Index<usize> for Permutation {
+    type Output = u8;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+impl IndexMut<usize> for Permutation {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.0[index]
+    }
+}
+
+impl Index<Range<usize>> for Permutation {
+    type Output = [u8];
+
+    fn index(&self, index: Range<usize>) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+impl IndexMut<Range<usize>> for Permutation {
+    fn index_mut(&mut self, index: Range<usize>) -> &mut Self::Output {
+        &mut self.0[index]
+    }
+}
+
+fn possible_commands() -> Vec<Command> {
+    let mut commands = vec![];
+    for instr in &[MOV, CMOVG, CMOVL] {
+        for to in 0..REGS {
+            for from in 0..REGS {
+                if to != from {
+                    commands.push((*instr, to, from));
+                }
+            }
+        }
+        for to in PAYLOAD_OFFSET..PAYLOAD_OFFSET+PAYLOAD_REGS {
+            for from in PAYLOAD_OFFSET..PAYLOAD_OFFSET+PAYLOAD_REGS {
+                if to != from {
+                    commands.push((*instr, to, from));
+                }
+            }
+        }
+    }
+    for i in 0..REGS {
+        for j in (i + 1)..REGS {
+            commands.push((CMP, i, j));
+        }
+    }
+    commands
+}
+
+// transform a permutation according to a command
+fn apply(cmd: &Command, perm: &mut Permutation) {
+    let (instr, to, from) = *cmd;
+    match instr {
+        CMP => {
+            perm[REGS + 0] = (perm[to] < perm[from]) as u8;
+            perm[REGS + 1] = (perm[to] > perm[from]) as u8;
+        }
+        MOV => perm[to] = perm[from],
+        CMOVG => {
+            if perm[REGS + 1] == 1 {
+                perm[to] = perm[from];
+            }
+        }
+        CMOVL => {
+            if perm[REGS + 0] == 1 {
+                perm[to] = perm[from];
+            }
+        }
+        _ => panic!("Unknown instruction"),
+    }
+}
+
+// map a command over all permutations in a state
+fn apply_all(cmd: &Command, state: &State) -> State {
+    let mut new_state = Vec::new();
+    for perm in state {
+        let mut new_perm = perm.clone();
+        apply(cmd, &mut new_perm);
+        new_state.push(new_perm);
+    }
+    new_state.sort();
+    new_state.dedup();
+    new_state
+}
+
+// check if the state can never reach a solution
+// corresponds to delete-relaxed planning check
+// every key required by the spec and its payload have to be somewhere in their class
+fn viable(state: &State) -> bool {
+    for perm in state {
+        for &n in &perm[TARGET..TARGET+NUMBERS] {
+            if n == 0 {
+                continue;
+            }
+            if !perm[0..REGS].contains(&n) {
+                return false;
+            }
+            if !perm[PAYLOAD_OFFSET..PAYLOAD_OFFSET+PAYLOAD_REGS].contains(&n) {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+// keys are as expected by the spec and each payload is next to its key
+fn reached(perm: &Permutation) -> bool {
+    (0..NUMBERS).all(|i| perm[TARGET+i] == 0 ||
+        (perm[i] == perm[TARGET+i] && perm[PAYLOAD_OFFSET+i] == perm[TARGET+i]))
+}
+
+// keys and payloads that are relevant for the spec (others are set to 0)
+fn output_part(perm: &Permutation) -> ([u8; NUMBERS], [u8; NUMBERS]) {
+    let mut keys = [0; NUMBERS];
+    let mut payloads = [0; NUMBERS];
+    for i in 0..NUMBERS {
+        if perm[TARGET+i] != 0 {
+            keys[i] = perm[i];
+            payloads[i] = perm[PAYLOAD_OFFSET+i];
+        }
+    }
+    (keys, payloads)
+}
+
+fn reg_name(reg: usize, argsort: bool) -> String {
+    // 1-indexed to stay consistent with minizinc
+    if reg < REGS {
+        format!("k{}", reg+1)
+    } else if argsort {
+        format!("i{}", reg-PAYLOAD_OFFSET+1)
+    } else {
+        format!("v{}", reg-PAYLOAD_OFFSET+1)
+    }
+}
+
+fn show_command(cmd: &Command, argsort: bool) -> String {
+    let (instr, to, from) = *cmd;
+    let to = reg_name(to, argsort);
+    let from = reg_name(from, argsort);
+    match instr {
+        CMP => format!("CMP {} {}", to, from),
+        MOV => format!("MOV {} {}", to, from),
+        CMOVG => format!("CMOVG {} {}", to, from),
+        CMOVL => format!("CMOVL {} {}", to, from),
+        _ => panic!("Unknown instruction"),
+    }
+}
+
+// linked list to store the commands and pointer to last element
+#[derive(Clone, Eq, PartialEq, Hash)]
+struct Node {
+    cmd: Command,
+    prev: Option<Box<Node>>,
+}
+
+// for each permutation, take out register values, concat => serializable byte array
+fn state_positions(state: &State) -> Vec<u8> {
+    state.iter().flat_map(|p| p.0).collect()
+}
+
+fn extract_program(node: &Node) -> Vec<Command> {
+    let mut cmds = vec![];
+    let mut node = node;
+    while let Some(prev) = &node.prev {
+        cmds.push(node.cmd);
+        node = prev;
+    }
+    cmds.reverse();
+    cmds
+}
+
+fn main() {
+    let possible_cmds = possible_commands();
+    let spec = Spec::from_env();
+    // only inputs that are constrained by the spec
+    let permutations = inputs(&spec);
+    let init_perm_count = permutations.len();
+    let argsort = std::env::var("ARGSORT").is_ok();
+
+    let mut queue = PriorityQueue::new();
+
+    // find unused sled-mapX file in a temporary directory (_CONDOR_SCRATCH_DIR or /tmp/ else)
+    let tmp_dir = std::env::var("_CONDOR_SCRATCH_DIR").unwrap_or("/tmp".to_string());
+    let mut i = 0;
+    let mut path = format!("{}/sled-map{}", tmp_dir, i);
+    while std::path::Path::new(&path).exists() {
+        i += 1;
+        path = format!("{}/sled-map{}", tmp_dir, i);
+    }
+    println!("Using sled map: {}", path);
+
+    println!("n = {}", NUMBERS);
+    println!("max_len = {}", MAX_LEN);
+    println!("swaps = {}", SWAPS);
+    println!("payload swaps = {}", PAYLOAD_SWAPS);
+    println!("argsort = {}", argsort);
+    println!("spec = {}", std::env::var("SPEC").unwrap_or("sort".to_string()));
+    println!("inputs = {}", init_perm_count);
+
+    let length_map = sled::open(path).unwrap();
+
+    // keys as in main_astar.rs, each payload register is tagged with its key
+    let initial_state: Rc<State> = Rc::new(permutations
+        .iter()
+        .map(|(p, target)| {
+            let mut perm = Permutation([0; REGS + 2 + PAYLOAD_REGS + NUMBERS]);
+            for (i, &x) in p.iter().enumerate() {
+                perm[i] = x;
+                perm[PAYLOAD_OFFSET + i] = x;
+            }
+            perm[TARGET..TARGET+NUMBERS].copy_from_slice(target);
+            perm
+        })
+        .collect());
+
+    length_map.insert(state_positions(&initial_state), vec![0 as u8]).unwrap();
+
+    let node0 = Node{cmd: (0,0,0), prev: None};
+    queue.push((node0,Rc::clone(&initial_state),0 as u8), Reverse(0));
+
+    let mut visited : u64 = 0;
+    let mut duplicate : u64 = 0;
+    let mut cut : u64 = 0;
+
+    let solution_dir = std::env::var("SOLUTION_DIR").ok();
+    let subdir = solution_dir.map(|dir| format!("{}/{}_{}_payload", dir, NUMBERS, MAX_LEN));
+    if let Some(subdir) = &subdir {
+        std::fs::create_dir_all(&subdir).unwrap();
+        println!("Storing solution in: {}", subdir);
+    }
+
+    let mut min_perm_count = [init_perm_count; (MAX_LEN as usize)+1];
+
+    let start = std::time::Instant::now();
+    while let Some(((prg,state,length), _)) = queue.pop() {
+        visited += 1;
+        if visited % 100000 == 0 {
+            print!("Open: {}, ", queue.len());
+            print!("Visited: {}, ", visited);
+            print!("Duplicate: {}, ", duplicate);
+            print!("Cut: {}, ", cut);
+            print!("Current length: {}, ", length);
+            print!("Time: {:?}", start.elapsed());
+            println!("");
+        }
+
+        let state_repr = state_positions(&state);
+        if let Some(state_len_vec) = length_map.get(&state_repr).unwrap() {
+            if state_len_vec[0] < length {
+                duplicate += 1;
+                continue;
+            }
+        }
+
+        if state.iter().all(reached) {
+            println!("Found solution of length: {}", length);
+            print!("Time: {:?}", start.elapsed());
+            println!("");
+
+            let cmds = extract_program(&prg);
+            if let Some(subdir) = &subdir {
+                let file = format!("{}/solution.txt", subdir);
+                let mut file = std::fs::File::create(file).unwrap();
+                for cmd in &cmds {
+                    writeln!(file, "{}", show_command(cmd, argsort)).unwrap();
+                }
+            }
+            println!("Program:");
+            for cmd in cmds {
+                println!("{}", show_command(&cmd, argsort));
+            }
+            break;
+        }
+
+        if length >= MAX_LEN {
+            continue;
+        }
+
+        let prev_box = Some(Box::new(prg));
+
+        for cmd in &possible_cmds {
+            let new_state = Rc::new(apply_all(&cmd, &state));
+            let new_length = length + 1;
+
+            if !viable(&new_state) {
+                cut += 1;
+                continue;
+            }
+
+            // keys and payloads both have to be in place => count them together
+            let new_perm_count = new_state.iter().map(output_part).unique().count();
+            let new_length_u = new_length as usize;
+
+            // same cut as in main_astar.rs
+            if min_perm_count[length as usize] < new_perm_count {
+                cut += 1;
+                continue;
+            }
+            if min_perm_count[new_length_u] > new_perm_count {
+                min_perm_count[new_length_u] = new_perm_count;
+            }
+
+            // if already found with smaller length, skip
+            let state_repr = state_positions(&new_state);
+            if let Some(old_length_vec) = length_map.get(&state_repr).unwrap() {
+                if old_length_vec[0] <= new_length {
+                    duplicate += 1;
+                    continue;
+                }
+            }
+            length_map.insert(state_repr, vec![new_length]).unwrap();
+
+            let heuristic = new_perm_count as u8;
+            let new_score = new_length + heuristic;
+            let prg = Node{cmd: *cmd, prev: prev_box.clone()};
+            queue.push((prg,Rc::clone(&new_state),new_length), Reverse(new_score));
+        }
+    }
+
+    println!("Visited: {}, Duplicate: {}", visited, duplicate);
+    println!("Elapsed: {:?}", start.elapsed());
+}
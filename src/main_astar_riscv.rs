@@ -0,0 +1,482 @@
+use itertools::Itertools;
+use std::ops::Range;
+// has largest value at the top
+use priority_queue::PriorityQueue;
+use std::cmp::Reverse;
+use std::rc::Rc;
+use std::io::Write;
+mod common;
+use common::{inputs, Spec};
+
+/*
+RISC-V instruction model (RV32I/RV64I + Zicond)
+
+There are no flags => comparisons write a general purpose register:
+  slt rd, rs1, rs2         rd = rs1 < rs2 (1 or 0)
+  czero.eqz rd, rs1, rs2   rd = rs2 == 0 ? 0 : rs1
+  czero.nez rd, rs1, rs2   rd = rs2 != 0 ? 0 : rs1
+  add rd, rs1, rs2         sub rd, rs1, rs2
+  and rd, rs1, rs2         or rd, rs1, rs2         xor rd, rs1, rs2
+  mv rd, rs1
+x0 (zero) can be read by every instruction (e.g. sub rd, zero, rs2 = -rs2, slt rd, zero, rs2), it is never written.
+A select (rd = c ? b : a) is czero.eqz t1, b, c; czero.nez t2, a, c; or rd, t1, t2,
+a conditional swap uses the xor of both values:
+  slt t0, a1, a0; xor t1, a0, a1; czero.eqz t1, t1, t0; xor a0, a0, t1; xor a1, a1, t1
+
+Values of a register (u8 like the other models):
+  0        zero
+  1..0x7F  xor of input values (bit v-1 for the input value v, n <= 7), one bit = the input value itself
+  TRUE     1 as result of slt
+  ONES     -1 (0 - TRUE)
+  GARBAGE  anything else (depends on the bits of the input values)
+Only results that hold for all inputs are concrete, e.g. x ^ y, x ^ x, x & -1, 0 - 1, czero with a known condition,
+slt between input values (or between constants). Everything else (e.g. x | y, x + y, slt x, TRUE, czero.eqz with an input value as condition)
+is GARBAGE => a program that sorts all permutations sorts all inputs (as with the other ISAs).
+
+The input is in a0.., the output in the same registers, scratch registers are t0.. (GARBAGE at the start).
+What is expected in the output registers is given by SPEC (see common.rs).
+A* with admissible heuristic (see main_iterative.rs) => the first solution is optimal for this value model.
+*/
+
+// n=2: 5 with 1 scratch register (x86 cmov: 4)
+const NUMBERS: usize = 2;
+const MAX_LEN: u8 = 10;
+const SWAPS: usize = 1;
+// 3 comparators with 5 instructions each => upper bound 15 (lower bound 11 after 5min)
+// const NUMBERS: usize = 3;
+// const MAX_LEN: u8 = 15;
+const REGS: usize = NUMBERS + SWAPS;
+// zero register after the writable ones
+const X0: usize = REGS;
+// after the zero register: expected input value in the registers 0..NUMBERS (0 = don't care)
+const TARGET: usize = X0 + 1;
+
+const SLT: usize = 0;
+const CZEROEQZ: usize = 1;
+const CZERONEZ: usize = 2;
+const OR: usize = 3;
+const XOR: usize = 4;
+const MV: usize = 5;
+const ADD: usize = 6;
+const SUB: usize = 7;
+const AND: usize = 8;
+const NUMBERS_U8: u8 = NUMBERS as u8;
+
+const TRUE: u8 = 0x80;
+const ONES: u8 = 0x81;
+const GARBAGE: u8 = 0xFF;
+
+type Command = (usize, usize, usize, usize);
+#[derive(Clone, Eq, PartialEq, Hash, Ord, PartialOrd, Debug, Copy)]
+struct Permutation([u8; REGS + 1 + NUMBERS]);
+type State = Vec<Permutation>;
+
+use std::ops::{Index, IndexMut};
+
+impl Index<usize> for Permutation {
+    type Output = u8;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+impl IndexMut<usize> for Permutation {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.0[index]
+    }
+}
+
+impl Index<Range<usize>> for Permutation {
+    type Output = [u8];
+
+    fn index(&self, index: Range<usize>) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+impl IndexMut<Range<usize>> for Permutation {
+    fn index_mut(&mut self, index: Range<usize>) -> &mut Self::Output {
+        &mut self.0[index]
+    }
+}
+
+// instructions with x0 as an operand that only copy or zero a register are left out (mv does that)
+fn possible_commands() -> Vec<Command> {
+    let mut commands = vec![];
+    for rd in 0..REGS {
+        for rs1 in 0..=REGS {
+            if rd != rs1 {
+                commands.push((MV, rd, rs1, rs1));
+            }
+            for rs2 in 0..=REGS {
+                if rs1 != rs2 {
+                    commands.push((SLT, rd, rs1, rs2));
+                }
+                if rs1 != rs2 && rs1 != X0 && rs2 != X0 {
+                    commands.push((CZEROEQZ, rd, rs1, rs2));
+                    commands.push((CZERONEZ, rd, rs1, rs2));
+                }
+                if rs1 != rs2 && rs2 != X0 {
+                    commands.push((SUB, rd, rs1, rs2));
+                }
+                // commutative, xor rd, rs, rs zeroes rd
+                if rs1 <= rs2 && rs2 != X0 {
+                    commands.push((XOR, rd, rs1, rs2));
+                }
+                if rs1 < rs2 && rs2 != X0 {
+                    commands.push((OR, rd, rs1, rs2));
+                    commands.push((AND, rd, rs1, rs2));
+                    commands.push((ADD, rd, rs1, rs2));
+                }
+            }
+        }
+    }
+    commands
+}
+
+// register value of the input value v
+fn input(v: u8) -> u8 {
+    1 << (v - 1)
+}
+
+// xor of input values (or zero)
+fn is_xor(x: u8) -> bool {
+    x < TRUE
+}
+
+fn constant(x: u8) -> Option<i32> {
+    match x {
+        0 => Some(0),
+        TRUE => Some(1),
+        ONES => Some(-1),
+        _ => None,
+    }
+}
+
+fn from_constant(c: i32) -> u8 {
+    match c {
+        0 => 0,
+        1 => TRUE,
+        -1 => ONES,
+        _ => GARBAGE,
+    }
+}
+
+// transform a permutation according to a command
+fn apply(cmd: &Command, perm: &mut Permutation) {
+    let (instr, rd, rs1, rs2) = *cmd;
+    let (a, b) = (perm[rs1], perm[rs2]);
+    // equal values of different registers are only equal if they are known
+    let same = a == b && (a != GARBAGE || rs1 == rs2);
+    perm[rd] = match instr {
+        SLT => {
+            // single input values (the bit order is the value order) or constants
+            if is_xor(a) && a.count_ones() == 1 && is_xor(b) && b.count_ones() == 1 {
+                if a < b { TRUE } else { 0 }
+            } else if let (Some(x), Some(y)) = (constant(a), constant(b)) {
+                if x < y { TRUE } else { 0 }
+            } else {
+                GARBAGE
+            }
+        }
+        CZEROEQZ | CZERONEZ => match constant(b) {
+            _ if a == 0 => 0,
+            Some(c) if (c == 0) == (instr == CZEROEQZ) => 0,
+            Some(_) => a,
+            // inputs may be 0
+            None => GARBAGE,
+        },
+        XOR => {
+            if same {
+                0
+            } else if is_xor(a) && is_xor(b) {
+                a ^ b
+            } else if a == 0 {
+                b
+            } else if b == 0 {
+                a
+            } else {
+                GARBAGE
+            }
+        }
+        OR => {
+            if same || b == 0 {
+                a
+            } else if a == 0 {
+                b
+            } else if a == ONES || b == ONES {
+                ONES
+            } else {
+                GARBAGE
+            }
+        }
+        AND => {
+            if same || b == ONES {
+                a
+            } else if a == ONES {
+                b
+            } else if a == 0 || b == 0 {
+                0
+            } else {
+                GARBAGE
+            }
+        }
+        ADD => {
+            if a == 0 {
+                b
+            } else if b == 0 {
+                a
+            } else if let (Some(x), Some(y)) = (constant(a), constant(b)) {
+                from_constant(x + y)
+            } else {
+                GARBAGE
+            }
+        }
+        SUB => {
+            if same {
+                0
+            } else if b == 0 {
+                a
+            } else if let (Some(x), Some(y)) = (constant(a), constant(b)) {
+                from_constant(x - y)
+            } else {
+                GARBAGE
+            }
+        }
+        MV => a,
+        _ => panic!("Unknown instruction"),
+    };
+}
+
+// map a command over all permutations in a state
+fn apply_all(cmd: &Command, state: &State) -> State {
+    let mut new_state = Vec::new();
+    for perm in state {
+        let mut new_perm = perm.clone();
+        apply(cmd, &mut new_perm);
+        new_state.push(new_perm);
+    }
+    new_state.sort();
+    new_state.dedup();
+    new_state
+}
+
+// check if the state can never reach a solution
+// corresponds to delete-relaxed planning check
+// only xor creates new input values => every input value required by the spec has to be an xor of register values
+fn viable(state: &State) -> bool {
+    for perm in state {
+        // xor basis (each element reduced by the previous ones)
+        let mut basis: Vec<u8> = vec![];
+        for &x in &perm[0..REGS] {
+            if is_xor(x) {
+                let reduced = basis.iter().fold(x, |x, &b| x.min(x ^ b));
+                if reduced != 0 {
+                    basis.push(reduced);
+                }
+            }
+        }
+        for &n in &perm[TARGET..TARGET+NUMBERS] {
+            if n != 0 && basis.iter().fold(input(n), |x, &b| x.min(x ^ b)) != 0 {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+// ABI names: arguments a0.., temporaries t0.., zero
+fn reg_name(reg: usize) -> String {
+    if reg == X0 {
+        "zero".to_string()
+    } else if reg < NUMBERS {
+        format!("a{}", reg)
+    } else {
+        format!("t{}", reg - NUMBERS)
+    }
+}
+
+// GNU assembler syntax (destination first)
+fn show_command(cmd: &Command) -> String {
+    let (instr, rd, rs1, rs2) = *cmd;
+    let (rd, rs1, rs2) = (reg_name(rd), reg_name(rs1), reg_name(rs2));
+    match instr {
+        SLT => format!("slt {}, {}, {}", rd, rs1, rs2),
+        CZEROEQZ => format!("czero.eqz {}, {}, {}", rd, rs1, rs2),
+        CZERONEZ => format!("czero.nez {}, {}, {}", rd, rs1, rs2),
+        OR => format!("or {}, {}, {}", rd, rs1, rs2),
+        XOR => format!("xor {}, {}, {}", rd, rs1, rs2),
+        ADD => format!("add {}, {}, {}", rd, rs1, rs2),
+        SUB => format!("sub {}, {}, {}", rd, rs1, rs2),
+        AND => format!("and {}, {}, {}", rd, rs1, rs2),
+        MV => format!("mv {}, {}", rd, rs1),
+        _ => panic!("Unknown instruction"),
+    }
+}
+
+// linked list to store the commands and pointer to last element
+// shared prefixes (Rc instead of Box) as in main_registers.rs
+#[derive(Clone, Eq, PartialEq, Hash)]
+struct Node {
+    cmd: Command,
+    prev: Option<Rc<Node>>,
+}
+
+// for each permutation, take out register values, concat => serializable byte array
+fn state_positions(state: &State) -> Vec<u8> {
+    state.iter().flat_map(|p| p.0).collect()
+}
+
+fn extract_program(node: &Node) -> Vec<Command> {
+    let mut cmds = vec![];
+    let mut node = node;
+    while let Some(prev) = &node.prev {
+        cmds.push(node.cmd);
+        node = prev;
+    }
+    cmds.reverse();
+    cmds
+}
+
+// the registers contain the output expected by the spec
+fn reached(perm: &Permutation) -> bool {
+    (0..NUMBERS).all(|i| perm[TARGET+i] == 0 || perm[i] == input(perm[TARGET+i]))
+}
+
+// each instruction writes at most one register
+// => every register that is wrong in at least one permutation needs one more instruction
+fn admissible_heuristic(state: &State) -> u8 {
+    (0..NUMBERS)
+        .filter(|&i| state.iter().any(|p| p[TARGET+i] != 0 && p[i] != input(p[TARGET+i])))
+        .count() as u8
+}
+
+// find unused sled-mapX file in a temporary directory (_CONDOR_SCRATCH_DIR or /tmp/ else)
+fn open_length_map() -> sled::Db {
+    let tmp_dir = std::env::var("_CONDOR_SCRATCH_DIR").unwrap_or("/tmp".to_string());
+    let mut i = 0;
+    let mut path = format!("{}/sled-map{}", tmp_dir, i);
+    while std::path::Path::new(&path).exists() {
+        i += 1;
+        path = format!("{}/sled-map{}", tmp_dir, i);
+    }
+    println!("Using sled map: {}", path);
+    sled::open(path).unwrap()
+}
+
+fn main() {
+    let possible_cmds = possible_commands();
+    let spec = Spec::from_env();
+    // only inputs that are constrained by the spec
+    let permutations = inputs(&spec);
+
+    println!("n = {}", NUMBERS);
+    println!("max_len = {}", MAX_LEN);
+    println!("swaps = {}", SWAPS);
+    println!("spec = {}", std::env::var("SPEC").unwrap_or("sort".to_string()));
+    println!("inputs = {}", permutations.len());
+    println!("instruction count = {}", possible_cmds.len());
+
+    // scratch registers are not 0 (unlike the other models, or with 0 would be a move)
+    let initial_state: Rc<State> = Rc::new(permutations
+        .iter()
+        .map(|(p, target)| {
+            let mut perm = Permutation([GARBAGE; REGS + 1 + NUMBERS]);
+            for (i, &x) in p.iter().enumerate() {
+                perm[i] = input(x);
+            }
+            perm[X0] = 0;
+            perm[TARGET..TARGET+NUMBERS].copy_from_slice(target);
+            perm
+        })
+        .sorted()
+        .collect());
+
+    let length_map = open_length_map();
+    length_map.insert(state_positions(&initial_state), vec![0 as u8]).unwrap();
+    let mut queue = PriorityQueue::new();
+    let node0 = Node{cmd: (0,0,0,0), prev: None};
+    queue.push((node0,Rc::clone(&initial_state),0 as u8), Reverse(admissible_heuristic(&initial_state)));
+
+    let mut visited : u64 = 0;
+    let mut duplicate : u64 = 0;
+    let mut cut : u64 = 0;
+    let mut solution = None;
+    let start = std::time::Instant::now();
+    while let Some(((prg,state,length), Reverse(score))) = queue.pop() {
+        visited += 1;
+        if visited % 100000 == 0 {
+            print!("Open: {}, ", queue.len());
+            print!("Visited: {}, ", visited);
+            print!("Duplicate: {}, ", duplicate);
+            print!("Cut: {}, ", cut);
+            print!("Current length: {}, ", length);
+            print!("Lower bound: {}, ", score);
+            print!("Time: {:?}", start.elapsed());
+            println!("");
+        }
+
+        if let Some(state_len_vec) = length_map.get(state_positions(&state)).unwrap() {
+            if state_len_vec[0] < length {
+                duplicate += 1;
+                continue;
+            }
+        }
+
+        if state.iter().all(reached) {
+            solution = Some(extract_program(&prg));
+            break;
+        }
+
+        let prev_rc = Some(Rc::new(prg));
+        for cmd in &possible_cmds {
+            let new_state = Rc::new(apply_all(cmd, &state));
+            let new_length = length + 1;
+
+            if !viable(&new_state) {
+                cut += 1;
+                continue;
+            }
+
+            let new_score = new_length + admissible_heuristic(&new_state);
+            if new_score > MAX_LEN {
+                cut += 1;
+                continue;
+            }
+
+            let state_repr = state_positions(&new_state);
+            if let Some(old_length_vec) = length_map.get(&state_repr).unwrap() {
+                if old_length_vec[0] <= new_length {
+                    duplicate += 1;
+                    continue;
+                }
+            }
+            length_map.insert(state_repr, vec![new_length]).unwrap();
+
+            let prg = Node{cmd: *cmd, prev: prev_rc.clone()};
+            queue.push((prg,Rc::clone(&new_state),new_length), Reverse(new_score));
+        }
+    }
+    println!("Visited: {}, Duplicate: {}, Cut: {}", visited, duplicate, cut);
+
+    match solution {
+        Some(cmds) => {
+            println!("Found program of length {}:", cmds.len());
+            for cmd in &cmds {
+                println!("{}", show_command(cmd));
+            }
+            if let Ok(dir) = std::env::var("SOLUTION_DIR") {
+                let subdir = format!("{}/{}_riscv", dir, NUMBERS);
+                std::fs::create_dir_all(&subdir).unwrap();
+                let mut file = std::fs::File::create(format!("{}/solution.s", subdir)).unwrap();
+                for cmd in &cmds {
+                    writeln!(file, "{}", show_command(cmd)).unwrap();
+                }
+                println!("Stored solution in: {}", subdir);
+            }
+        }
+        None => println!("No program of length <= {}", MAX_LEN),
+    }
+    println!("Elapsed: {:?}", start.elapsed());
+}
@@ -0,0 +1,486 @@
+use itertools::Itertools;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::ops::Range;
+use std::rc::Rc;
+use std::io::Write;
+
+/*
+Branching programs: cmp/mov/cmovg/cmovl (main_astar.rs) + conditional jumps jg/jl
+
+A jump splits the state: the permutations with the flag set continue at the label, the others fall through
+=> a program is a tree (a DAG in the output: equal states share their label),
+   each path only has to sort the permutations that reach it.
+A path ends with ret (not counted, like in the straight-line kernels).
+Without jumps the result is a straight-line program.
+
+OBJECTIVE environment variable:
+  worst (default): length of the longest path (the jump counts on both paths),
+                   among the optimal programs the one with the least executed instructions (no dead code)
+  average: average number of executed instructions over all n! inputs,
+           among the programs whose longest path is at most the optimal worst case + SLACK
+
+Search: depth-first on the worst case length with iterative deepening,
+a jump needs both parts solvable within the remaining length (AND node), failed (state, length) pairs are memoized.
+The average objective is an exact minimum over the same trees (memoized per state and remaining length).
+The heuristic counts the wrong output registers (jumps write none) => admissible on every path.
+*/
+
+// n=3: worst 10 in 3s (1s for the bound, straight-line 11), average 8.17 (longest path 10) in 4s
+const NUMBERS: usize = 3;
+const MAX_LEN: u8 = 11;
+const SWAPS: usize = 1;
+// n=4: no program with longest path 10 (1min, each further length ~8x slower)
+// const NUMBERS: usize = 4;
+// const MAX_LEN: u8 = 20;
+const REGS: usize = NUMBERS + SWAPS;
+const SLACK: u8 = 0;
+// flags after the registers
+const LT: usize = REGS;
+const GT: usize = REGS + 1;
+
+const CMP: usize = 0;
+const MOV: usize = 1;
+const CMOVG: usize = 2;
+const CMOVL: usize = 3;
+const JG: usize = 4;
+const JL: usize = 5;
+const NUMBERS_U8: u8 = NUMBERS as u8;
+
+type Command = (usize, usize, usize);
+// registers, lt and gt flag
+#[derive(Clone, Eq, PartialEq, Hash, Ord, PartialOrd, Debug, Copy)]
+struct Permutation([u8; REGS + 2]);
+// contents with the number of inputs that reach them (720 for n=6 => not in the u8 registers)
+type State = Vec<(Permutation, u32)>;
+
+use std::ops::{Index, IndexMut};
+
+impl Index<usize> for Permutation {
+    type Output = u8;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+impl IndexMut<usize> for Permutation {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.0[index]
+    }
+}
+
+impl Index<Range<usize>> for Permutation {
+    type Output = [u8];
+
+    fn index(&self, index: Range<usize>) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Objective {
+    Worst,
+    Average,
+}
+
+impl Objective {
+    fn from_env() -> Objective {
+        let objective = std::env::var("OBJECTIVE").unwrap_or("worst".to_string());
+        match objective.as_str() {
+            "worst" => Objective::Worst,
+            "average" => Objective::Average,
+            _ => panic!("Unknown OBJECTIVE: {}", objective),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Objective::Worst => "worst",
+            Objective::Average => "average",
+        }
+    }
+}
+
+fn possible_commands() -> Vec<Command> {
+    let mut commands = vec![];
+    for instr in &[MOV, CMOVG, CMOVL] {
+        for to in 0..REGS {
+            for from in 0..REGS {
+                if to != from {
+                    commands.push((*instr, to, from));
+                }
+            }
+        }
+    }
+    for i in 0..REGS {
+        for j in (i + 1)..REGS {
+            commands.push((CMP, i, j));
+        }
+    }
+    commands.push((JG, 0, 0));
+    commands.push((JL, 0, 0));
+    commands
+}
+
+// transform a permutation according to a command (jumps do not change registers)
+fn apply(cmd: &Command, perm: &mut Permutation) {
+    let (instr, to, from) = *cmd;
+    match instr {
+        CMP => {
+            perm[LT] = (perm[to] < perm[from]) as u8;
+            perm[GT] = (perm[to] > perm[from]) as u8;
+        }
+        MOV => perm[to] = perm[from],
+        CMOVG => {
+            if perm[GT] == 1 {
+                perm[to] = perm[from];
+            }
+        }
+        CMOVL => {
+            if perm[LT] == 1 {
+                perm[to] = perm[from];
+            }
+        }
+        JG | JL => {}
+        _ => panic!("Unknown instruction"),
+    }
+}
+
+// equal contents are merged, their weights (number of inputs) add up
+fn normalize(mut state: State) -> State {
+    state.sort();
+    let mut merged: State = Vec::new();
+    for (perm, w) in state {
+        match merged.last_mut() {
+            Some((last, last_w)) if *last == perm => *last_w += w,
+            _ => merged.push((perm, w)),
+        }
+    }
+    merged
+}
+
+// map a command over all permutations in a state
+fn apply_all(cmd: &Command, state: &State) -> State {
+    normalize(state.iter().map(|&(perm, w)| {
+        let mut new_perm = perm;
+        apply(cmd, &mut new_perm);
+        (new_perm, w)
+    }).collect())
+}
+
+// (taken, fall through) part of the state, None if one of them is empty (the jump is useless)
+fn split(cmd: &Command, state: &State) -> Option<(State, State)> {
+    let flag = if cmd.0 == JG { GT } else { LT };
+    let (taken, fall): (State, State) = state.iter().partition(|(p, _)| p[flag] == 1);
+    if taken.is_empty() || fall.is_empty() {
+        return None;
+    }
+    Some((taken, fall))
+}
+
+// check if the state can never reach a solution
+// corresponds to delete-relaxed planning check
+fn viable(state: &State) -> bool {
+    for (perm, _) in state {
+        for n in 1..=NUMBERS_U8 {
+            if !perm[0..REGS].contains(&n) {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+fn is_sorted(perm: &Permutation) -> bool {
+    perm[0..NUMBERS].iter().copied().eq(1..=NUMBERS_U8)
+}
+
+fn weight(state: &State) -> u32 {
+    state.iter().map(|(_, w)| w).sum()
+}
+
+// each instruction writes at most one register, jumps none
+// => every register that is wrong in at least one permutation needs one more instruction on every path
+fn admissible_heuristic(state: &State) -> u8 {
+    (0..NUMBERS)
+        .filter(|&i| state.iter().any(|(p, _)| p[i] != (i+1) as u8))
+        .count() as u8
+}
+
+enum Tree {
+    Ret,
+    Step(Command, Rc<Tree>),
+    // (jump, state at the label, taken, fall through)
+    Jump(Command, Rc<State>, Rc<Tree>, Rc<Tree>),
+}
+
+// longest path and total number of executed instructions (over the weights)
+fn costs(tree: &Tree, state: &State) -> (u8, u32) {
+    match tree {
+        Tree::Ret => (0, 0),
+        Tree::Step(cmd, next) => {
+            let (worst, total) = costs(next, &apply_all(cmd, state));
+            (worst + 1, total + weight(state))
+        }
+        Tree::Jump(cmd, _, taken, fall) => {
+            let (taken_state, fall_state) = split(cmd, state).unwrap();
+            let (worst_taken, total_taken) = costs(taken, &taken_state);
+            let (worst_fall, total_fall) = costs(fall, &fall_state);
+            (worst_taken.max(worst_fall) + 1, total_taken + total_fall + weight(state))
+        }
+    }
+}
+
+// run one input down the tree (the check of the straight-line binaries: all permutations)
+fn sorts(tree: &Tree, input: &[u8]) -> bool {
+    let mut perm = Permutation([0; REGS + 2]);
+    for (i, &x) in input.iter().enumerate() {
+        perm[i] = x;
+    }
+    let mut node = tree;
+    loop {
+        match node {
+            Tree::Ret => return is_sorted(&perm),
+            Tree::Step(cmd, next) => {
+                apply(cmd, &mut perm);
+                node = next;
+            }
+            Tree::Jump(cmd, _, taken, fall) => {
+                let flag = if cmd.0 == JG { GT } else { LT };
+                node = if perm[flag] == 1 { taken } else { fall };
+            }
+        }
+    }
+}
+
+// best (total, tree) for a state and remaining length, None if there is no tree
+type Best = Option<(u32, Rc<Tree>)>;
+
+struct Search {
+    possible_cmds: Vec<Command>,
+    // largest remaining length without a solution
+    failed: HashMap<State, u8>,
+    // best (total, tree) per state and remaining length (average objective)
+    best: HashMap<(State, u8), Best>,
+    visited: u64,
+}
+
+impl Search {
+    // some tree with longest path <= len
+    fn worst(&mut self, state: &State, len: u8) -> Option<Rc<Tree>> {
+        self.visited += 1;
+        if state.iter().all(|(p, _)| is_sorted(p)) {
+            return Some(Rc::new(Tree::Ret));
+        }
+        if len == 0 || admissible_heuristic(state) > len {
+            return None;
+        }
+        if let Some(&failed_len) = self.failed.get(state) {
+            if failed_len >= len {
+                return None;
+            }
+        }
+        for i in 0..self.possible_cmds.len() {
+            let cmd = self.possible_cmds[i];
+            if cmd.0 == JG || cmd.0 == JL {
+                if let Some((taken, fall)) = split(&cmd, state) {
+                    if let Some(taken_tree) = self.worst(&taken, len - 1) {
+                        if let Some(fall_tree) = self.worst(&fall, len - 1) {
+                            return Some(Rc::new(Tree::Jump(cmd, Rc::new(taken), taken_tree, fall_tree)));
+                        }
+                    }
+                }
+                continue;
+            }
+            let new_state = apply_all(&cmd, state);
+            if new_state == *state || !viable(&new_state) {
+                continue;
+            }
+            if let Some(tree) = self.worst(&new_state, len - 1) {
+                return Some(Rc::new(Tree::Step(cmd, tree)));
+            }
+        }
+        self.failed.insert(state.clone(), len);
+        None
+    }
+
+    // tree with longest path <= len and the least total number of executed instructions
+    fn average(&mut self, state: &State, len: u8) -> Best {
+        self.visited += 1;
+        if state.iter().all(|(p, _)| is_sorted(p)) {
+            return Some((0, Rc::new(Tree::Ret)));
+        }
+        if len == 0 || admissible_heuristic(state) > len {
+            return None;
+        }
+        let key = (state.clone(), len);
+        if let Some(result) = self.best.get(&key) {
+            return result.clone();
+        }
+        let w = weight(state);
+        // every input executes at least one more instruction and one per wrong register
+        let lower = state
+            .iter()
+            .map(|(p, w)| w * (0..NUMBERS).filter(|&i| p[i] != (i+1) as u8).count().max(1) as u32)
+            .sum::<u32>();
+        let mut best: Best = None;
+        for i in 0..self.possible_cmds.len() {
+            let cmd = self.possible_cmds[i];
+            if best.as_ref().is_some_and(|(best_total, _)| *best_total == lower) {
+                break;
+            }
+            let candidate = if cmd.0 == JG || cmd.0 == JL {
+                match split(&cmd, state) {
+                    Some((taken, fall)) => match (self.average(&taken, len - 1), self.average(&fall, len - 1)) {
+                        (Some((total_taken, taken_tree)), Some((total_fall, fall_tree))) =>
+                            Some((w + total_taken + total_fall, Rc::new(Tree::Jump(cmd, Rc::new(taken), taken_tree, fall_tree)))),
+                        _ => None,
+                    },
+                    None => None,
+                }
+            } else {
+                let new_state = apply_all(&cmd, state);
+                if new_state == *state || !viable(&new_state) {
+                    None
+                } else {
+                    self.average(&new_state, len - 1).map(|(total, tree)| (w + total, Rc::new(Tree::Step(cmd, tree))))
+                }
+            };
+            if let Some((total, tree)) = candidate {
+                if best.as_ref().is_none_or(|(best_total, _)| total < *best_total) {
+                    best = Some((total, tree));
+                }
+            }
+        }
+        self.best.insert(key, best.clone());
+        best
+    }
+}
+
+const REG_NAMES: [&str; 8] = ["eax", "ecx", "edx", "esi", "r8d", "r9d", "r10d", "r11d"];
+
+// AT&T syntax, cmp to, from sets the flags of to - from
+fn show_command(cmd: &Command) -> String {
+    let (instr, to, from) = *cmd;
+    let (to, from) = (REG_NAMES[to], REG_NAMES[from]);
+    match instr {
+        CMP => format!("cmp %{}, %{}", from, to),
+        MOV => format!("mov %{}, %{}", from, to),
+        CMOVG => format!("cmovg %{}, %{}", from, to),
+        CMOVL => format!("cmovl %{}, %{}", from, to),
+        _ => panic!("Unknown instruction"),
+    }
+}
+
+// fall through code first, the labels after the ret of the path
+// a jump to a state that already has a label with a subtree of the same costs reuses it (DAG)
+fn show_program(tree: &Rc<Tree>) -> Vec<String> {
+    let mut lines = vec![];
+    let mut labels: HashMap<(Rc<State>, (u8, u32)), usize> = HashMap::new();
+    let mut pending: Vec<(usize, Rc<Tree>)> = vec![];
+    let mut emitted: HashSet<usize> = HashSet::new();
+    let mut current = Some(Rc::clone(tree));
+    loop {
+        while let Some(node) = current.take() {
+            match &*node {
+                Tree::Ret => lines.push("ret".to_string()),
+                Tree::Step(cmd, next) => {
+                    lines.push(show_command(cmd));
+                    current = Some(Rc::clone(next));
+                }
+                Tree::Jump(cmd, state, taken, fall) => {
+                    let next_label = labels.len() + 1;
+                    let key = (Rc::clone(state), costs(taken, state));
+                    let label = *labels.entry(key).or_insert(next_label);
+                    if label == next_label {
+                        pending.push((label, Rc::clone(taken)));
+                    }
+                    let jump = if cmd.0 == JG { "jg" } else { "jl" };
+                    lines.push(format!("{} .L{}", jump, label));
+                    current = Some(Rc::clone(fall));
+                }
+            }
+        }
+        match pending.pop() {
+            Some((label, node)) => {
+                if emitted.insert(label) {
+                    lines.push(format!(".L{}:", label));
+                    current = Some(node);
+                }
+            }
+            None => break,
+        }
+    }
+    lines
+}
+
+fn main() {
+    let objective = Objective::from_env();
+    let permutations: Vec<Vec<u8>> = (1..=NUMBERS_U8).permutations(NUMBERS).collect();
+
+    println!("n = {}", NUMBERS);
+    println!("max_len = {}", MAX_LEN);
+    println!("swaps = {}", SWAPS);
+    println!("objective = {}", objective.name());
+
+    let initial_state: State = normalize(permutations
+        .iter()
+        .map(|p| {
+            let mut perm = Permutation([0; REGS + 2]);
+            for (i, &x) in p.iter().enumerate() {
+                perm[i] = x;
+            }
+            (perm, 1)
+        })
+        .collect());
+
+    let mut search = Search { possible_cmds: possible_commands(), failed: HashMap::new(), best: HashMap::new(), visited: 0 };
+    println!("instruction count = {}", search.possible_cmds.len());
+    let start = std::time::Instant::now();
+
+    // iterative deepening on the longest path
+    let mut found = None;
+    for len in admissible_heuristic(&initial_state)..=MAX_LEN {
+        let tree = search.worst(&initial_state, len);
+        println!("Longest path {}: {} (visited: {}, time: {:?})", len, if tree.is_some() { "found" } else { "impossible" }, search.visited, start.elapsed());
+        if tree.is_some() {
+            found = Some(len);
+            break;
+        }
+    }
+    let worst_len = match found {
+        Some(found) => found,
+        None => {
+            println!("No program with paths of length <= {}", MAX_LEN);
+            return;
+        }
+    };
+
+    // the first tree within the bound may contain instructions that are overwritten on a path
+    // => also for the worst objective the least total among the trees within the bound
+    let len = match objective {
+        Objective::Worst => worst_len,
+        Objective::Average => (worst_len + SLACK).min(MAX_LEN),
+    };
+    let (_, tree) = search.average(&initial_state, len).unwrap();
+
+    let (worst, total) = costs(&tree, &initial_state);
+    println!("Found program with longest path {}, average path {:.2}:", worst, total as f64 / permutations.len() as f64);
+    let lines = show_program(&tree);
+    for line in &lines {
+        println!("{}", line);
+    }
+    println!("Sorts all permutations of 1..{}: {}", NUMBERS, permutations.iter().all(|p| sorts(&tree, p)));
+    println!("Elapsed: {:?}", start.elapsed());
+
+    if let Ok(dir) = std::env::var("SOLUTION_DIR") {
+        let subdir = format!("{}/{}_branch_{}", dir, NUMBERS, objective.name());
+        std::fs::create_dir_all(&subdir).unwrap();
+        let mut file = std::fs::File::create(format!("{}/solution.s", subdir)).unwrap();
+        for line in &lines {
+            writeln!(file, "{}", line).unwrap();
+        }
+        println!("Stored solution in: {}", subdir);
+    }
+}
@@ -0,0 +1,371 @@
+use itertools::Itertools;
+use std::collections::HashMap;
+use std::ops::Range;
+use rand::seq::SliceRandom;
+// has largest value at the top
+use priority_queue::PriorityQueue;
+use std::cmp::Reverse;
+use std::rc::Rc;
+use std::io::Write;
+
+/*
+Counterexample guided synthesis (CEGIS)
+
+Instead of starting the A* with all n! permutations, we start with a small
+random subset (see the commented out choose_multiple in main_astar.rs).
+Every program found for the subset is verified against all permutations.
+Failing inputs are added to the subset and the search is restarted.
+
+A program that sorts all permutations also sorts every subset
+=> if the search for a subset fails, there is no program of length MAX_LEN at all.
+For this to hold, the search may not use the greedy min_perm_count cut from main_astar.rs
+(on subsets it regularly cuts away all solutions).
+We first search with the cut (fast) and only fall back to the exhaustive search if it fails.
+*/
+
+// const NUMBERS: usize = 3;
+// const MAX_LEN: u8 = 11;
+// const NUMBERS: usize = 4;
+// const MAX_LEN: u8 = 20;
+const NUMBERS: usize = 5;
+const MAX_LEN: u8 = 33;
+const SWAPS: usize = 1;
+// const NUMBERS: usize = 6;
+// const MAX_LEN: u8 = 45;
+// const SWAPS: usize = 2;
+const REGS: usize = NUMBERS + SWAPS;
+const CMP: usize = 0;
+const MOV: usize = 1;
+const CMOVG: usize = 2;
+const CMOVL: usize = 3;
+const NUMBERS_U8: u8 = NUMBERS as u8;
+
+// number of permutations in the first round
+const INIT_PERM_COUNT: usize = 6;
+// number of failing permutations added per round
+// (adding all failing inputs makes the states large again)
+const CEX_PER_ROUND: usize = 2;
+
+type Command = (usize, usize, usize);
+#[derive(Clone, Eq, PartialEq, Hash, Ord, PartialOrd, Debug, Copy)]
+struct Permutation([u8; REGS + 2]);
+type State = Vec<Permutation>;
+
+use std::ops::{Index, IndexMut};
+
+impl Index<usize> for Permutation {
+    type Output = u8;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+impl IndexMut<usize> for Permutation {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.0[index]
+    }
+}
+
+impl Index<Range<usize>> for Permutation {
+    type Output = [u8];
+
+    fn index(&self, index: Range<usize>) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+impl IndexMut<Range<usize>> for Permutation {
+    fn index_mut(&mut self, index: Range<usize>) -> &mut Self::Output {
+        &mut self.0[index]
+    }
+}
+
+fn possible_commands() -> Vec<Command> {
+    let mut commands = vec![];
+    for instr in &[MOV, CMOVG, CMOVL] {
+        for to in 0..REGS {
+            for from in 0..REGS {
+                if to != from {
+                    commands.push((*instr, to, from));
+                }
+            }
+        }
+    }
+    for i in 0..REGS {
+        for j in (i + 1)..REGS {
+            commands.push((CMP, i, j));
+        }
+    }
+    commands
+}
+
+// transform a permutation according to a command
+fn apply(cmd: &Command, perm: &mut Permutation) {
+    let (instr, to, from) = *cmd;
+    match instr {
+        CMP => {
+            perm[REGS + 0] = (perm[to] < perm[from]) as u8;
+            perm[REGS + 1] = (perm[to] > perm[from]) as u8;
+        }
+        MOV => perm[to] = perm[from],
+        CMOVG => {
+            if perm[REGS + 1] == 1 {
+                perm[to] = perm[from];
+            }
+        }
+        CMOVL => {
+            if perm[REGS + 0] == 1 {
+                perm[to] = perm[from];
+            }
+        }
+        _ => panic!("Unknown instruction"),
+    }
+}
+
+// map a command over all permutations in a state
+fn apply_all(cmd: &Command, state: &State) -> State {
+    let mut new_state = Vec::new();
+    for perm in state {
+        let mut new_perm = perm.clone();
+        apply(cmd, &mut new_perm);
+        new_state.push(new_perm);
+    }
+    new_state.sort();
+    new_state.dedup();
+    new_state
+}
+
+// check if the state can never reach a solution
+// corresponds to delete-relaxed planning check
+fn viable(state: &State) -> bool {
+    for perm in state {
+        for n in 1..=NUMBERS_U8 {
+            if !perm[0..REGS].contains(&n) {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+fn show_command(cmd: &Command) -> String {
+    let (instr, to, from) = *cmd;
+    // 1-indexed to stay consistent with minizinc
+    let to = to+1;
+    let from = from+1;
+    match instr {
+        CMP => format!("CMP {} {}", to, from),
+        MOV => format!("MOV {} {}", to, from),
+        CMOVG => format!("CMOVG {} {}", to, from),
+        CMOVL => format!("CMOVL {} {}", to, from),
+        _ => panic!("Unknown instruction"),
+    }
+}
+
+// linked list to store the commands and pointer to last element
+#[derive(Clone, Eq, PartialEq, Hash)]
+struct Node {
+    cmd: Command,
+    prev: Option<Box<Node>>,
+}
+
+// for each permutation, take out register values, concat => serializable byte array
+fn state_positions(state: &State) -> Vec<u8> {
+    state.iter().flat_map(|p| p.0).collect()
+}
+
+fn extract_program(node: &Node) -> Vec<Command> {
+    let mut cmds = vec![];
+    let mut node = node;
+    while let Some(prev) = &node.prev {
+        cmds.push(node.cmd);
+        node = prev;
+    }
+    cmds.reverse();
+    cmds
+}
+
+// extend numerical permutations with register for swap and flags
+fn initial_state(permutations: &[Vec<u8>]) -> State {
+    let mut state: State = permutations
+        .iter()
+        .map(|p| {
+            let mut perm = Permutation([0; REGS + 2]);
+            for (i, &x) in p.iter().enumerate() {
+                perm[i] = x;
+            }
+            perm
+        })
+        .collect();
+    state.sort();
+    state
+}
+
+fn is_sorted(perm: &Permutation) -> bool {
+    perm[0..NUMBERS].iter().copied().eq(1..=NUMBERS_U8)
+}
+
+// run the program on every permutation and collect the inputs that are not sorted afterwards
+fn counterexamples(cmds: &[Command], permutations: &[Vec<u8>]) -> Vec<Vec<u8>> {
+    permutations
+        .iter()
+        .filter(|p| {
+            let mut perm = initial_state(&[p.to_vec()])[0];
+            for cmd in cmds {
+                apply(cmd, &mut perm);
+            }
+            !is_sorted(&perm)
+        })
+        .cloned()
+        .collect()
+}
+
+// A* (as in main_astar.rs) for the given input permutations
+// returns the first program that sorts all of them
+// without the greedy cut, None means that no such program exists
+fn search(possible_cmds: &[Command], permutations: &[Vec<u8>], greedy_cut: bool) -> Option<Vec<Command>> {
+    let init_perm_count = permutations.len();
+    let initial_state: Rc<State> = Rc::new(initial_state(permutations));
+
+    // the states are small => an in-memory map is sufficient
+    let mut length_map: HashMap<Vec<u8>, u8> = HashMap::new();
+    length_map.insert(state_positions(&initial_state), 0);
+
+    let mut queue = PriorityQueue::new();
+    let node0 = Node{cmd: (0,0,0), prev: None};
+    queue.push((node0,Rc::clone(&initial_state),0 as u8), Reverse(0));
+
+    let mut min_perm_count = [init_perm_count; (MAX_LEN as usize)+1];
+
+    let mut visited : u64 = 0;
+    let mut duplicate : u64 = 0;
+    let mut cut : u64 = 0;
+    let start = std::time::Instant::now();
+    while let Some(((prg,state,length), _)) = queue.pop() {
+        visited += 1;
+        if visited % 100000 == 0 {
+            print!("Open: {}, ", queue.len());
+            print!("Visited: {}, ", visited);
+            print!("Duplicate: {}, ", duplicate);
+            print!("Cut: {}, ", cut);
+            print!("Current length: {}, ", length);
+            print!("Time: {:?}", start.elapsed());
+            println!("");
+        }
+
+        if length_map[&state_positions(&state)] < length {
+            duplicate += 1;
+            continue;
+        }
+
+        if state.iter().all(is_sorted) {
+            println!("Visited: {}, Duplicate: {}, Cut: {}", visited, duplicate, cut);
+            return Some(extract_program(&prg));
+        }
+
+        if length >= MAX_LEN {
+            continue;
+        }
+
+        let prev_box = Some(Box::new(prg));
+        for cmd in possible_cmds {
+            let new_state = Rc::new(apply_all(cmd, &state));
+            let new_length = length + 1;
+
+            if !viable(&new_state) {
+                cut += 1;
+                continue;
+            }
+
+            let new_perm_count = new_state.iter().map(|p| &p[0..NUMBERS]).unique().count();
+            let new_length_u = new_length as usize;
+
+            // same (greedy) cut as in main_astar.rs
+            if greedy_cut && min_perm_count[length as usize] < new_perm_count {
+                cut += 1;
+                continue;
+            }
+            if min_perm_count[new_length_u] > new_perm_count {
+                min_perm_count[new_length_u] = new_perm_count;
+            }
+
+            let state_repr = state_positions(&new_state);
+            if let Some(&old_length) = length_map.get(&state_repr) {
+                if old_length <= new_length {
+                    duplicate += 1;
+                    continue;
+                }
+            }
+            length_map.insert(state_repr, new_length);
+
+            let heuristic = new_perm_count as u8;
+            let new_score = new_length + heuristic;
+            let prg = Node{cmd: *cmd, prev: prev_box.clone()};
+            queue.push((prg,Rc::clone(&new_state),new_length), Reverse(new_score));
+        }
+    }
+    println!("Visited: {}, Duplicate: {}, Cut: {}", visited, duplicate, cut);
+    None
+}
+
+fn main() {
+    let possible_cmds = possible_commands();
+    let permutations: Vec<Vec<u8>> = (1..=NUMBERS_U8).permutations(NUMBERS).collect();
+
+    println!("n = {}", NUMBERS);
+    println!("max_len = {}", MAX_LEN);
+    println!("swaps = {}", SWAPS);
+
+    let mut rng = rand::thread_rng();
+    let mut subset = permutations
+        .choose_multiple(&mut rng, INIT_PERM_COUNT.min(permutations.len()))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let start = std::time::Instant::now();
+    let mut round = 0;
+    loop {
+        round += 1;
+        println!("Round {}: searching with {} of {} permutations", round, subset.len(), permutations.len());
+        let mut cmds = search(&possible_cmds, &subset, true);
+        if cmds.is_none() {
+            println!("Greedy search failed, retrying without cut");
+            cmds = search(&possible_cmds, &subset, false);
+        }
+        let cmds = match cmds {
+            Some(cmds) => cmds,
+            None => {
+                // every program for all permutations would also work on the subset
+                println!("No program of length <= {} for the subset => none for all permutations", MAX_LEN);
+                break;
+            }
+        };
+        println!("Candidate of length {} after {:?}", cmds.len(), start.elapsed());
+
+        let failing = counterexamples(&cmds, &permutations);
+        if failing.is_empty() {
+            println!("Verified on all {} permutations", permutations.len());
+            println!("Program:");
+            for cmd in &cmds {
+                println!("{}", show_command(cmd));
+            }
+            if let Ok(dir) = std::env::var("SOLUTION_DIR") {
+                let subdir = format!("{}/{}_{}_cegis", dir, NUMBERS, MAX_LEN);
+                std::fs::create_dir_all(&subdir).unwrap();
+                let mut file = std::fs::File::create(format!("{}/solution.txt", subdir)).unwrap();
+                for cmd in &cmds {
+                    writeln!(file, "{}", show_command(cmd)).unwrap();
+                }
+                println!("Stored solution in: {}", subdir);
+            }
+            break;
+        }
+
+        println!("Candidate fails on {} permutations", failing.len());
+        subset.extend(failing.choose_multiple(&mut rng, CEX_PER_ROUND).cloned());
+    }
+
+    println!("Rounds: {}", round);
+    println!("Elapsed: {:?}", start.elapsed());
+}
@@ -0,0 +1,574 @@
+use itertools::Itertools;
+use std::collections::HashMap;
+// has largest value at the top
+use priority_queue::PriorityQueue;
+use std::cmp::Reverse;
+use std::rc::Rc;
+use std::io::Write;
+
+/*
+A* search for an instruction set read from a description file (instead of possible_commands, apply, show_command)
+
+Usage: isa FILE   (examples in isa/: cmov.isa = main_astar.rs, minmax.isa = main_astar_minmax.rs, ...)
+
+Format (one directive per line, # starts a comment):
+  class NAME REG...        register class with the assembly names of its registers (the order gives the indices)
+  flag NAME                boolean state like the lt/gt flags of cmp (0 at the start)
+  input CLASS              the first n registers of the class hold the input (several lines: in every class)
+  output CLASS             the first n registers of the class hold the sorted output
+  instr NAME OP:CLASS...   opcode with register operands, the following lines belong to it:
+    where EXPR             constraint on the operand indices within their class (no flags), e.g. d != s, a < b
+    TARGET = EXPR          effect on an operand or a flag, all right-hand sides see the state before the instruction
+    asm TEMPLATE           assembly, {OP} is replaced by the register name
+Expressions: numbers, operands, flags, min(x, y), max(x, y), !x, x && y, x || y,
+             x < y, x > y, x <= y, x >= y, x == y, x != y, c ? x : y
+Registers hold the values 1..n of the permutation (0 = empty scratch register), comparisons are 0 or 1.
+A register effect only takes operands, min/max of those or c ? x : y with such branches (checked while reading),
+numbers and boolean results can only be assigned to flags.
+
+As in main_astar.rs, every input value has to stay in some register (no expression creates values)
+and the heuristic counts the wrong output registers (divided by the most registers an instruction writes).
+The GPU binaries (gpu.cl) still use the built-in instruction set.
+*/
+
+// n=3: cmov.isa 11 in 12s, minmax.isa 8, avx.isa 6, xchg.isa 11 in 93s (same as the built-in models)
+const NUMBERS: usize = 3;
+const MAX_LEN: u8 = 20;
+const NUMBERS_U8: u8 = NUMBERS as u8;
+
+#[derive(Clone, PartialEq, Debug)]
+enum Token {
+    Num(u8),
+    Name(String),
+    Op(String),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Var {
+    Operand(usize),
+    Flag(usize),
+}
+
+#[derive(Clone, Debug)]
+enum Expr {
+    Num(u8),
+    Var(Var),
+    Not(Box<Expr>),
+    Binary(String, Box<Expr>, Box<Expr>),
+    Cond(Box<Expr>, Box<Expr>, Box<Expr>),
+}
+
+struct Instr {
+    name: String,
+    // (name, class)
+    operands: Vec<(String, usize)>,
+    constraints: Vec<Expr>,
+    effects: Vec<(Var, Expr)>,
+    template: String,
+}
+
+struct Isa {
+    // (name, register names)
+    classes: Vec<(String, Vec<String>)>,
+    flags: Vec<String>,
+    inputs: Vec<usize>,
+    output: usize,
+    instrs: Vec<Instr>,
+}
+
+// (instr, global register of each operand)
+type Command = (usize, Vec<usize>);
+// registers of all classes, then the flags
+type Permutation = Vec<u8>;
+type State = Vec<Permutation>;
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let chars = text.chars().collect::<Vec<_>>();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                i += 1;
+            }
+            let number = chars[start..i].iter().collect::<String>();
+            tokens.push(Token::Num(number.parse().map_err(|_| format!("Invalid number: {}", number))?));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Name(chars[start..i].iter().collect()));
+        } else {
+            let two = chars[i..(i + 2).min(chars.len())].iter().collect::<String>();
+            if ["<=", ">=", "==", "!=", "&&", "||"].contains(&two.as_str()) {
+                tokens.push(Token::Op(two));
+                i += 2;
+            } else if "<>!?:(),".contains(c) {
+                tokens.push(Token::Op(c.to_string()));
+                i += 1;
+            } else {
+                return Err(format!("Unexpected character: {}", c));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+// recursive descent, precedence (low to high): ?:, ||, &&, comparisons, !
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    resolve: &'a dyn Fn(&str) -> Option<Var>,
+}
+
+impl<'a> Parser<'a> {
+    fn peek_op(&self, op: &str) -> bool {
+        self.tokens.get(self.pos) == Some(&Token::Op(op.to_string()))
+    }
+
+    fn expect_op(&mut self, op: &str) -> Result<(), String> {
+        if !self.peek_op(op) {
+            return Err(format!("Expected {}", op));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn expr(&mut self) -> Result<Expr, String> {
+        let cond = self.or()?;
+        if self.peek_op("?") {
+            self.pos += 1;
+            let then = self.expr()?;
+            self.expect_op(":")?;
+            let otherwise = self.expr()?;
+            return Ok(Expr::Cond(Box::new(cond), Box::new(then), Box::new(otherwise)));
+        }
+        Ok(cond)
+    }
+
+    fn binary(&mut self, ops: &[&str], next: fn(&mut Self) -> Result<Expr, String>) -> Result<Expr, String> {
+        let mut left = next(self)?;
+        while let Some(op) = ops.iter().find(|op| self.peek_op(op)) {
+            self.pos += 1;
+            let right = next(self)?;
+            left = Expr::Binary(op.to_string(), Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        self.binary(&["||"], Self::and)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        self.binary(&["&&"], Self::comparison)
+    }
+
+    fn comparison(&mut self) -> Result<Expr, String> {
+        self.binary(&["<=", ">=", "==", "!=", "<", ">"], Self::unary)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.peek_op("!") {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        self.atom()
+    }
+
+    fn atom(&mut self) -> Result<Expr, String> {
+        let token = self.tokens.get(self.pos).cloned().ok_or("Unexpected end of expression")?;
+        self.pos += 1;
+        match token {
+            Token::Num(value) => Ok(Expr::Num(value)),
+            Token::Name(name) if (name == "min" || name == "max") && self.peek_op("(") => {
+                self.pos += 1;
+                let left = self.expr()?;
+                self.expect_op(",")?;
+                let right = self.expr()?;
+                self.expect_op(")")?;
+                Ok(Expr::Binary(name, Box::new(left), Box::new(right)))
+            }
+            Token::Name(name) => (self.resolve)(&name).map(Expr::Var).ok_or(format!("Unknown name: {}", name)),
+            Token::Op(op) if op == "(" => {
+                let inner = self.expr()?;
+                self.expect_op(")")?;
+                Ok(inner)
+            }
+            Token::Op(op) => Err(format!("Unexpected {}", op)),
+        }
+    }
+}
+
+fn parse_expr(text: &str, resolve: &dyn Fn(&str) -> Option<Var>) -> Result<Expr, String> {
+    let mut parser = Parser { tokens: tokenize(text)?, pos: 0, resolve };
+    let expr = parser.expr()?;
+    if parser.pos != parser.tokens.len() {
+        return Err(format!("Unexpected {:?}", parser.tokens[parser.pos]));
+    }
+    Ok(expr)
+}
+
+fn eval(expr: &Expr, var: &dyn Fn(Var) -> u8) -> u8 {
+    match expr {
+        Expr::Num(value) => *value,
+        Expr::Var(v) => var(*v),
+        Expr::Not(inner) => (eval(inner, var) == 0) as u8,
+        Expr::Cond(cond, then, otherwise) => {
+            if eval(cond, var) != 0 { eval(then, var) } else { eval(otherwise, var) }
+        }
+        Expr::Binary(op, left, right) => {
+            let (a, b) = (eval(left, var), eval(right, var));
+            match op.as_str() {
+                "min" => a.min(b),
+                "max" => a.max(b),
+                "&&" => (a != 0 && b != 0) as u8,
+                "||" => (a != 0 || b != 0) as u8,
+                "<" => (a < b) as u8,
+                ">" => (a > b) as u8,
+                "<=" => (a <= b) as u8,
+                ">=" => (a >= b) as u8,
+                "==" => (a == b) as u8,
+                "!=" => (a != b) as u8,
+                _ => panic!("Unknown operator: {}", op),
+            }
+        }
+    }
+}
+
+// constraints are evaluated on the operand indices only
+fn uses_flag(expr: &Expr) -> bool {
+    match expr {
+        Expr::Num(_) => false,
+        Expr::Var(v) => matches!(v, Var::Flag(_)),
+        Expr::Not(inner) => uses_flag(inner),
+        Expr::Cond(cond, then, otherwise) => uses_flag(cond) || uses_flag(then) || uses_flag(otherwise),
+        Expr::Binary(_, left, right) => uses_flag(left) || uses_flag(right),
+    }
+}
+
+// register effects may only move, select or compare-and-select register values
+// (numbers, flags and boolean results would be taken for input values)
+fn value_typed(expr: &Expr) -> bool {
+    match expr {
+        Expr::Num(_) | Expr::Not(_) => false,
+        Expr::Var(v) => matches!(v, Var::Operand(_)),
+        Expr::Cond(_, then, otherwise) => value_typed(then) && value_typed(otherwise),
+        Expr::Binary(op, left, right) => (op == "min" || op == "max") && value_typed(left) && value_typed(right),
+    }
+}
+
+fn read_isa(content: &str) -> Result<Isa, String> {
+    let mut isa = Isa { classes: vec![], flags: vec![], inputs: vec![], output: usize::MAX, instrs: vec![] };
+    for (number, line) in content.lines().enumerate() {
+        let line = line.split('#').next().unwrap().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let error = |msg: String| format!("Line {}: {}", number + 1, msg);
+        let (directive, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.trim();
+        let class_index = |name: &str| isa.classes.iter().position(|(c, _)| c == name).ok_or(format!("Unknown class: {}", name));
+        match directive {
+            "class" => {
+                let mut words = rest.split_whitespace();
+                let name = words.next().ok_or(error("Missing class name".to_string()))?;
+                isa.classes.push((name.to_string(), words.map(|w| w.to_string()).collect()));
+            }
+            "flag" => isa.flags.push(rest.to_string()),
+            "input" => isa.inputs.push(class_index(rest).map_err(error)?),
+            "output" => isa.output = class_index(rest).map_err(error)?,
+            "instr" => {
+                let mut words = rest.split_whitespace();
+                let name = words.next().ok_or(error("Missing instruction name".to_string()))?;
+                let mut operands = vec![];
+                for word in words {
+                    let (op, class) = word.split_once(':').ok_or(error(format!("Operand without class: {}", word)))?;
+                    operands.push((op.to_string(), class_index(class).map_err(error)?));
+                }
+                isa.instrs.push(Instr { name: name.to_string(), operands, constraints: vec![], effects: vec![], template: String::new() });
+            }
+            _ => {
+                let flags = isa.flags.clone();
+                let instr = isa.instrs.last_mut().ok_or(error(format!("Unknown directive: {}", directive)))?;
+                let operands = instr.operands.iter().map(|(op, _)| op.clone()).collect::<Vec<_>>();
+                let resolve = |name: &str| {
+                    operands.iter().position(|op| op == name).map(Var::Operand)
+                        .or(flags.iter().position(|f| f == name).map(Var::Flag))
+                };
+                if directive == "where" {
+                    let constraint = parse_expr(rest, &resolve).map_err(error)?;
+                    if uses_flag(&constraint) {
+                        return Err(error("Flags are not allowed in a constraint".to_string()));
+                    }
+                    instr.constraints.push(constraint);
+                } else if directive == "asm" {
+                    instr.template = rest.trim_matches('"').to_string();
+                } else if let Some((target, expr)) = line.split_once('=') {
+                    let target = resolve(target.trim()).ok_or(error(format!("Unknown target: {}", target.trim())))?;
+                    let expr = parse_expr(expr, &resolve).map_err(error)?;
+                    if matches!(target, Var::Operand(_)) && !value_typed(&expr) {
+                        return Err(error("A register can only take operands, min/max or c ? x : y of those".to_string()));
+                    }
+                    instr.effects.push((target, expr));
+                } else {
+                    return Err(error(format!("Unknown directive: {}", directive)));
+                }
+            }
+        }
+    }
+    if isa.inputs.is_empty() || isa.output == usize::MAX {
+        return Err("Missing input or output class".to_string());
+    }
+    for &class in isa.inputs.iter().chain([isa.output].iter()) {
+        if isa.classes[class].1.len() < NUMBERS {
+            return Err(format!("Class {} has less than {} registers", isa.classes[class].0, NUMBERS));
+        }
+    }
+    Ok(isa)
+}
+
+impl Isa {
+    fn offset(&self, class: usize) -> usize {
+        self.classes[..class].iter().map(|(_, regs)| regs.len()).sum()
+    }
+
+    fn regs(&self) -> usize {
+        self.offset(self.classes.len())
+    }
+
+    fn reg_name(&self, reg: usize) -> &str {
+        let class = (0..self.classes.len()).rev().find(|&c| self.offset(c) <= reg).unwrap();
+        &self.classes[class].1[reg - self.offset(class)]
+    }
+
+    // all operand assignments that satisfy the constraints
+    fn possible_commands(&self) -> Vec<Command> {
+        let mut commands = vec![];
+        for (i, instr) in self.instrs.iter().enumerate() {
+            let assignments = instr.operands
+                .iter()
+                .map(|(_, class)| 0..self.classes[*class].1.len())
+                .multi_cartesian_product();
+            for indices in assignments {
+                let index = |v: Var| match v {
+                    Var::Operand(k) => indices[k] as u8,
+                    Var::Flag(_) => panic!("Flags in a constraint"),
+                };
+                if instr.constraints.iter().all(|c| eval(c, &index) != 0) {
+                    let regs = instr.operands.iter().zip(&indices).map(|((_, class), k)| self.offset(*class) + k).collect();
+                    commands.push((i, regs));
+                }
+            }
+        }
+        commands
+    }
+
+    // transform a permutation according to a command
+    fn apply(&self, cmd: &Command, perm: &Permutation) -> Permutation {
+        let (instr, regs) = cmd;
+        let slot = |v: Var| match v {
+            Var::Operand(k) => regs[k],
+            Var::Flag(f) => self.regs() + f,
+        };
+        let value = |v: Var| perm[slot(v)];
+        let mut new_perm = perm.clone();
+        for (target, expr) in &self.instrs[*instr].effects {
+            new_perm[slot(*target)] = eval(expr, &value);
+        }
+        new_perm
+    }
+
+    fn show_command(&self, cmd: &Command) -> String {
+        let (instr, regs) = cmd;
+        let instr = &self.instrs[*instr];
+        if instr.template.is_empty() {
+            return format!("{} {}", instr.name, regs.iter().map(|&r| self.reg_name(r)).join(", "));
+        }
+        let mut text = instr.template.clone();
+        for ((op, _), &reg) in instr.operands.iter().zip(regs) {
+            text = text.replace(&format!("{{{}}}", op), self.reg_name(reg));
+        }
+        text
+    }
+
+    // registers written by an instruction at most (flags are not counted)
+    fn max_writes(&self) -> usize {
+        self.instrs
+            .iter()
+            .map(|instr| instr.effects.iter().filter(|(target, _)| matches!(target, Var::Operand(_))).count())
+            .max()
+            .unwrap_or(1)
+            .max(1)
+    }
+}
+
+// map a command over all permutations in a state
+fn apply_all(isa: &Isa, cmd: &Command, state: &State) -> State {
+    let mut new_state = state.iter().map(|perm| isa.apply(cmd, perm)).collect::<Vec<_>>();
+    new_state.sort();
+    new_state.dedup();
+    new_state
+}
+
+// check if the state can never reach a solution
+// corresponds to delete-relaxed planning check
+fn viable(isa: &Isa, state: &State) -> bool {
+    let regs = isa.regs();
+    state.iter().all(|perm| (1..=NUMBERS_U8).all(|n| perm[0..regs].contains(&n)))
+}
+
+fn is_sorted(isa: &Isa, perm: &Permutation) -> bool {
+    let output = isa.offset(isa.output);
+    perm[output..output + NUMBERS].iter().copied().eq(1..=NUMBERS_U8)
+}
+
+// wrong output registers, an instruction corrects at most max_writes of them
+fn admissible_heuristic(isa: &Isa, state: &State) -> u8 {
+    let output = isa.offset(isa.output);
+    let wrong = (0..NUMBERS)
+        .filter(|&i| state.iter().any(|p| p[output + i] != (i+1) as u8))
+        .count();
+    ((wrong + isa.max_writes() - 1) / isa.max_writes()) as u8
+}
+
+// linked list to store the commands and pointer to last element
+// shared prefixes (Rc instead of Box) as in main_registers.rs
+#[derive(Clone, Eq, PartialEq, Hash)]
+struct Node {
+    cmd: Command,
+    prev: Option<Rc<Node>>,
+}
+
+fn extract_program(node: &Node) -> Vec<Command> {
+    let mut cmds = vec![];
+    let mut node = node;
+    while let Some(prev) = &node.prev {
+        cmds.push(node.cmd.clone());
+        node = prev;
+    }
+    cmds.reverse();
+    cmds
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let file = args.get(1).expect("Usage: isa FILE");
+    let content = std::fs::read_to_string(file).expect("Could not read ISA");
+    let isa = match read_isa(&content) {
+        Ok(isa) => isa,
+        Err(msg) => {
+            println!("{}: {}", file, msg);
+            std::process::exit(1);
+        }
+    };
+    let possible_cmds = isa.possible_commands();
+    let permutations: Vec<Vec<u8>> = (1..=NUMBERS_U8).permutations(NUMBERS).collect();
+
+    println!("n = {}", NUMBERS);
+    println!("max_len = {}", MAX_LEN);
+    println!("isa = {}", file);
+    println!("registers = {}", isa.classes.iter().map(|(name, regs)| format!("{} {}", name, regs.len())).join(", "));
+    println!("instruction count = {}", possible_cmds.len());
+
+    let initial_state: Rc<State> = Rc::new(permutations
+        .iter()
+        .map(|p| {
+            let mut perm = vec![0; isa.regs() + isa.flags.len()];
+            for &class in &isa.inputs {
+                let offset = isa.offset(class);
+                for (i, &x) in p.iter().enumerate() {
+                    perm[offset + i] = x;
+                }
+            }
+            perm
+        })
+        .sorted()
+        .collect());
+
+    // the states are small => an in-memory map is sufficient (see main_cegis.rs)
+    let mut length_map: HashMap<Rc<State>, u8> = HashMap::new();
+    length_map.insert(Rc::clone(&initial_state), 0);
+    let mut queue = PriorityQueue::new();
+    let node0 = Node{cmd: (0, vec![]), prev: None};
+    queue.push((node0,Rc::clone(&initial_state),0 as u8), Reverse(admissible_heuristic(&isa, &initial_state)));
+
+    let mut visited : u64 = 0;
+    let mut duplicate : u64 = 0;
+    let mut cut : u64 = 0;
+    let mut solution = None;
+    let start = std::time::Instant::now();
+    while let Some(((prg,state,length), Reverse(score))) = queue.pop() {
+        visited += 1;
+        if visited % 100000 == 0 {
+            print!("Open: {}, ", queue.len());
+            print!("Visited: {}, ", visited);
+            print!("Duplicate: {}, ", duplicate);
+            print!("Cut: {}, ", cut);
+            print!("Current length: {}, ", length);
+            print!("Lower bound: {}, ", score);
+            print!("Time: {:?}", start.elapsed());
+            println!("");
+        }
+        if length_map[&state] < length {
+            duplicate += 1;
+            continue;
+        }
+        if state.iter().all(|p| is_sorted(&isa, p)) {
+            solution = Some(extract_program(&prg));
+            break;
+        }
+
+        let prev_rc = Some(Rc::new(prg));
+        for cmd in &possible_cmds {
+            let new_state = apply_all(&isa, cmd, &state);
+            let new_length = length + 1;
+            if !viable(&isa, &new_state) {
+                cut += 1;
+                continue;
+            }
+            let new_score = new_length + admissible_heuristic(&isa, &new_state);
+            if new_score > MAX_LEN {
+                cut += 1;
+                continue;
+            }
+            if let Some(&old_length) = length_map.get(&new_state) {
+                if old_length <= new_length {
+                    duplicate += 1;
+                    continue;
+                }
+            }
+            let new_state = Rc::new(new_state);
+            length_map.insert(Rc::clone(&new_state), new_length);
+            let prg = Node{cmd: cmd.clone(), prev: prev_rc.clone()};
+            queue.push((prg,new_state,new_length), Reverse(new_score));
+        }
+    }
+    println!("Visited: {}, Duplicate: {}, Cut: {}", visited, duplicate, cut);
+
+    match solution {
+        Some(cmds) => {
+            println!("Found program of length {}:", cmds.len());
+            for cmd in &cmds {
+                println!("{}", isa.show_command(cmd));
+            }
+            if let Ok(dir) = std::env::var("SOLUTION_DIR") {
+                let name = std::path::Path::new(file).file_stem().unwrap().to_string_lossy().to_string();
+                let subdir = format!("{}/{}_isa_{}", dir, NUMBERS, name);
+                std::fs::create_dir_all(&subdir).unwrap();
+                let mut file = std::fs::File::create(format!("{}/solution.s", subdir)).unwrap();
+                for cmd in &cmds {
+                    writeln!(file, "{}", isa.show_command(cmd)).unwrap();
+                }
+                println!("Stored solution in: {}", subdir);
+            }
+        }
+        None => println!("No program of length <= {}", MAX_LEN),
+    }
+    println!("Elapsed: {:?}", start.elapsed());
+}
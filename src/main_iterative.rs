@@ -0,0 +1,455 @@
+use itertools::Itertools;
+use std::ops::Range;
+// has largest value at the top
+use priority_queue::PriorityQueue;
+use std::cmp::Reverse;
+use std::rc::Rc;
+use std::io::Write;
+
+/*
+Automatic discovery of the optimal length (no MAX_LEN needed)
+
+1. upper bound: translate a bubble sort network into cmp/mov/cmovg (4 instructions per comparator)
+2. tighten: greedy search (heuristic and cut as in main_astar.rs) for a program shorter than the best one
+   repeat until the greedy search fails
+3. prove: A* with an admissible (and consistent) heuristic and the best length as bound
+   the f-value of the popped nodes is a lower bound for the optimal length
+
+After each step (and during the search) the best program and the lower bound are reported.
+*/
+
+// const NUMBERS: usize = 3;
+// const NUMBERS: usize = 4;
+const NUMBERS: usize = 5;
+const SWAPS: usize = 1;
+// const NUMBERS: usize = 6;
+// const SWAPS: usize = 2;
+const REGS: usize = NUMBERS + SWAPS;
+const CMP: usize = 0;
+const MOV: usize = 1;
+const CMOVG: usize = 2;
+const CMOVL: usize = 3;
+const NUMBERS_U8: u8 = NUMBERS as u8;
+
+type Command = (usize, usize, usize);
+#[derive(Clone, Eq, PartialEq, Hash, Ord, PartialOrd, Debug, Copy)]
+struct Permutation([u8; REGS + 2]);
+type State = Vec<Permutation>;
+
+use std::ops::{Index, IndexMut};
+
+impl Index<usize> for Permutation {
+    type Output = u8;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+impl IndexMut<usize> for Permutation {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.0[index]
+    }
+}
+
+impl Index<Range<usize>> for Permutation {
+    type Output = [u8];
+
+    fn index(&self, index: Range<usize>) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+impl IndexMut<Range<usize>> for Permutation {
+    fn index_mut(&mut self, index: Range<usize>) -> &mut Self::Output {
+        &mut self.0[index]
+    }
+}
+
+fn possible_commands() -> Vec<Command> {
+    let mut commands = vec![];
+    for instr in &[MOV, CMOVG, CMOVL] {
+        for to in 0..REGS {
+            for from in 0..REGS {
+                if to != from {
+                    commands.push((*instr, to, from));
+                }
+            }
+        }
+    }
+    for i in 0..REGS {
+        for j in (i + 1)..REGS {
+            commands.push((CMP, i, j));
+        }
+    }
+    commands
+}
+
+// transform a permutation according to a command
+fn apply(cmd: &Command, perm: &mut Permutation) {
+    let (instr, to, from) = *cmd;
+    match instr {
+        CMP => {
+            perm[REGS + 0] = (perm[to] < perm[from]) as u8;
+            perm[REGS + 1] = (perm[to] > perm[from]) as u8;
+        }
+        MOV => perm[to] = perm[from],
+        CMOVG => {
+            if perm[REGS + 1] == 1 {
+                perm[to] = perm[from];
+            }
+        }
+        CMOVL => {
+            if perm[REGS + 0] == 1 {
+                perm[to] = perm[from];
+            }
+        }
+        _ => panic!("Unknown instruction"),
+    }
+}
+
+// map a command over all permutations in a state
+fn apply_all(cmd: &Command, state: &State) -> State {
+    let mut new_state = Vec::new();
+    for perm in state {
+        let mut new_perm = perm.clone();
+        apply(cmd, &mut new_perm);
+        new_state.push(new_perm);
+    }
+    new_state.sort();
+    new_state.dedup();
+    new_state
+}
+
+// check if the state can never reach a solution
+// corresponds to delete-relaxed planning check
+fn viable(state: &State) -> bool {
+    for perm in state {
+        for n in 1..=NUMBERS_U8 {
+            if !perm[0..REGS].contains(&n) {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+fn show_command(cmd: &Command) -> String {
+    let (instr, to, from) = *cmd;
+    // 1-indexed to stay consistent with minizinc
+    let to = to+1;
+    let from = from+1;
+    match instr {
+        CMP => format!("CMP {} {}", to, from),
+        MOV => format!("MOV {} {}", to, from),
+        CMOVG => format!("CMOVG {} {}", to, from),
+        CMOVL => format!("CMOVL {} {}", to, from),
+        _ => panic!("Unknown instruction"),
+    }
+}
+
+// linked list to store the commands and pointer to last element
+#[derive(Clone, Eq, PartialEq, Hash)]
+struct Node {
+    cmd: Command,
+    prev: Option<Box<Node>>,
+}
+
+// for each permutation, take out register values, concat => serializable byte array
+fn state_positions(state: &State) -> Vec<u8> {
+    state.iter().flat_map(|p| p.0).collect()
+}
+
+fn extract_program(node: &Node) -> Vec<Command> {
+    let mut cmds = vec![];
+    let mut node = node;
+    while let Some(prev) = &node.prev {
+        cmds.push(node.cmd);
+        node = prev;
+    }
+    cmds.reverse();
+    cmds
+}
+
+fn is_sorted(perm: &Permutation) -> bool {
+    perm[0..NUMBERS].iter().copied().eq(1..=NUMBERS_U8)
+}
+
+// find unused sled-mapX file in a temporary directory (_CONDOR_SCRATCH_DIR or /tmp/ else)
+// the map is opened as temporary => sled removes the directory when the db is dropped
+fn open_length_map() -> sled::Db {
+    let tmp_dir = std::env::var("_CONDOR_SCRATCH_DIR").unwrap_or("/tmp".to_string());
+    let mut i = 0;
+    let mut path = format!("{}/sled-map{}", tmp_dir, i);
+    while std::path::Path::new(&path).exists() {
+        i += 1;
+        path = format!("{}/sled-map{}", tmp_dir, i);
+    }
+    println!("Using sled map: {}", path);
+    sled::Config::new().path(path).temporary(true).open().unwrap()
+}
+
+// comparator (i,j) with i < j: afterwards register i holds the smaller value
+// uses the first swap register as temporary
+fn comparator(i: usize, j: usize) -> Vec<Command> {
+    vec![
+        (MOV, NUMBERS, i),
+        (CMP, i, j),
+        (CMOVG, i, j),
+        (CMOVG, j, NUMBERS),
+    ]
+}
+
+// upper bound without any search: bubble sort network
+fn bubble_sort_program() -> Vec<Command> {
+    let mut cmds = vec![];
+    for i in 0..NUMBERS-1 {
+        for j in 0..NUMBERS-1-i {
+            cmds.extend(comparator(j, j+1));
+        }
+    }
+    cmds
+}
+
+fn sorts_all(cmds: &[Command], initial_state: &State) -> bool {
+    let mut state = initial_state.clone();
+    for cmd in cmds {
+        state = apply_all(cmd, &state);
+    }
+    state.iter().all(is_sorted)
+}
+
+// a lower bound on the remaining instructions
+// each instruction writes at most one register (the same one in all permutations)
+// => every register that is wrong in at least one permutation needs one more instruction
+// changes by at most one per instruction => consistent
+fn admissible_heuristic(state: &State) -> u8 {
+    (0..NUMBERS)
+        .filter(|&i| state.iter().any(|p| p[i] != (i+1) as u8))
+        .count() as u8
+}
+
+fn report(best: &[Command], lower_bound: u8, start: &std::time::Instant) {
+    println!("Best program: {} instructions, lower bound: {}, Time: {:?}", best.len(), lower_bound, start.elapsed());
+}
+
+fn store(best: &[Command]) {
+    if let Ok(dir) = std::env::var("SOLUTION_DIR") {
+        let subdir = format!("{}/{}_iterative", dir, NUMBERS);
+        std::fs::create_dir_all(&subdir).unwrap();
+        let file = format!("{}/solution_{}.txt", subdir, best.len());
+        let mut file = std::fs::File::create(file).unwrap();
+        for cmd in best {
+            writeln!(file, "{}", show_command(cmd)).unwrap();
+        }
+    }
+}
+
+// greedy search as in main_astar.rs (perm count heuristic, min_perm_count cut)
+// returns a program of length <= max_len if it finds one
+fn greedy_search(possible_cmds: &[Command], initial_state: &Rc<State>, max_len: u8) -> Option<Vec<Command>> {
+    let init_perm_count = initial_state.len();
+    let length_map = open_length_map();
+    length_map.insert(state_positions(initial_state), vec![0 as u8]).unwrap();
+
+    let mut queue = PriorityQueue::new();
+    let node0 = Node{cmd: (0,0,0), prev: None};
+    queue.push((node0,Rc::clone(initial_state),0 as u8), Reverse(0));
+
+    let mut min_perm_count = vec![init_perm_count; (max_len as usize)+1];
+    let mut result = None;
+    while let Some(((prg,state,length), _)) = queue.pop() {
+        if let Some(state_len_vec) = length_map.get(state_positions(&state)).unwrap() {
+            if state_len_vec[0] < length {
+                continue;
+            }
+        }
+
+        if state.iter().all(is_sorted) {
+            result = Some(extract_program(&prg));
+            break;
+        }
+
+        if length >= max_len {
+            continue;
+        }
+
+        let prev_box = Some(Box::new(prg));
+        for cmd in possible_cmds {
+            let new_state = Rc::new(apply_all(cmd, &state));
+            let new_length = length + 1;
+
+            if !viable(&new_state) {
+                continue;
+            }
+
+            let new_perm_count = new_state.iter().map(|p| &p[0..NUMBERS]).unique().count();
+            let new_length_u = new_length as usize;
+            if min_perm_count[length as usize] < new_perm_count {
+                continue;
+            }
+            if min_perm_count[new_length_u] > new_perm_count {
+                min_perm_count[new_length_u] = new_perm_count;
+            }
+
+            let state_repr = state_positions(&new_state);
+            if let Some(old_length_vec) = length_map.get(&state_repr).unwrap() {
+                if old_length_vec[0] <= new_length {
+                    continue;
+                }
+            }
+            length_map.insert(state_repr, vec![new_length]).unwrap();
+
+            let new_score = new_length + new_perm_count as u8;
+            let prg = Node{cmd: *cmd, prev: prev_box.clone()};
+            queue.push((prg,Rc::clone(&new_state),new_length), Reverse(new_score));
+        }
+    }
+    drop(length_map);
+    result
+}
+
+fn main() {
+    let possible_cmds = possible_commands();
+    let permutations: Vec<Vec<u8>> = (1..=NUMBERS_U8).permutations(NUMBERS).collect();
+
+    // if in git repository, print hash
+    let git_hash = std::process::Command::new("git")
+        .args(&["rev-parse", "--short", "HEAD"])
+        .output()
+        .expect("failed to execute git")
+        .stdout;
+    let git_hash = String::from_utf8(git_hash).unwrap();
+    println!("Git hash: {}", git_hash);
+    println!("n = {}", NUMBERS);
+    println!("swaps = {}", SWAPS);
+
+    // extend numerical permutations with register for swap and flags
+    let initial_state: Rc<State> = Rc::new(permutations
+        .iter()
+        .map(|p| {
+            let mut perm = Permutation([0; REGS + 2]);
+            for (i, &x) in p.iter().enumerate() {
+                perm[i] = x;
+            }
+            perm
+        })
+        .sorted()
+        .collect());
+
+    let start = std::time::Instant::now();
+
+    // 1. upper bound from a sorting network
+    let mut best = bubble_sort_program();
+    assert!(sorts_all(&best, &initial_state), "Bubble sort network does not sort");
+    let mut lower_bound = admissible_heuristic(&initial_state);
+    println!("Upper bound from bubble sort network");
+    report(&best, lower_bound, &start);
+    store(&best);
+
+    // 2. tighten the upper bound with the (fast, incomplete) greedy search
+    while best.len() as u8 > lower_bound {
+        let bound = best.len() as u8 - 1;
+        println!("Greedy search with max_len = {}", bound);
+        match greedy_search(&possible_cmds, &initial_state, bound) {
+            Some(cmds) => {
+                assert!(sorts_all(&cmds, &initial_state));
+                best = cmds;
+                report(&best, lower_bound, &start);
+                store(&best);
+            }
+            None => break,
+        }
+    }
+
+    // 3. prove optimality (or find shorter programs) with A* and an admissible heuristic
+    // all nodes with f >= best length can be cut
+    println!("Proving lower bound");
+    let length_map = open_length_map();
+    length_map.insert(state_positions(&initial_state), vec![0 as u8]).unwrap();
+    let mut queue = PriorityQueue::new();
+    let node0 = Node{cmd: (0,0,0), prev: None};
+    queue.push((node0,Rc::clone(&initial_state),0 as u8), Reverse(lower_bound));
+
+    let mut visited : u64 = 0;
+    let mut duplicate : u64 = 0;
+    let mut cut : u64 = 0;
+    while let Some(((prg,state,length), Reverse(score))) = queue.pop() {
+        if score >= best.len() as u8 {
+            // every remaining node needs at least as many instructions as the best program
+            break;
+        }
+        // the heuristic is consistent => popped f-values are monotone
+        if score > lower_bound {
+            lower_bound = score;
+            report(&best, lower_bound, &start);
+        }
+
+        visited += 1;
+        if visited % 100000 == 0 {
+            print!("Open: {}, ", queue.len());
+            print!("Visited: {}, ", visited);
+            print!("Duplicate: {}, ", duplicate);
+            print!("Cut: {}, ", cut);
+            print!("Current length: {}, ", length);
+            print!("Best: {}, ", best.len());
+            print!("Lower bound: {}, ", lower_bound);
+            print!("Time: {:?}", start.elapsed());
+            println!("");
+        }
+
+        if let Some(state_len_vec) = length_map.get(state_positions(&state)).unwrap() {
+            if state_len_vec[0] < length {
+                duplicate += 1;
+                continue;
+            }
+        }
+
+        if state.iter().all(is_sorted) {
+            // first solution popped by A* with a consistent heuristic is optimal
+            best = extract_program(&prg);
+            store(&best);
+            break;
+        }
+
+        let prev_box = Some(Box::new(prg));
+        for cmd in &possible_cmds {
+            let new_state = Rc::new(apply_all(cmd, &state));
+            let new_length = length + 1;
+
+            if !viable(&new_state) {
+                cut += 1;
+                continue;
+            }
+
+            let new_score = new_length + admissible_heuristic(&new_state);
+            if new_score >= best.len() as u8 {
+                cut += 1;
+                continue;
+            }
+
+            let state_repr = state_positions(&new_state);
+            if let Some(old_length_vec) = length_map.get(&state_repr).unwrap() {
+                if old_length_vec[0] <= new_length {
+                    duplicate += 1;
+                    continue;
+                }
+            }
+            length_map.insert(state_repr, vec![new_length]).unwrap();
+
+            let prg = Node{cmd: *cmd, prev: prev_box.clone()};
+            queue.push((prg,Rc::clone(&new_state),new_length), Reverse(new_score));
+        }
+    }
+    // queue exhausted or only nodes with f >= best => best is optimal
+    lower_bound = best.len() as u8;
+
+    println!("Optimal length: {}", best.len());
+    report(&best, lower_bound, &start);
+    println!("Program:");
+    for cmd in &best {
+        println!("{}", show_command(cmd));
+    }
+    println!("Visited: {}, Duplicate: {}, Cut: {}", visited, duplicate, cut);
+    println!("Elapsed: {:?}", start.elapsed());
+}
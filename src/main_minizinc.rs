@@ -0,0 +1,244 @@
+use itertools::Itertools;
+
+mod common;
+mod x86;
+use common::{inputs, Spec};
+use x86::*;
+
+/*
+Generate a MiniZinc model of the synthesis problem and import solutions of the solver.
+
+Usage:
+  minizinc export [FILE]   writes the model (default: sort.mzn)
+  minizinc import FILE     reads the output of the solver (e.g. minizinc --solver chuffed sort.mzn > FILE)
+                           and verifies the last solution on all inputs
+
+The configuration is chosen via environment variables (the same for export and import):
+  ISA  = cmov | minmax | mixed   (main_astar.rs, main_astar_minmax.rs, main_astar_mixed.rs)
+  SPEC = sort | desc | min | max | median | topk:K | partial:K | table:FILE   (see common.rs)
+
+Model:
+- one instruction (instr, to, from) per step, restricted to our possible_commands via a table constraint
+- registers and flags of every input after every step
+- programs are padded with NOPs at the end => minimizing the non-NOP steps gives the optimal length <= MAX_LEN
+Registers are 1-indexed (normal registers first, then xmm registers),
+the solver output uses the same format as show_command.
+*/
+
+const NUMBERS: usize = 3;
+const MAX_LEN: usize = 11;
+// const NUMBERS: usize = 4;
+// const MAX_LEN: usize = 20;
+const SWAPS: usize = 1;
+const NOP: usize = 8; // only in the model (padding at the end)
+const INSTR_NAMES: [&str; 9] = ["CMP", "MOV", "CMOVG", "CMOVL", "MIN", "MAX", "MOVD", "MOVDQA", "NOP"];
+const NUMBERS_U8: u8 = NUMBERS as u8;
+
+
+// 2d array literal [| a, b | c, d |]
+fn mzn_array2d(rows: Vec<Vec<usize>>) -> String {
+    let rows = rows.iter().map(|row| format!("    {}", row.iter().join(", "))).join(" |\n");
+    format!("[|\n{}\n|]", rows)
+}
+
+fn model(isa: Isa, spec_name: &str, inputs: &[(Vec<u8>, [u8; NUMBERS])]) -> String {
+    let regs = registers(isa);
+    let mut commands = possible_commands(isa)
+        .iter()
+        .map(|&(instr, to, from)| vec![instr, program_reg(isa, to), program_reg(isa, from)])
+        .collect::<Vec<_>>();
+    commands.push(vec![NOP, 1, 1]);
+    let init = inputs
+        .iter()
+        .map(|(input, _)| {
+            let perm = initial_perm(isa, input);
+            regs.iter().map(|&r| perm[r] as usize).collect()
+        })
+        .collect();
+    let target = inputs
+        .iter()
+        .map(|(_, target)| target.iter().map(|&t| t as usize).collect())
+        .collect();
+    let out = isa.output_regs().map(|r| program_reg(isa, r)).join(", ");
+    let names = INSTR_NAMES.iter().map(|name| format!("\"{}\"", name)).join(", ");
+
+    format!(r#"% {isa} ISA, n = {n}, {swaps} scratch register(s) per class, spec {spec}
+% solve with e.g.: minizinc --solver chuffed --intermediate-solutions <this file>
+include "table.mzn";
+
+int: n = {n};
+int: len = {len};
+int: regs = {regs};
+int: inputs = {inputs};
+
+set of int: STEP = 1..len;
+set of int: REG = 1..regs;
+set of int: INPUT = 1..inputs;
+set of int: VAL = 0..n;
+
+int: CMP = {cmp};
+int: MOV = {mov};
+int: CMOVG = {cmovg};
+int: CMOVL = {cmovl};
+int: MIN = {min};
+int: MAX = {max};
+int: MOVD = {movd};
+int: MOVDQA = {movdqa};
+int: NOP = {nop};
+set of int: INSTR = 0..NOP;
+array[INSTR] of string: names = array1d(INSTR, [{names}]);
+
+% allowed (instr, dst, src) triples of the ISA
+array[int, 1..3] of int: commands = {commands};
+% registers before the program (0 = empty scratch register)
+array[INPUT, REG] of VAL: init = {init};
+% expected outputs (0 = don't care)
+array[INPUT, 1..n] of VAL: target = {target};
+% registers holding the outputs
+array[1..n] of REG: out = [{out}];
+
+array[STEP] of var INSTR: instr;
+array[STEP] of var REG: dst;
+array[STEP] of var REG: src;
+
+array[INPUT, 0..len, REG] of var VAL: reg;
+array[INPUT, 0..len] of var bool: lt;
+array[INPUT, 0..len] of var bool: gt;
+
+constraint forall(s in STEP)(table([instr[s], dst[s], src[s]], commands));
+% padding only at the end
+constraint forall(s in 1..len-1)(instr[s] = NOP -> instr[s+1] = NOP);
+
+constraint forall(i in INPUT)(
+    forall(r in REG)(reg[i,0,r] = init[i,r]) /\ not lt[i,0] /\ not gt[i,0]
+);
+
+constraint forall(i in INPUT, s in STEP)(
+    let {{
+        var VAL: a = reg[i,s-1,dst[s]];
+        var VAL: b = reg[i,s-1,src[s]];
+    }} in
+    (lt[i,s] = if instr[s] = CMP then a < b else lt[i,s-1] endif) /\
+    (gt[i,s] = if instr[s] = CMP then a > b else gt[i,s-1] endif) /\
+    forall(r in REG)(
+        reg[i,s,r] = if r = dst[s] then
+            if instr[s] in {{MOV, MOVD, MOVDQA}} then b
+            elseif instr[s] = CMOVG then (if gt[i,s-1] then b else a endif)
+            elseif instr[s] = CMOVL then (if lt[i,s-1] then b else a endif)
+            elseif instr[s] = MIN then min(a, b)
+            elseif instr[s] = MAX then max(a, b)
+            else a endif
+        else reg[i,s-1,r] endif
+    )
+);
+
+constraint forall(i in INPUT, o in 1..n where target[i,o] > 0)(reg[i,len,out[o]] = target[i,o]);
+
+solve minimize sum(s in STEP)(bool2int(instr[s] != NOP));
+
+output [
+    if fix(instr[s]) != NOP then names[fix(instr[s])] ++ " \(fix(dst[s])) \(fix(src[s]))\n" else "" endif
+    | s in STEP
+];
+"#,
+        isa = isa.name(), n = NUMBERS, swaps = SWAPS, spec = spec_name, len = MAX_LEN,
+        regs = regs.len(), inputs = inputs.len(),
+        cmp = CMP, mov = MOV, cmovg = CMOVG, cmovl = CMOVL, min = MIN, max = MAX,
+        movd = MOVD, movdqa = MOVDQA, nop = NOP, names = names,
+        commands = mzn_array2d(commands), init = mzn_array2d(init), target = mzn_array2d(target), out = out)
+}
+
+// reads the output of the solver, e.g.
+//   MOV 4 1
+//   CMP 1 2
+//   ...
+//   ----------
+//   ==========
+// the last solution is the best one (minimize prints all improving solutions)
+fn read_solution(file: &str, isa: Isa) -> Option<Vec<Command>> {
+    let content = std::fs::read_to_string(file).expect("Could not read solution");
+    if content.contains("=====UNSATISFIABLE=====") {
+        return None;
+    }
+    let solution = content
+        .split("----------")
+        .filter(|block| block.lines().any(|line| !line.trim().is_empty() && !line.starts_with('%') && !line.starts_with("=====")))
+        .last()?;
+    let regs = registers(isa);
+    let possible_cmds = possible_commands(isa);
+    let mut cmds = vec![];
+    for line in solution.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('%') || line.starts_with("=====") {
+            continue;
+        }
+        let parts = line.split_whitespace().collect::<Vec<_>>();
+        if parts.len() != 3 {
+            panic!("Expected instruction with two registers: {}", line);
+        }
+        let instr = INSTR_NAMES.iter().position(|&name| name == parts[0])
+            .unwrap_or_else(|| panic!("Unknown instruction: {}", parts[0]));
+        let reg = |x: &str| {
+            let index = x.parse::<usize>().unwrap_or_else(|_| panic!("Invalid register: {}", x));
+            *regs.get(index.wrapping_sub(1)).unwrap_or_else(|| panic!("Unknown register: {}", x))
+        };
+        let cmd = (instr, reg(parts[1]), reg(parts[2]));
+        if !possible_cmds.contains(&cmd) {
+            panic!("Instruction is not part of the {} ISA: {}", isa.name(), line);
+        }
+        cmds.push(cmd);
+    }
+    Some(cmds)
+}
+
+// run the program on all inputs, returns the inputs with a wrong output
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let isa = Isa::from_env();
+    let spec_name = std::env::var("SPEC").unwrap_or("sort".to_string());
+    let spec = Spec::from_env();
+    let inputs = inputs(&spec);
+
+    println!("n = {}", NUMBERS);
+    println!("max_len = {}", MAX_LEN);
+    println!("swaps = {}", SWAPS);
+    println!("isa = {}", isa.name());
+    println!("spec = {}", spec_name);
+    println!("inputs = {}", inputs.len());
+
+    match args.get(1).map(|s| s.as_str()) {
+        Some("export") => {
+            let file = args.get(2).cloned().unwrap_or("sort.mzn".to_string());
+            std::fs::write(&file, model(isa, &spec_name, &inputs)).unwrap();
+            println!("Model: {}", file);
+        }
+        Some("import") => {
+            let file = args.get(2).expect("Usage: minizinc import FILE");
+            let cmds = match read_solution(file, isa) {
+                Some(cmds) => cmds,
+                None => {
+                    println!("No program of length <= {}", MAX_LEN);
+                    return;
+                }
+            };
+            println!("Program of length {}:", cmds.len());
+            for cmd in &cmds {
+                println!("{}", show_command(cmd, isa));
+            }
+            let wrong = verify(&cmds, isa, &inputs);
+            if wrong.is_empty() {
+                println!("Program is correct on all {} inputs", inputs.len());
+            } else {
+                for (input, perm) in &wrong {
+                    println!("Wrong output for input {:?}: {:?}", input, &perm[isa.output_regs()]);
+                }
+                println!("Program is wrong on {} of {} inputs", wrong.len(), inputs.len());
+                std::process::exit(1);
+            }
+        }
+        _ => {
+            println!("Usage: minizinc export [FILE] | minizinc import FILE");
+            std::process::exit(1);
+        }
+    }
+}
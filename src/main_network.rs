@@ -0,0 +1,477 @@
+use itertools::Itertools;
+use std::collections::{HashMap, HashSet};
+// has largest value at the top
+use priority_queue::PriorityQueue;
+use std::cmp::Reverse;
+use std::rc::Rc;
+use std::io::Write;
+
+/*
+Sorting networks and min/max programs via the 0-1 principle
+
+min, max and mov are monotone => a program that sorts all 0/1 vectors sorts all inputs
+(for every threshold t: thresholding commutes with the program).
+Instead of the n! permutations a state holds (a subset of) the 2^n binary inputs.
+
+MODE environment variable:
+  program  min/max ISA of main_astar_minmax.rs (mov, min, max with scratch registers)
+           a permutation is a bit vector of the registers + the number of ones of the input
+           goal: the output registers hold the sorted input (zeros first)
+  size     comparator networks with the minimal number of comparators
+  depth    comparator networks with the minimal number of layers (parallel comparators)
+In the network modes, a comparator (i,j) with i < j puts the min on wire i and the max on wire j.
+A state is the set of reachable 0/1 vectors of the wires (a bitset over the 2^n vectors).
+Networks are printed with 1-indexed wires, one layer per line: (1,2) (3,4)
+*/
+
+// program: n=4 (15 instructions) in ~30s
+// size: n=6 (12 comparators) in < 1s, n=7 (16) in ~25s
+// depth: n=6 (5 layers) in < 1s, n=7 (6 layers) in ~1min
+// n=8 needs symmetry breaking (e.g. a fixed first layer)
+const NUMBERS: usize = 4;
+// const NUMBERS: usize = 7;
+const SWAPS: usize = 1;
+const REGS: usize = NUMBERS + SWAPS;
+const MOV: usize = 0; // movdqa
+const MIN: usize = 1; // pminud => compare first and second, move smaller to first
+const MAX: usize = 2;
+const INPUTS: usize = 1 << NUMBERS;
+const WORDS: usize = (INPUTS + 63) / 64;
+// bits of a program permutation: registers at 0..REGS, number of ones from COUNT_SHIFT on
+const COUNT_SHIFT: usize = 16;
+
+type Command = (usize, usize, usize);
+// bit vector of the registers + number of ones of the input
+type BinaryPerm = u32;
+type ProgramState = Vec<BinaryPerm>;
+// set of 0/1 vectors on the wires
+type NetworkState = [u64; WORDS];
+type Comparator = (usize, usize);
+
+fn possible_commands() -> Vec<Command> {
+    let mut commands = vec![];
+    for instr in &[MOV, MIN, MAX] {
+        for to in 0..REGS {
+            for from in 0..REGS {
+                if to != from {
+                    commands.push((*instr, to, from));
+                }
+            }
+        }
+    }
+    commands
+}
+
+fn bit(x: u32, i: usize) -> u32 {
+    (x >> i) & 1
+}
+
+// on bits: min = and, max = or
+fn apply(cmd: &Command, perm: BinaryPerm) -> BinaryPerm {
+    let (instr, to, from) = *cmd;
+    let value = match instr {
+        MOV => bit(perm, from),
+        MIN => bit(perm, to) & bit(perm, from),
+        MAX => bit(perm, to) | bit(perm, from),
+        _ => panic!("Unknown instruction"),
+    };
+    (perm & !(1 << to)) | (value << to)
+}
+
+// map a command over all permutations in a state
+fn apply_all(cmd: &Command, state: &ProgramState) -> ProgramState {
+    let mut new_state = state.iter().map(|&p| apply(cmd, p)).collect::<Vec<_>>();
+    new_state.sort();
+    new_state.dedup();
+    new_state
+}
+
+// sorted output (zeros first) for k ones on the first NUMBERS registers/wires
+fn sorted_bits(ones: usize) -> u32 {
+    ((1 << NUMBERS) - 1) ^ ((1 << (NUMBERS - ones)) - 1)
+}
+
+fn ones(perm: BinaryPerm) -> usize {
+    (perm >> COUNT_SHIFT) as usize
+}
+
+fn is_sorted(perm: &BinaryPerm) -> bool {
+    perm & ((1 << NUMBERS) - 1) == sorted_bits(ones(*perm))
+}
+
+// min, max and mov do not create values
+// => a one (zero) has to be in some register as long as the output needs one
+fn viable(state: &ProgramState) -> bool {
+    let all = (1 << REGS) - 1;
+    state.iter().all(|&p| {
+        let regs = p & all;
+        (ones(p) == 0 || regs != 0) && (ones(p) == NUMBERS || regs != all)
+    })
+}
+
+// each instruction writes at most one register
+// => every output register that is wrong in at least one permutation needs one more instruction
+fn admissible_heuristic(state: &ProgramState) -> u8 {
+    (0..NUMBERS)
+        .filter(|&i| state.iter().any(|&p| bit(p, i) != bit(sorted_bits(ones(p)), i)))
+        .count() as u8
+}
+
+fn show_command(cmd: &Command) -> String {
+    let (instr, to, from) = *cmd;
+    // 1-indexed to stay consistent with minizinc
+    let to = to+1;
+    let from = from+1;
+    match instr {
+        MOV => format!("MOV {} {}", to, from),
+        MIN => format!("MIN {} {}", to, from),
+        MAX => format!("MAX {} {}", to, from),
+        _ => panic!("Unknown instruction"),
+    }
+}
+
+// linked list to store the commands and pointer to last element
+// shared prefixes (Rc instead of Box) as in main_registers.rs
+#[derive(Clone, Eq, PartialEq, Hash)]
+struct Node<T> {
+    cmd: T,
+    prev: Option<Rc<Node<T>>>,
+}
+
+fn extract_program<T: Copy>(node: &Node<T>) -> Vec<T> {
+    let mut cmds = vec![];
+    let mut node = node;
+    while let Some(prev) = &node.prev {
+        cmds.push(node.cmd);
+        node = prev;
+    }
+    cmds.reverse();
+    cmds
+}
+
+// A* with admissible heuristic over the min/max ISA
+fn program_search() -> Option<Vec<Command>> {
+    let possible_cmds = possible_commands();
+    // all 0/1 inputs, scratch registers 0
+    let initial_state: Rc<ProgramState> = Rc::new((0..INPUTS as u32)
+        .map(|v| v | ((v.count_ones()) << COUNT_SHIFT))
+        .collect());
+    println!("inputs = {} (instead of {} permutations)", initial_state.len(), (1..=NUMBERS).product::<usize>());
+
+    let mut length_map: HashMap<ProgramState, u8> = HashMap::new();
+    length_map.insert(initial_state.to_vec(), 0);
+    let mut queue = PriorityQueue::new();
+    let node0 = Node{cmd: (0,0,0), prev: None};
+    queue.push((node0,Rc::clone(&initial_state),0 as u8), Reverse(admissible_heuristic(&initial_state)));
+
+    let mut visited : u64 = 0;
+    let start = std::time::Instant::now();
+    while let Some(((prg,state,length), Reverse(score))) = queue.pop() {
+        visited += 1;
+        if visited % 100000 == 0 {
+            print!("Open: {}, ", queue.len());
+            print!("Visited: {}, ", visited);
+            print!("Lower bound: {}, ", score);
+            print!("Time: {:?}", start.elapsed());
+            println!("");
+        }
+        if length_map[state.as_ref()] < length {
+            continue;
+        }
+        if state.iter().all(is_sorted) {
+            println!("Visited: {}", visited);
+            return Some(extract_program(&prg));
+        }
+
+        let prev_rc = Some(Rc::new(prg));
+        for cmd in &possible_cmds {
+            let new_state = apply_all(cmd, &state);
+            let new_length = length + 1;
+            if !viable(&new_state) {
+                continue;
+            }
+            if let Some(&old_length) = length_map.get(&new_state) {
+                if old_length <= new_length {
+                    continue;
+                }
+            }
+            length_map.insert(new_state.clone(), new_length);
+            let new_score = new_length + admissible_heuristic(&new_state);
+            let prg = Node{cmd: *cmd, prev: prev_rc.clone()};
+            queue.push((prg,Rc::new(new_state),new_length), Reverse(new_score));
+        }
+    }
+    None
+}
+
+fn contains(state: &NetworkState, v: usize) -> bool {
+    (state[v / 64] >> (v % 64)) & 1 == 1
+}
+
+fn insert(state: &mut NetworkState, v: usize) {
+    state[v / 64] |= 1 << (v % 64);
+}
+
+fn vectors(state: &NetworkState) -> impl Iterator<Item = usize> + '_ {
+    (0..INPUTS).filter(move |&v| contains(state, v))
+}
+
+// only comparators that change at least one vector (1 on wire i, 0 on wire j)
+fn useful(state: &NetworkState, (i, j): Comparator) -> bool {
+    vectors(state).any(|v| (v >> i) & 1 == 1 && (v >> j) & 1 == 0)
+}
+
+fn apply_comparator(state: &NetworkState, (i, j): Comparator) -> NetworkState {
+    let mut new_state = [0; WORDS];
+    for v in vectors(state) {
+        let new_v = if (v >> i) & 1 == 1 && (v >> j) & 1 == 0 { v ^ (1 << i) ^ (1 << j) } else { v };
+        insert(&mut new_state, new_v);
+    }
+    new_state
+}
+
+fn network_sorted(state: &NetworkState) -> bool {
+    vectors(state).all(|v| v as u32 == sorted_bits(v.count_ones() as usize))
+}
+
+// each comparator changes two wires
+// => every wire that is wrong for at least one vector needs a comparator, two per comparator
+fn network_heuristic(state: &NetworkState) -> u8 {
+    let mut wrong = 0;
+    for v in vectors(state) {
+        wrong |= v as u32 ^ sorted_bits(v.count_ones() as usize);
+    }
+    ((wrong.count_ones() + 1) / 2) as u8
+}
+
+fn all_inputs() -> NetworkState {
+    let mut state = [0; WORDS];
+    for v in 0..INPUTS {
+        insert(&mut state, v);
+    }
+    state
+}
+
+fn comparators() -> Vec<Comparator> {
+    (0..NUMBERS).tuple_combinations().collect()
+}
+
+// A* over comparators with the admissible wire heuristic
+fn size_search() -> Option<Vec<Comparator>> {
+    let comparators = comparators();
+    let initial_state = all_inputs();
+    let mut length_map: HashMap<NetworkState, u8> = HashMap::new();
+    length_map.insert(initial_state, 0);
+    let mut queue = PriorityQueue::new();
+    let node0 = Node{cmd: (0,0), prev: None};
+    queue.push((node0,initial_state,0 as u8), Reverse(network_heuristic(&initial_state)));
+
+    let mut visited : u64 = 0;
+    let start = std::time::Instant::now();
+    while let Some(((prg,state,length), Reverse(score))) = queue.pop() {
+        visited += 1;
+        if visited % 100000 == 0 {
+            print!("Open: {}, ", queue.len());
+            print!("Visited: {}, ", visited);
+            print!("Lower bound: {}, ", score);
+            print!("Time: {:?}", start.elapsed());
+            println!("");
+        }
+        if length_map[&state] < length {
+            continue;
+        }
+        if network_sorted(&state) {
+            println!("Visited: {}", visited);
+            return Some(extract_program(&prg));
+        }
+
+        let prev_rc = Some(Rc::new(prg));
+        for &c in &comparators {
+            if !useful(&state, c) {
+                continue;
+            }
+            let new_state = apply_comparator(&state, c);
+            let new_length = length + 1;
+            if let Some(&old_length) = length_map.get(&new_state) {
+                if old_length <= new_length {
+                    continue;
+                }
+            }
+            length_map.insert(new_state, new_length);
+            let new_score = new_length + network_heuristic(&new_state);
+            let prg = Node{cmd: c, prev: prev_rc.clone()};
+            queue.push((prg,new_state,new_length), Reverse(new_score));
+        }
+    }
+    None
+}
+
+// all non-empty sets of disjoint useful comparators
+fn layers(state: &NetworkState, comparators: &[Comparator]) -> Vec<Vec<Comparator>> {
+    fn extend(rest: &[Comparator], used: u32, layer: &mut Vec<Comparator>, layers: &mut Vec<Vec<Comparator>>) {
+        for (k, &(i, j)) in rest.iter().enumerate() {
+            if used & ((1 << i) | (1 << j)) != 0 {
+                continue;
+            }
+            layer.push((i, j));
+            layers.push(layer.clone());
+            extend(&rest[k+1..], used | (1 << i) | (1 << j), layer, layers);
+            layer.pop();
+        }
+    }
+    let useful = comparators.iter().copied().filter(|&c| useful(state, c)).collect::<Vec<_>>();
+    let mut layers = vec![];
+    extend(&useful, 0, &mut vec![], &mut layers);
+    layers
+}
+
+// breadth-first over layers => the first sorted state has minimal depth
+fn depth_search() -> Option<Vec<Vec<Comparator>>> {
+    let comparators = comparators();
+    let initial_state = all_inputs();
+    let mut seen = HashSet::new();
+    seen.insert(initial_state);
+    let node0 = Node{cmd: vec![], prev: None};
+    let mut frontier = vec![(node0, initial_state)];
+    let start = std::time::Instant::now();
+    let mut depth = 0;
+    while !frontier.is_empty() {
+        depth += 1;
+        let mut next_frontier = vec![];
+        for (prg, state) in frontier {
+            let prev_rc = Some(Rc::new(prg));
+            for layer in layers(&state, &comparators) {
+                let mut new_state = state;
+                for &c in &layer {
+                    new_state = apply_comparator(&new_state, c);
+                }
+                if !seen.insert(new_state) {
+                    continue;
+                }
+                let prg = Node{cmd: layer, prev: prev_rc.clone()};
+                if network_sorted(&new_state) {
+                    return Some(extract_program_layers(&prg));
+                }
+                next_frontier.push((prg, new_state));
+            }
+        }
+        println!("Depth: {}, States: {}, Seen: {}, Time: {:?}", depth, next_frontier.len(), seen.len(), start.elapsed());
+        frontier = next_frontier;
+    }
+    None
+}
+
+// like extract_program but the commands are not Copy
+fn extract_program_layers(node: &Node<Vec<Comparator>>) -> Vec<Vec<Comparator>> {
+    let mut layers = vec![];
+    let mut node = node;
+    while let Some(prev) = &node.prev {
+        layers.push(node.cmd.clone());
+        node = prev;
+    }
+    layers.reverse();
+    layers
+}
+
+// comparators of a sequence in as few layers as possible (each comparator as early as possible)
+fn to_layers(network: &[Comparator]) -> Vec<Vec<Comparator>> {
+    let mut layers: Vec<Vec<Comparator>> = vec![];
+    let mut wire_depth = [0; NUMBERS];
+    for &(i, j) in network {
+        let depth = wire_depth[i].max(wire_depth[j]);
+        if depth == layers.len() {
+            layers.push(vec![]);
+        }
+        layers[depth].push((i, j));
+        wire_depth[i] = depth + 1;
+        wire_depth[j] = depth + 1;
+    }
+    layers
+}
+
+// check the result of the 0/1 search on all permutations
+fn verify<T>(cmds: &[T], run: impl Fn(&T, &mut Vec<usize>)) -> bool {
+    (1..=NUMBERS).permutations(NUMBERS).all(|perm| {
+        let mut regs = perm.clone();
+        regs.resize(REGS, 0);
+        for cmd in cmds {
+            run(cmd, &mut regs);
+        }
+        (1..=NUMBERS).zip(regs.iter()).all(|(i, &r)| i == r)
+    })
+}
+
+fn show_layer(layer: &[Comparator]) -> String {
+    // 1-indexed to stay consistent with minizinc
+    layer.iter().map(|(i, j)| format!("({},{})", i+1, j+1)).join(" ")
+}
+
+fn store(name: &str, lines: &[String]) {
+    if let Ok(dir) = std::env::var("SOLUTION_DIR") {
+        let subdir = format!("{}/{}_network", dir, NUMBERS);
+        std::fs::create_dir_all(&subdir).unwrap();
+        let file = format!("{}/{}.txt", subdir, name);
+        let mut file = std::fs::File::create(&file).unwrap();
+        for line in lines {
+            writeln!(file, "{}", line).unwrap();
+        }
+        println!("Stored solution in: {}", subdir);
+    }
+}
+
+fn main() {
+    let mode = std::env::var("MODE").unwrap_or("program".to_string());
+    println!("n = {}", NUMBERS);
+    println!("mode = {}", mode);
+    let start = std::time::Instant::now();
+
+    match mode.as_str() {
+        "program" => {
+            println!("swaps = {}", SWAPS);
+            match program_search() {
+                Some(cmds) => {
+                    println!("Found program of length {}", cmds.len());
+                    println!("Correct on all permutations: {}", verify(&cmds, |&(instr, to, from), regs| {
+                        regs[to] = match instr {
+                            MOV => regs[from],
+                            MIN => regs[to].min(regs[from]),
+                            _ => regs[to].max(regs[from]),
+                        }
+                    }));
+                    let lines = cmds.iter().map(show_command).collect::<Vec<_>>();
+                    for line in &lines {
+                        println!("{}", line);
+                    }
+                    store("program", &lines);
+                }
+                None => println!("No program found"),
+            }
+        }
+        "size" | "depth" => {
+            let layers = if mode == "size" {
+                size_search().map(|network| to_layers(&network))
+            } else {
+                depth_search()
+            };
+            match layers {
+                Some(layers) => {
+                    let size = layers.iter().map(|l| l.len()).sum::<usize>();
+                    println!("Found network of size {} and depth {}", size, layers.len());
+                    println!("Correct on all permutations: {}", verify(&layers.concat(), |&(i, j), regs| {
+                        if regs[i] > regs[j] {
+                            regs.swap(i, j);
+                        }
+                    }));
+                    let lines = layers.iter().map(|l| show_layer(l)).collect::<Vec<_>>();
+                    for line in &lines {
+                        println!("{}", line);
+                    }
+                    store(&format!("network_{}", mode), &lines);
+                }
+                None => println!("No network found"),
+            }
+        }
+        _ => panic!("Unknown mode: {}", mode),
+    }
+    println!("Elapsed: {:?}", start.elapsed());
+}
@@ -0,0 +1,381 @@
+use itertools::Itertools;
+use std::collections::HashMap;
+use std::ops::Range;
+// has largest value at the top
+use priority_queue::PriorityQueue;
+use std::cmp::Reverse;
+use std::rc::Rc;
+use std::io::Write;
+
+/*
+Pareto front over
+- instruction count
+- number of scratch registers touched
+- critical path latency (of the output registers)
+
+Instead of one length per state (length_map in main_astar.rs), each state keeps all non-dominated labels.
+A label consists of the length, the set of used scratch registers and the ready time of each register (and the flags).
+The future latency depends on all ready times => labels are only comparable component-wise.
+
+The search explores all programs up to MAX_LEN (ordered by length + admissible heuristic)
+and keeps the non-dominated solutions.
+*/
+
+const NUMBERS: usize = 3;
+const MAX_LEN: u8 = 12;
+// const NUMBERS: usize = 4;
+// const MAX_LEN: u8 = 21;
+const SWAPS: usize = 1;
+// const SWAPS: usize = 2; // > 5GB for n = 3
+const REGS: usize = NUMBERS + SWAPS;
+const CMP: usize = 0;
+const MOV: usize = 1;
+const CMOVG: usize = 2;
+const CMOVL: usize = 3;
+const NUMBERS_U8: u8 = NUMBERS as u8;
+
+// latencies in cycles (cmov is 2 on older Intel cores)
+const LAT_CMP: u8 = 1;
+const LAT_MOV: u8 = 1;
+const LAT_CMOV: u8 = 1;
+// index of the flags in the ready times
+const FLAGS: usize = REGS;
+
+type Command = (usize, usize, usize);
+#[derive(Clone, Eq, PartialEq, Hash, Ord, PartialOrd, Debug, Copy)]
+struct Permutation([u8; REGS + 2]);
+type State = Vec<Permutation>;
+
+use std::ops::{Index, IndexMut};
+
+impl Index<usize> for Permutation {
+    type Output = u8;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+impl IndexMut<usize> for Permutation {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.0[index]
+    }
+}
+
+impl Index<Range<usize>> for Permutation {
+    type Output = [u8];
+
+    fn index(&self, index: Range<usize>) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+impl IndexMut<Range<usize>> for Permutation {
+    fn index_mut(&mut self, index: Range<usize>) -> &mut Self::Output {
+        &mut self.0[index]
+    }
+}
+
+fn possible_commands() -> Vec<Command> {
+    let mut commands = vec![];
+    for instr in &[MOV, CMOVG, CMOVL] {
+        for to in 0..REGS {
+            for from in 0..REGS {
+                if to != from {
+                    commands.push((*instr, to, from));
+                }
+            }
+        }
+    }
+    for i in 0..REGS {
+        for j in (i + 1)..REGS {
+            commands.push((CMP, i, j));
+        }
+    }
+    commands
+}
+
+// transform a permutation according to a command
+fn apply(cmd: &Command, perm: &mut Permutation) {
+    let (instr, to, from) = *cmd;
+    match instr {
+        CMP => {
+            perm[REGS + 0] = (perm[to] < perm[from]) as u8;
+            perm[REGS + 1] = (perm[to] > perm[from]) as u8;
+        }
+        MOV => perm[to] = perm[from],
+        CMOVG => {
+            if perm[REGS + 1] == 1 {
+                perm[to] = perm[from];
+            }
+        }
+        CMOVL => {
+            if perm[REGS + 0] == 1 {
+                perm[to] = perm[from];
+            }
+        }
+        _ => panic!("Unknown instruction"),
+    }
+}
+
+// map a command over all permutations in a state
+fn apply_all(cmd: &Command, state: &State) -> State {
+    let mut new_state = Vec::new();
+    for perm in state {
+        let mut new_perm = perm.clone();
+        apply(cmd, &mut new_perm);
+        new_state.push(new_perm);
+    }
+    new_state.sort();
+    new_state.dedup();
+    new_state
+}
+
+// check if the state can never reach a solution
+// corresponds to delete-relaxed planning check
+fn viable(state: &State) -> bool {
+    for perm in state {
+        for n in 1..=NUMBERS_U8 {
+            if !perm[0..REGS].contains(&n) {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+fn show_command(cmd: &Command) -> String {
+    let (instr, to, from) = *cmd;
+    // 1-indexed to stay consistent with minizinc
+    let to = to+1;
+    let from = from+1;
+    match instr {
+        CMP => format!("CMP {} {}", to, from),
+        MOV => format!("MOV {} {}", to, from),
+        CMOVG => format!("CMOVG {} {}", to, from),
+        CMOVL => format!("CMOVL {} {}", to, from),
+        _ => panic!("Unknown instruction"),
+    }
+}
+
+// linked list to store the commands and pointer to last element
+#[derive(Clone, Eq, PartialEq, Hash)]
+struct Node {
+    cmd: Command,
+    prev: Option<Rc<Node>>,
+}
+
+// for each permutation, take out register values, concat => serializable byte array
+fn state_positions(state: &State) -> Vec<u8> {
+    state.iter().flat_map(|p| p.0).collect()
+}
+
+fn extract_program(node: &Node) -> Vec<Command> {
+    let mut cmds = vec![];
+    let mut node = node;
+    while let Some(prev) = &node.prev {
+        cmds.push(node.cmd);
+        node = prev;
+    }
+    cmds.reverse();
+    cmds
+}
+
+fn is_sorted(perm: &Permutation) -> bool {
+    perm[0..NUMBERS].iter().copied().eq(1..=NUMBERS_U8)
+}
+
+// each instruction writes at most one register
+// => every register that is wrong in at least one permutation needs one more instruction
+fn admissible_heuristic(state: &State) -> u8 {
+    (0..NUMBERS)
+        .filter(|&i| state.iter().any(|p| p[i] != (i+1) as u8))
+        .count() as u8
+}
+
+// cost of a (partial) program besides the state it reaches
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+struct Label {
+    length: u8,
+    // bit i = scratch register i used
+    scratch: u8,
+    // cycle in which each register (and the flags) is available
+    ready: [u8; REGS + 1],
+}
+
+impl Label {
+    fn dominates(&self, other: &Label) -> bool {
+        self.length <= other.length
+            && self.scratch & other.scratch == self.scratch
+            && self.ready.iter().zip(other.ready.iter()).all(|(a, b)| a <= b)
+    }
+
+    fn scratch_count(&self) -> u8 {
+        self.scratch.count_ones() as u8
+    }
+
+    // critical path of the outputs
+    fn latency(&self) -> u8 {
+        *self.ready[0..NUMBERS].iter().max().unwrap()
+    }
+
+    fn extend(&self, cmd: &Command) -> Label {
+        let (instr, to, from) = *cmd;
+        let mut label = self.clone();
+        label.length += 1;
+        for reg in [to, from] {
+            if reg >= NUMBERS {
+                label.scratch |= 1 << (reg - NUMBERS);
+            }
+        }
+        match instr {
+            CMP => label.ready[FLAGS] = self.ready[to].max(self.ready[from]) + LAT_CMP,
+            MOV => label.ready[to] = self.ready[from] + LAT_MOV,
+            CMOVG | CMOVL => {
+                // conditional move also depends on the old value of the destination
+                label.ready[to] = self.ready[to].max(self.ready[from]).max(self.ready[FLAGS]) + LAT_CMOV;
+            }
+            _ => panic!("Unknown instruction"),
+        }
+        label
+    }
+}
+
+// (length, scratch registers, latency)
+type Objectives = (u8, u8, u8);
+
+fn objectives_dominate(a: &Objectives, b: &Objectives) -> bool {
+    a.0 <= b.0 && a.1 <= b.1 && a.2 <= b.2
+}
+
+fn main() {
+    let possible_cmds = possible_commands();
+    let permutations: Vec<Vec<u8>> = (1..=NUMBERS_U8).permutations(NUMBERS).collect();
+
+    println!("n = {}", NUMBERS);
+    println!("max_len = {}", MAX_LEN);
+    println!("swaps = {}", SWAPS);
+    println!("latencies: cmp {}, mov {}, cmov {}", LAT_CMP, LAT_MOV, LAT_CMOV);
+
+    // extend numerical permutations with register for swap and flags
+    let initial_state: Rc<State> = Rc::new(permutations
+        .iter()
+        .map(|p| {
+            let mut perm = Permutation([0; REGS + 2]);
+            for (i, &x) in p.iter().enumerate() {
+                perm[i] = x;
+            }
+            perm
+        })
+        .sorted()
+        .collect());
+
+    // non-dominated labels per state
+    let mut labels: HashMap<Vec<u8>, Vec<Label>> = HashMap::new();
+    let label0 = Label{length: 0, scratch: 0, ready: [0; REGS + 1]};
+    labels.insert(state_positions(&initial_state), vec![label0.clone()]);
+
+    let mut queue = PriorityQueue::new();
+    let node0 = Node{cmd: (0,0,0), prev: None};
+    queue.push((node0,Rc::clone(&initial_state),label0), Reverse(admissible_heuristic(&initial_state)));
+
+    let mut front: Vec<(Objectives, Vec<Command>)> = vec![];
+
+    let mut visited : u64 = 0;
+    let mut duplicate : u64 = 0;
+    let mut cut : u64 = 0;
+    let start = std::time::Instant::now();
+    while let Some(((prg,state,label), _)) = queue.pop() {
+        visited += 1;
+        if visited % 100000 == 0 {
+            print!("Open: {}, ", queue.len());
+            print!("Visited: {}, ", visited);
+            print!("Duplicate: {}, ", duplicate);
+            print!("Cut: {}, ", cut);
+            print!("Current length: {}, ", label.length);
+            print!("Front: {}, ", front.len());
+            print!("Time: {:?}", start.elapsed());
+            println!("");
+        }
+
+        // label was replaced by a better one in the meantime
+        if !labels[&state_positions(&state)].contains(&label) {
+            duplicate += 1;
+            continue;
+        }
+
+        if state.iter().all(is_sorted) {
+            let objectives = (label.length, label.scratch_count(), label.latency());
+            if !front.iter().any(|(o, _)| objectives_dominate(o, &objectives)) {
+                println!("New Pareto optimal program: length {}, scratch registers {}, latency {}, Time: {:?}",
+                    objectives.0, objectives.1, objectives.2, start.elapsed());
+                front.retain(|(o, _)| !objectives_dominate(&objectives, o));
+                front.push((objectives, extract_program(&prg)));
+            }
+            // no continue: a longer continuation might have a lower latency
+            // (e.g. move a value from a register that is ready earlier)
+        }
+
+        if label.length >= MAX_LEN {
+            continue;
+        }
+
+        let prev_rc = Some(Rc::new(prg));
+        for cmd in &possible_cmds {
+            let new_state = Rc::new(apply_all(cmd, &state));
+            let new_label = label.extend(cmd);
+
+            if !viable(&new_state) {
+                cut += 1;
+                continue;
+            }
+
+            // no cut with the front: the latency of the outputs can still decrease by moving values around
+            let new_score = new_label.length + admissible_heuristic(&new_state);
+            if new_score > MAX_LEN {
+                cut += 1;
+                continue;
+            }
+
+            let state_repr = state_positions(&new_state);
+            let state_labels = labels.entry(state_repr).or_insert(vec![]);
+            if state_labels.iter().any(|l| l.dominates(&new_label)) {
+                duplicate += 1;
+                continue;
+            }
+            state_labels.retain(|l| !new_label.dominates(l));
+            state_labels.push(new_label.clone());
+
+            let prg = Node{cmd: *cmd, prev: prev_rc.clone()};
+            queue.push((prg,Rc::clone(&new_state),new_label), Reverse(new_score));
+        }
+    }
+
+    front.sort_by_key(|(o, _)| *o);
+    println!("Pareto front ({} programs):", front.len());
+    for ((length, scratch, latency), cmds) in &front {
+        println!("length {}, scratch registers {}, latency {}", length, scratch, latency);
+        for cmd in cmds {
+            println!("  {}", show_command(cmd));
+        }
+    }
+
+    if let Ok(dir) = std::env::var("SOLUTION_DIR") {
+        let subdir = format!("{}/{}_{}_pareto", dir, NUMBERS, MAX_LEN);
+        std::fs::create_dir_all(&subdir).unwrap();
+        let mut summary = std::fs::File::create(format!("{}/front.txt", subdir)).unwrap();
+        writeln!(summary, "length scratch latency file").unwrap();
+        for ((length, scratch, latency), cmds) in &front {
+            let name = format!("solution_{}_{}_{}.txt", length, scratch, latency);
+            writeln!(summary, "{} {} {} {}", length, scratch, latency, name).unwrap();
+            let mut file = std::fs::File::create(format!("{}/{}", subdir, name)).unwrap();
+            for cmd in cmds {
+                writeln!(file, "{}", show_command(cmd)).unwrap();
+            }
+        }
+        println!("Stored Pareto front in: {}", subdir);
+    }
+
+    println!("Visited: {}, Duplicate: {}, Cut: {}", visited, duplicate, cut);
+    println!("Elapsed: {:?}", start.elapsed());
+}
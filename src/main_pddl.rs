@@ -0,0 +1,318 @@
+use itertools::Itertools;
+
+mod common;
+mod x86;
+use common::{inputs, Spec};
+use x86::*;
+
+/*
+Export the synthesis problem as PDDL (domain + problem) for off-the-shelf planners
+and import their plans back as programs.
+
+Usage:
+  pddl export [DIR]   writes DIR/domain.pddl and DIR/problem.pddl (default: current directory)
+  pddl import PLAN    reads a plan (e.g. sas_plan of Fast Downward) and verifies it on all inputs
+
+The configuration is chosen via environment variables (the same for export and import):
+  ISA  = cmov | minmax | mixed   (main_astar.rs, main_astar_minmax.rs, main_astar_mixed.rs)
+  SPEC = sort | desc | min | max | median | topk:K | partial:K | table:FILE   (see common.rs)
+
+Encoding (one planning task for all inputs at once, like our state = set of permutations):
+- objects: one perm per input, registers, values v0 (empty scratch register), v1, ..., vn
+- (holds ?p ?r ?v): register r holds value v in input p
+- (lt ?p), (gt ?p): flags of the last cmp in input p
+- each instruction is one action, its effect on each input is a conditional effect
+  (universally quantified over the inputs)
+- goal: all expected outputs are in their registers for all inputs
+A plan of length k is a program of length k and vice versa.
+*/
+
+const NUMBERS: usize = 3;
+// const NUMBERS: usize = 4;
+const SWAPS: usize = 1;
+const NUMBERS_U8: u8 = NUMBERS as u8;
+
+// 1-indexed to stay consistent with minizinc
+fn reg_name(reg: usize) -> String {
+    if reg < REGS {
+        format!("r{}", reg+1)
+    } else if reg >= XMMOFFSET {
+        format!("x{}", reg-XMMOFFSET+1)
+    } else {
+        panic!("Unknown register")
+    }
+}
+
+fn parse_reg(name: &str) -> Option<usize> {
+    let (class, index) = name.split_at(1);
+    let index = index.parse::<usize>().ok()?.checked_sub(1)?;
+    match class {
+        "r" if index < REGS => Some(index),
+        "x" if index < XMMREGS => Some(XMMOFFSET + index),
+        _ => None,
+    }
+}
+
+fn show_command(cmd: &Command) -> String {
+    let (instr, to, from) = *cmd;
+    let to = reg_name(to);
+    let from = reg_name(from);
+    match instr {
+        CMP => format!("CMP {} {}", to, from),
+        MOV => format!("MOV {} {}", to, from),
+        CMOVG => format!("CMOVG {} {}", to, from),
+        CMOVL => format!("CMOVL {} {}", to, from),
+        MIN => format!("MIN {} {}", to, from),
+        MAX => format!("MAX {} {}", to, from),
+        MOVD => format!("MOVD {} {}", to, from),
+        MOVDQA => format!("MOVDQA {} {}", to, from),
+        _ => panic!("Unknown instruction"),
+    }
+}
+
+// PDDL action name of a command (movd needs one action per direction)
+fn action_name(cmd: &Command) -> &'static str {
+    let (instr, to, _) = *cmd;
+    match instr {
+        CMP => "cmp",
+        MOV => "mov",
+        CMOVG => "cmovg",
+        CMOVL => "cmovl",
+        MIN => "pminud",
+        MAX => "pmaxud",
+        MOVD if to < REGS => "movd-to-gp",
+        MOVD => "movd-to-xmm",
+        MOVDQA => "movdqa",
+        _ => panic!("Unknown instruction"),
+    }
+}
+
+
+// copy the value of ?from into ?to (under an additional condition per input)
+// delete effects are applied before add effects => ?to == ?from would be a noop anyway
+fn copy_effect(condition: Option<&str>) -> String {
+    let when = |fact: &str| match condition {
+        Some(c) => format!("(and {} {})", c, fact),
+        None => fact.to_string(),
+    };
+    format!(
+"    :effect (forall (?p - perm ?v - val)
+              (and (when {} (not (holds ?p ?to ?v)))
+                   (when {} (holds ?p ?to ?v))))", when("(holds ?p ?to ?v)"), when("(holds ?p ?from ?v)"))
+}
+
+// ?to := min/max(?to, ?from) where `order` decides whether ?from replaces ?to
+fn select_effect(order: &str) -> String {
+    format!(
+"    :effect (forall (?p - perm ?v ?w - val)
+              (when (and (holds ?p ?to ?v) (holds ?p ?from ?w) {})
+                    (and (not (holds ?p ?to ?v)) (holds ?p ?to ?w))))", order)
+}
+
+fn copy_action(name: &str, to: &str, from: &str, condition: Option<&str>) -> String {
+    format!(
+"  (:action {}
+    :parameters (?to - {} ?from - {})
+    :precondition (not (= ?to ?from))
+{})
+", name, to, from, copy_effect(condition))
+}
+
+fn select_action(name: &str, order: &str) -> String {
+    format!(
+"  (:action {}
+    :parameters (?to ?from - xmmreg)
+    :precondition (not (= ?to ?from))
+{})
+", name, select_effect(order))
+}
+
+fn domain(isa: Isa) -> String {
+    let mut actions = String::new();
+    if isa != Isa::MinMax {
+        // cmp ri, rj only for i < j (as in possible_commands)
+        actions.push_str(
+"  (:action cmp
+    :parameters (?a ?b - gpreg)
+    :precondition (below ?a ?b)
+    :effect (and (forall (?p - perm)
+                   (and (when (lt ?p) (not (lt ?p)))
+                        (when (gt ?p) (not (gt ?p)))))
+                 (forall (?p - perm ?v ?w - val)
+                   (and (when (and (holds ?p ?a ?v) (holds ?p ?b ?w) (less ?v ?w)) (lt ?p))
+                        (when (and (holds ?p ?a ?v) (holds ?p ?b ?w) (less ?w ?v)) (gt ?p))))))
+");
+        actions.push_str(&copy_action("mov", "gpreg", "gpreg", None));
+        actions.push_str(&copy_action("cmovg", "gpreg", "gpreg", Some("(gt ?p)")));
+        actions.push_str(&copy_action("cmovl", "gpreg", "gpreg", Some("(lt ?p)")));
+    }
+    if isa != Isa::Cmov {
+        actions.push_str(&copy_action("movdqa", "xmmreg", "xmmreg", None));
+        actions.push_str(&select_action("pminud", "(less ?w ?v)"));
+        actions.push_str(&select_action("pmaxud", "(less ?v ?w)"));
+    }
+    if isa == Isa::Mixed {
+        actions.push_str(&copy_action("movd-to-gp", "gpreg", "xmmreg", None));
+        actions.push_str(&copy_action("movd-to-xmm", "xmmreg", "gpreg", None));
+    }
+    format!(
+"(define (domain sort-{})
+  (:requirements :strips :typing :equality :negative-preconditions :conditional-effects)
+  (:types perm val reg - object
+          gpreg xmmreg - reg)
+  (:predicates (holds ?p - perm ?r - reg ?v - val)
+               (lt ?p - perm)
+               (gt ?p - perm)
+               (less ?v ?w - val)
+               (below ?a ?b - reg))
+{})
+", isa.name(), actions)
+}
+
+fn problem(isa: Isa, spec_name: &str, inputs: &[(Vec<u8>, [u8; NUMBERS])]) -> String {
+    let perm_name = |k: usize| format!("p{}", k+1);
+    let val_name = |v: u8| format!("v{}", v);
+
+    let mut objects = String::new();
+    objects.push_str(&format!("    {} - perm\n", (0..inputs.len()).map(perm_name).join(" ")));
+    objects.push_str(&format!("    {} - val\n", (0..=NUMBERS_U8).map(val_name).join(" ")));
+    if !isa.gp_regs().is_empty() {
+        objects.push_str(&format!("    {} - gpreg\n", isa.gp_regs().map(reg_name).join(" ")));
+    }
+    if !isa.xmm_regs().is_empty() {
+        objects.push_str(&format!("    {} - xmmreg\n", isa.xmm_regs().map(reg_name).join(" ")));
+    }
+
+    let mut init = String::new();
+    for v in 0..=NUMBERS_U8 {
+        for w in (v+1)..=NUMBERS_U8 {
+            init.push_str(&format!("    (less {} {})\n", val_name(v), val_name(w)));
+        }
+    }
+    for i in isa.gp_regs() {
+        for j in (i+1)..isa.gp_regs().end {
+            init.push_str(&format!("    (below {} {})\n", reg_name(i), reg_name(j)));
+        }
+    }
+    for (k, (input, _)) in inputs.iter().enumerate() {
+        let perm = initial_perm(isa, input);
+        // scratch registers hold v0 (like the 0 in our permutations)
+        for reg in isa.gp_regs().chain(isa.xmm_regs()) {
+            init.push_str(&format!("    (holds {} {} {})\n", perm_name(k), reg_name(reg), val_name(perm[reg])));
+        }
+    }
+
+    let mut goal = String::new();
+    for (k, (_, target)) in inputs.iter().enumerate() {
+        for (reg, &t) in isa.output_regs().zip(target.iter()) {
+            if t != 0 {
+                goal.push_str(&format!("    (holds {} {} {})\n", perm_name(k), reg_name(reg), val_name(t)));
+            }
+        }
+    }
+
+    let name = spec_name.replace(|c: char| !c.is_ascii_alphanumeric(), "-");
+    format!(
+"(define (problem sort-{isa}-{n}-{swaps}-{name})
+  (:domain sort-{isa})
+  (:objects
+{objects}  )
+  (:init
+{init}  )
+  (:goal (and
+{goal}  ))
+)
+", isa = isa.name(), n = NUMBERS, swaps = SWAPS, name = name, objects = objects, init = init, goal = goal)
+}
+
+// reads a plan with one action per line, e.g. "(cmovg r1 r4)"
+// ignores comments (;) and step prefixes/costs of other planners ("0: (mov r1 r4) [1]")
+fn read_plan(file: &str, isa: Isa) -> Vec<Command> {
+    let content = std::fs::read_to_string(file).expect("Could not read plan");
+    let possible_cmds = possible_commands(isa);
+    let mut cmds = vec![];
+    for line in content.lines() {
+        let line = line.split(';').next().unwrap().trim().to_lowercase();
+        if line.is_empty() {
+            continue;
+        }
+        let action = match (line.find('('), line.find(')')) {
+            (Some(open), Some(close)) => &line[open+1..close],
+            _ => line.as_str(),
+        };
+        let parts = action.split_whitespace().collect::<Vec<_>>();
+        if parts.len() != 3 {
+            panic!("Expected action with two registers: {}", line);
+        }
+        let instr = match parts[0] {
+            "cmp" => CMP,
+            "mov" => MOV,
+            "cmovg" => CMOVG,
+            "cmovl" => CMOVL,
+            "pminud" => MIN,
+            "pmaxud" => MAX,
+            "movd-to-gp" | "movd-to-xmm" => MOVD,
+            "movdqa" => MOVDQA,
+            _ => panic!("Unknown action: {}", parts[0]),
+        };
+        let to = parse_reg(parts[1]).unwrap_or_else(|| panic!("Unknown register: {}", parts[1]));
+        let from = parse_reg(parts[2]).unwrap_or_else(|| panic!("Unknown register: {}", parts[2]));
+        let cmd = (instr, to, from);
+        if !possible_cmds.contains(&cmd) || action_name(&cmd) != parts[0] {
+            panic!("Action is not part of the {} ISA: {}", isa.name(), line);
+        }
+        cmds.push(cmd);
+    }
+    cmds
+}
+
+// run the program on all inputs, returns the inputs with a wrong output
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let isa = Isa::from_env();
+    let spec_name = std::env::var("SPEC").unwrap_or("sort".to_string());
+    let spec = Spec::from_env();
+    let inputs = inputs(&spec);
+
+    println!("n = {}", NUMBERS);
+    println!("swaps = {}", SWAPS);
+    println!("isa = {}", isa.name());
+    println!("spec = {}", spec_name);
+    println!("inputs = {}", inputs.len());
+
+    match args.get(1).map(|s| s.as_str()) {
+        Some("export") => {
+            let dir = args.get(2).cloned().unwrap_or(".".to_string());
+            std::fs::create_dir_all(&dir).unwrap();
+            let domain_file = format!("{}/domain.pddl", dir);
+            let problem_file = format!("{}/problem.pddl", dir);
+            std::fs::write(&domain_file, domain(isa)).unwrap();
+            std::fs::write(&problem_file, problem(isa, &spec_name, &inputs)).unwrap();
+            println!("Actions: {}", possible_commands(isa).len());
+            println!("Domain: {}", domain_file);
+            println!("Problem: {}", problem_file);
+        }
+        Some("import") => {
+            let file = args.get(2).expect("Usage: pddl import PLAN");
+            let cmds = read_plan(file, isa);
+            println!("Program of length {}:", cmds.len());
+            for cmd in &cmds {
+                println!("{}", show_command(cmd));
+            }
+            let wrong = verify(&cmds, isa, &inputs);
+            if wrong.is_empty() {
+                println!("Program is correct on all {} inputs", inputs.len());
+            } else {
+                for (input, perm) in &wrong {
+                    println!("Wrong output for input {:?}: {:?}", input, &perm[isa.output_regs()]);
+                }
+                println!("Program is wrong on {} of {} inputs", wrong.len(), inputs.len());
+                std::process::exit(1);
+            }
+        }
+        _ => {
+            println!("Usage: pddl export [DIR] | pddl import PLAN");
+            std::process::exit(1);
+        }
+    }
+}
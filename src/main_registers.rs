@@ -0,0 +1,366 @@
+use itertools::Itertools;
+use std::ops::Range;
+// has largest value at the top
+use priority_queue::PriorityQueue;
+use std::cmp::Reverse;
+use std::rc::Rc;
+use std::io::Write;
+
+/*
+Optimal program length for each number of scratch registers
+
+The permutation has room for MAX_SWAPS scratch registers,
+a search with `swaps` scratch registers only uses the first `swaps` of them
+(the others stay 0 and are never read or written).
+
+For swaps = 0, 1, ..., MAX_SWAPS we run A* with an admissible heuristic (see main_iterative.rs).
+More registers never hurt => the optimal program for `swaps` is also one for `swaps+1`
+=> with one more register, we only search for strictly shorter programs.
+*/
+
+// 0: none, 1: 11, 2: 11 in 234s
+const NUMBERS: usize = 3;
+const MAX_LEN: u8 = 12;
+// const NUMBERS: usize = 4;
+// const MAX_LEN: u8 = 24;
+const MAX_SWAPS: usize = 2;
+const REGS: usize = NUMBERS + MAX_SWAPS;
+const CMP: usize = 0;
+const MOV: usize = 1;
+const CMOVG: usize = 2;
+const CMOVL: usize = 3;
+const NUMBERS_U8: u8 = NUMBERS as u8;
+
+type Command = (usize, usize, usize);
+#[derive(Clone, Eq, PartialEq, Hash, Ord, PartialOrd, Debug, Copy)]
+struct Permutation([u8; REGS + 2]);
+type State = Vec<Permutation>;
+
+use std::ops::{Index, IndexMut};
+
+impl Index<usize> for Permutation {
+    type Output = u8;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+impl IndexMut<usize> for Permutation {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.0[index]
+    }
+}
+
+impl Index<Range<usize>> for Permutation {
+    type Output = [u8];
+
+    fn index(&self, index: Range<usize>) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+impl IndexMut<Range<usize>> for Permutation {
+    fn index_mut(&mut self, index: Range<usize>) -> &mut Self::Output {
+        &mut self.0[index]
+    }
+}
+
+// commands using the input registers and the first `swaps` scratch registers
+fn possible_commands(swaps: usize) -> Vec<Command> {
+    let regs = NUMBERS + swaps;
+    let mut commands = vec![];
+    for instr in &[MOV, CMOVG, CMOVL] {
+        for to in 0..regs {
+            for from in 0..regs {
+                if to != from {
+                    commands.push((*instr, to, from));
+                }
+            }
+        }
+    }
+    for i in 0..regs {
+        for j in (i + 1)..regs {
+            commands.push((CMP, i, j));
+        }
+    }
+    commands
+}
+
+// transform a permutation according to a command
+fn apply(cmd: &Command, perm: &mut Permutation) {
+    let (instr, to, from) = *cmd;
+    match instr {
+        CMP => {
+            perm[REGS + 0] = (perm[to] < perm[from]) as u8;
+            perm[REGS + 1] = (perm[to] > perm[from]) as u8;
+        }
+        MOV => perm[to] = perm[from],
+        CMOVG => {
+            if perm[REGS + 1] == 1 {
+                perm[to] = perm[from];
+            }
+        }
+        CMOVL => {
+            if perm[REGS + 0] == 1 {
+                perm[to] = perm[from];
+            }
+        }
+        _ => panic!("Unknown instruction"),
+    }
+}
+
+// map a command over all permutations in a state
+fn apply_all(cmd: &Command, state: &State) -> State {
+    let mut new_state = Vec::new();
+    for perm in state {
+        let mut new_perm = perm.clone();
+        apply(cmd, &mut new_perm);
+        new_state.push(new_perm);
+    }
+    new_state.sort();
+    new_state.dedup();
+    new_state
+}
+
+// check if the state can never reach a solution
+// corresponds to delete-relaxed planning check
+// (unused scratch registers are 0 => checking all registers is fine)
+fn viable(state: &State) -> bool {
+    for perm in state {
+        for n in 1..=NUMBERS_U8 {
+            if !perm[0..REGS].contains(&n) {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+fn show_command(cmd: &Command) -> String {
+    let (instr, to, from) = *cmd;
+    // 1-indexed to stay consistent with minizinc
+    let to = to+1;
+    let from = from+1;
+    match instr {
+        CMP => format!("CMP {} {}", to, from),
+        MOV => format!("MOV {} {}", to, from),
+        CMOVG => format!("CMOVG {} {}", to, from),
+        CMOVL => format!("CMOVL {} {}", to, from),
+        _ => panic!("Unknown instruction"),
+    }
+}
+
+// linked list to store the commands and pointer to last element
+// shared prefixes (Rc instead of Box) as cloning whole programs for each child
+// does not fit into memory for larger register counts
+#[derive(Clone, Eq, PartialEq, Hash)]
+struct Node {
+    cmd: Command,
+    prev: Option<Rc<Node>>,
+}
+
+// for each permutation, take out register values, concat => serializable byte array
+fn state_positions(state: &State) -> Vec<u8> {
+    state.iter().flat_map(|p| p.0).collect()
+}
+
+// scratch registers are interchangeable
+// => use the smallest representation over all orders of the scratch registers
+// (only for the duplicate check, the programs keep their register names)
+fn canonical_positions(state: &State, swaps: usize) -> Vec<u8> {
+    (NUMBERS..NUMBERS+swaps)
+        .permutations(swaps)
+        .map(|order| {
+            let mut renamed = state.iter().map(|p| {
+                let mut new_perm = *p;
+                for (i, &reg) in order.iter().enumerate() {
+                    new_perm[NUMBERS+i] = p[reg];
+                }
+                new_perm
+            }).collect::<Vec<_>>();
+            renamed.sort();
+            state_positions(&renamed)
+        })
+        .min()
+        .unwrap()
+}
+
+fn extract_program(node: &Node) -> Vec<Command> {
+    let mut cmds = vec![];
+    let mut node = node;
+    while let Some(prev) = &node.prev {
+        cmds.push(node.cmd);
+        node = prev;
+    }
+    cmds.reverse();
+    cmds
+}
+
+fn is_sorted(perm: &Permutation) -> bool {
+    perm[0..NUMBERS].iter().copied().eq(1..=NUMBERS_U8)
+}
+
+// each instruction writes at most one register
+// => every register that is wrong in at least one permutation needs one more instruction
+fn admissible_heuristic(state: &State) -> u8 {
+    (0..NUMBERS)
+        .filter(|&i| state.iter().any(|p| p[i] != (i+1) as u8))
+        .count() as u8
+}
+
+// find unused sled-mapX file in a temporary directory (_CONDOR_SCRATCH_DIR or /tmp/ else)
+// one map per scratch register count => opened as temporary, sled removes the directory when the db is dropped
+fn open_length_map() -> sled::Db {
+    let tmp_dir = std::env::var("_CONDOR_SCRATCH_DIR").unwrap_or("/tmp".to_string());
+    let mut i = 0;
+    let mut path = format!("{}/sled-map{}", tmp_dir, i);
+    while std::path::Path::new(&path).exists() {
+        i += 1;
+        path = format!("{}/sled-map{}", tmp_dir, i);
+    }
+    println!("Using sled map: {}", path);
+    sled::Config::new().path(path).temporary(true).open().unwrap()
+}
+
+// A* with admissible heuristic => the first solution is optimal
+// only programs of length <= max_len are considered
+fn optimal_program(initial_state: &Rc<State>, swaps: usize, max_len: u8) -> Option<Vec<Command>> {
+    let possible_cmds = possible_commands(swaps);
+    let length_map = open_length_map();
+    length_map.insert(canonical_positions(initial_state, swaps), vec![0 as u8]).unwrap();
+
+    let mut queue = PriorityQueue::new();
+    let node0 = Node{cmd: (0,0,0), prev: None};
+    queue.push((node0,Rc::clone(initial_state),0 as u8), Reverse(admissible_heuristic(initial_state)));
+
+    let mut visited : u64 = 0;
+    let mut duplicate : u64 = 0;
+    let mut cut : u64 = 0;
+    let start = std::time::Instant::now();
+    while let Some(((prg,state,length), Reverse(score))) = queue.pop() {
+        visited += 1;
+        if visited % 100000 == 0 {
+            print!("Open: {}, ", queue.len());
+            print!("Visited: {}, ", visited);
+            print!("Duplicate: {}, ", duplicate);
+            print!("Cut: {}, ", cut);
+            print!("Current length: {}, ", length);
+            print!("Lower bound: {}, ", score);
+            print!("Time: {:?}", start.elapsed());
+            println!("");
+        }
+
+        if let Some(state_len_vec) = length_map.get(canonical_positions(&state, swaps)).unwrap() {
+            if state_len_vec[0] < length {
+                duplicate += 1;
+                continue;
+            }
+        }
+
+        if state.iter().all(is_sorted) {
+            println!("Visited: {}, Duplicate: {}, Cut: {}", visited, duplicate, cut);
+            return Some(extract_program(&prg));
+        }
+
+        let prev_rc = Some(Rc::new(prg));
+        for cmd in &possible_cmds {
+            let new_state = Rc::new(apply_all(cmd, &state));
+            let new_length = length + 1;
+
+            if !viable(&new_state) {
+                cut += 1;
+                continue;
+            }
+
+            let new_score = new_length + admissible_heuristic(&new_state);
+            if new_score > max_len {
+                cut += 1;
+                continue;
+            }
+
+            let state_repr = canonical_positions(&new_state, swaps);
+            if let Some(old_length_vec) = length_map.get(&state_repr).unwrap() {
+                if old_length_vec[0] <= new_length {
+                    duplicate += 1;
+                    continue;
+                }
+            }
+            length_map.insert(state_repr, vec![new_length]).unwrap();
+
+            let prg = Node{cmd: *cmd, prev: prev_rc.clone()};
+            queue.push((prg,Rc::clone(&new_state),new_length), Reverse(new_score));
+        }
+    }
+    println!("Visited: {}, Duplicate: {}, Cut: {}", visited, duplicate, cut);
+    None
+}
+
+fn main() {
+    let permutations: Vec<Vec<u8>> = (1..=NUMBERS_U8).permutations(NUMBERS).collect();
+
+    println!("n = {}", NUMBERS);
+    println!("max_len = {}", MAX_LEN);
+    println!("max swaps = {}", MAX_SWAPS);
+
+    // extend numerical permutations with register for swap and flags
+    let initial_state: Rc<State> = Rc::new(permutations
+        .iter()
+        .map(|p| {
+            let mut perm = Permutation([0; REGS + 2]);
+            for (i, &x) in p.iter().enumerate() {
+                perm[i] = x;
+            }
+            perm
+        })
+        .sorted()
+        .collect());
+
+    let solution_dir = std::env::var("SOLUTION_DIR").ok();
+    let subdir = solution_dir.map(|dir| format!("{}/{}_registers", dir, NUMBERS));
+    if let Some(subdir) = &subdir {
+        std::fs::create_dir_all(&subdir).unwrap();
+        println!("Storing solutions in: {}", subdir);
+    }
+
+    let start = std::time::Instant::now();
+    let mut results = vec![];
+    // best program so far (valid for all larger register budgets)
+    let mut best: Option<Vec<Command>> = None;
+    for swaps in 0..=MAX_SWAPS {
+        // only look for programs that are strictly shorter than the best one
+        let bound = best.as_ref().map(|b| b.len() as u8 - 1).unwrap_or(MAX_LEN);
+        println!("Searching with {} scratch registers (max_len = {})", swaps, bound);
+        match optimal_program(&initial_state, swaps, bound) {
+            Some(cmds) => {
+                println!("Scratch registers: {}, optimal length: {}, Time: {:?}", swaps, cmds.len(), start.elapsed());
+                for cmd in &cmds {
+                    println!("{}", show_command(cmd));
+                }
+                if let Some(subdir) = &subdir {
+                    let file = format!("{}/solution_{}_swaps.txt", subdir, swaps);
+                    let mut file = std::fs::File::create(file).unwrap();
+                    for cmd in &cmds {
+                        writeln!(file, "{}", show_command(cmd)).unwrap();
+                    }
+                }
+                best = Some(cmds);
+            }
+            None => match &best {
+                // the additional register does not help
+                Some(cmds) => println!("Scratch registers: {}, optimal length: {} (no improvement), Time: {:?}", swaps, cmds.len(), start.elapsed()),
+                None => println!("Scratch registers: {}, no program of length <= {}, Time: {:?}", swaps, bound, start.elapsed()),
+            },
+        }
+        results.push((swaps, best.as_ref().map(|c| c.len())));
+    }
+
+    println!("Summary:");
+    for (swaps, len) in results {
+        match len {
+            Some(len) => println!("{} scratch registers: {} instructions", swaps, len),
+            None => println!("{} scratch registers: > {}", swaps, MAX_LEN),
+        }
+    }
+    println!("Elapsed: {:?}", start.elapsed());
+}
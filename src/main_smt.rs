@@ -0,0 +1,227 @@
+use itertools::Itertools;
+use std::fmt::Write;
+use std::ops::Range;
+
+mod x86;
+use x86::*;
+
+/*
+SMT-LIB2 correctness query for a program
+
+Our search only checks the permutations of 1..n.
+The query is over 32-bit bit-vectors (arbitrary values, duplicates allowed)
+and asserts that the output is not the sorted input
+=> unsat is a proof that the program sorts all inputs.
+
+Usage:
+  smt PROGRAM [FILE]   writes the query to FILE (default: PROGRAM.smt2)
+  e.g. z3 FILE, cvc5 FILE, bitwuzla FILE
+
+The program has one instruction per line, e.g. "CMOVG 1 4" or "min 1, 2"
+(registers 1-indexed, normal registers first, then xmm registers, like the output of the minizinc binary).
+
+Environment variables:
+  ISA   = cmov | minmax | mixed   (main_astar.rs, main_astar_minmax.rs, main_astar_mixed.rs)
+  ORDER = signed | unsigned       order of the sorted output
+          (default: signed for cmov (cmovg/cmovl are signed conditions), unsigned else (pminud/pmaxud))
+
+Semantics:
+- cmp sets lt/gt (signed like cmovl/cmovg), pminud/pmaxud are unsigned
+- scratch registers and flags start with arbitrary values (a program must not depend on them)
+- mixed: the input is in both register classes,
+  the output is expected in the class of the last written register (main_astar_mixed.rs accepts either)
+- sorted output: o_1 <= ... <= o_n and each input value occurs equally often in input and output
+*/
+
+const NUMBERS: usize = 3;
+// const NUMBERS: usize = 4;
+const SWAPS: usize = 1;
+const NUMBERS_U8: u8 = NUMBERS as u8;
+
+// registers holding the output
+// (mixed: the class of the last written register)
+fn output_regs(isa: Isa, cmds: &[Command]) -> Range<usize> {
+    match isa {
+        Isa::Cmov => 0..NUMBERS,
+        Isa::MinMax => XMMOFFSET..XMMOFFSET+NUMBERS,
+        Isa::Mixed => match cmds.iter().rev().find(|cmd| cmd.0 != CMP) {
+            Some(&(_, to, _)) if to >= XMMOFFSET => XMMOFFSET..XMMOFFSET+NUMBERS,
+            _ => 0..NUMBERS,
+        },
+    }
+}
+
+// one instruction per line, e.g. "CMOVG 1 4" or "min 1, 2"
+// empty lines and comments (# or ;) are ignored
+fn read_program(file: &str, isa: Isa) -> Vec<Command> {
+    let content = std::fs::read_to_string(file).expect("Could not read program");
+    let regs = registers(isa);
+    let possible_cmds = possible_commands(isa);
+    let mut cmds = vec![];
+    for line in content.lines() {
+        let line = line.split(|c| c == '#' || c == ';').next().unwrap().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let upper = line.replace(',', " ").to_uppercase();
+        let parts = upper.split_whitespace().collect::<Vec<_>>();
+        if parts.len() != 3 {
+            panic!("Expected instruction with two registers: {}", line);
+        }
+        let instr = INSTR_NAMES.iter().position(|&name| name == parts[0])
+            .unwrap_or_else(|| panic!("Unknown instruction: {}", parts[0]));
+        let reg = |x: &str| {
+            let index = x.parse::<usize>().unwrap_or_else(|_| panic!("Invalid register: {}", x));
+            *regs.get(index.wrapping_sub(1)).unwrap_or_else(|| panic!("Unknown register: {}", x))
+        };
+        let (to, from) = (reg(parts[1]), reg(parts[2]));
+        // main_astar_minmax.rs calls movdqa mov
+        let instr = if instr == MOV && to >= XMMOFFSET { MOVDQA } else { instr };
+        let cmd = (instr, to, from);
+        if !possible_cmds.contains(&cmd) {
+            panic!("Instruction is not part of the {} ISA: {}", isa.name(), line);
+        }
+        cmds.push(cmd);
+    }
+    cmds
+}
+
+// the check of our search: all permutations of 1..n (scratch registers 0)
+fn sorts_permutations(cmds: &[Command], isa: Isa) -> bool {
+    let outputs = output_regs(isa, cmds);
+    (1..=NUMBERS_U8).permutations(NUMBERS).all(|input| {
+        let mut perm = initial_perm(isa, &input);
+        for cmd in cmds {
+            apply(cmd, &mut perm);
+        }
+        perm[outputs.clone()].iter().copied().eq(1..=NUMBERS_U8)
+    })
+}
+
+// SSA name of a register (or flag) after `version` instructions
+fn smt_name(reg: usize, version: usize) -> String {
+    if reg < REGS {
+        format!("r{}_{}", reg+1, version)
+    } else if reg == REGS {
+        format!("lt_{}", version)
+    } else if reg == REGS + 1 {
+        format!("gt_{}", version)
+    } else {
+        format!("x{}_{}", reg-XMMOFFSET+1, version)
+    }
+}
+
+// n-ary operators need at least two arguments
+fn smt_op(op: &str, terms: Vec<String>, neutral: &str) -> String {
+    match terms.len() {
+        0 => neutral.to_string(),
+        1 => terms[0].clone(),
+        _ => format!("({} {})", op, terms.join(" ")),
+    }
+}
+
+fn smt_query(cmds: &[Command], isa: Isa, signed: bool) -> String {
+    let mut query = String::new();
+    writeln!(query, "; {} ISA, n = {}, {} instructions, {} order", isa.name(), NUMBERS, cmds.len(), if signed { "signed" } else { "unsigned" }).unwrap();
+    writeln!(query, "; unsat <=> the program sorts all inputs").unwrap();
+    // options are only allowed before set-logic (start mode)
+    writeln!(query, "(set-option :produce-models true)").unwrap();
+    writeln!(query, "(set-logic QF_BV)").unwrap();
+
+    let input = |i: usize| format!("in{}", i+1);
+    for i in 0..NUMBERS {
+        writeln!(query, "(declare-fun {} () (_ BitVec 32))", input(i)).unwrap();
+    }
+
+    // initial registers: inputs or arbitrary values
+    let input_regs = isa.input_regs();
+    for reg in registers(isa) {
+        match input_regs.iter().find(|regs| regs.contains(&reg)) {
+            Some(regs) => writeln!(query, "(define-fun {} () (_ BitVec 32) {})", smt_name(reg, 0), input(reg - regs.start)).unwrap(),
+            None => writeln!(query, "(declare-fun {} () (_ BitVec 32))", smt_name(reg, 0)).unwrap(),
+        }
+    }
+    if isa != Isa::MinMax {
+        writeln!(query, "(declare-fun {} () Bool)", smt_name(REGS, 0)).unwrap();
+        writeln!(query, "(declare-fun {} () Bool)", smt_name(REGS + 1, 0)).unwrap();
+    }
+
+    // current SSA version of each register and flag
+    let mut version = [0; REGS + 2 + XMMREGS];
+    for (step, cmd) in cmds.iter().enumerate() {
+        let step = step + 1;
+        let (instr, to, from) = *cmd;
+        let a = smt_name(to, version[to]);
+        let b = smt_name(from, version[from]);
+        writeln!(query, "; {}", show_command(cmd, isa)).unwrap();
+        if instr == CMP {
+            writeln!(query, "(define-fun {} () Bool (bvslt {} {}))", smt_name(REGS, step), a, b).unwrap();
+            writeln!(query, "(define-fun {} () Bool (bvsgt {} {}))", smt_name(REGS + 1, step), a, b).unwrap();
+            version[REGS] = step;
+            version[REGS + 1] = step;
+            continue;
+        }
+        let value = match instr {
+            MOV | MOVD | MOVDQA => b,
+            CMOVG => format!("(ite {} {} {})", smt_name(REGS + 1, version[REGS + 1]), b, a),
+            CMOVL => format!("(ite {} {} {})", smt_name(REGS, version[REGS]), b, a),
+            MIN => format!("(ite (bvult {} {}) {} {})", b, a, b, a),
+            MAX => format!("(ite (bvugt {} {}) {} {})", b, a, b, a),
+            _ => panic!("Unknown instruction"),
+        };
+        writeln!(query, "(define-fun {} () (_ BitVec 32) {})", smt_name(to, step), value).unwrap();
+        version[to] = step;
+    }
+
+    let outputs = output_regs(isa, cmds).map(|reg| smt_name(reg, version[reg])).collect::<Vec<_>>();
+    let le = if signed { "bvsle" } else { "bvule" };
+    let sorted = outputs.iter().tuple_windows().map(|(a, b)| format!("({} {} {})", le, a, b)).collect();
+    writeln!(query, "(define-fun sorted () Bool {})", smt_op("and", sorted, "true")).unwrap();
+
+    // multiset equality: every input value occurs equally often in the input and the output
+    // (n values in the output => no other values)
+    let count = |values: Vec<String>, x: &str| {
+        let terms = values.iter().map(|v| format!("(ite (= {} {}) #x01 #x00)", v, x)).collect();
+        smt_op("bvadd", terms, "#x00")
+    };
+    let inputs = (0..NUMBERS).map(input).collect::<Vec<_>>();
+    let permuted = inputs
+        .iter()
+        .map(|x| format!("(= {} {})", count(outputs.clone(), x), count(inputs.clone(), x)))
+        .collect();
+    writeln!(query, "(define-fun permuted () Bool {})", smt_op("and", permuted, "true")).unwrap();
+
+    writeln!(query, "(assert (not (and sorted permuted)))").unwrap();
+    writeln!(query, "(check-sat)").unwrap();
+    writeln!(query, "; sat => (get-model) gives a counterexample").unwrap();
+    query
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let isa = Isa::from_env();
+    let signed = match std::env::var("ORDER").ok().as_deref() {
+        Some("signed") => true,
+        Some("unsigned") => false,
+        Some(order) => panic!("Unknown order: {}", order),
+        None => isa == Isa::Cmov,
+    };
+
+    let program = args.get(1).expect("Usage: smt PROGRAM [FILE]");
+    let file = args.get(2).cloned().unwrap_or(format!("{}.smt2", program));
+    let cmds = read_program(program, isa);
+
+    println!("n = {}", NUMBERS);
+    println!("swaps = {}", SWAPS);
+    println!("isa = {}", isa.name());
+    println!("order = {}", if signed { "signed" } else { "unsigned" });
+    println!("Program of length {}:", cmds.len());
+    for cmd in &cmds {
+        println!("{}", show_command(cmd, isa));
+    }
+    // only a quick check, the query is the proof
+    println!("Sorts all permutations of 1..{}: {}", NUMBERS, sorts_permutations(&cmds, isa));
+
+    std::fs::write(&file, smt_query(&cmds, isa, signed)).unwrap();
+    println!("Query: {}", file);
+}
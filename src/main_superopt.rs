@@ -0,0 +1,433 @@
+use itertools::Itertools;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+// has largest value at the top
+use priority_queue::PriorityQueue;
+use std::cmp::Reverse;
+use std::rc::Rc;
+use std::io::Write;
+
+/*
+Superoptimize an existing program instead of starting from scratch
+
+Usage: superopt PROGRAM   (one instruction per line like the solutions of main_astar.rs, e.g. "CMOVG 1 4")
+
+states[k] = state after the first k instructions (apply_all)
+the rest of the program only sees states[k] => every part can be replaced by a shorter one with the same result
+Rewrites (repeated until none applies):
+1. dead code: states[i] == states[j] => drop the instructions i..j
+2. windows: BFS from states[i] for a program shorter than j-i that reaches exactly states[j] (j-i <= MAX_WINDOW)
+3. suffix: A* with the admissible heuristic (see main_iterative.rs) from states[i] to a sorted state,
+   the original suffix is the upper bound => only strictly shorter suffixes
+   (MAX_SUFFIX environment variable limits the length of the searched suffixes, the whole program is a search from scratch)
+
+Proof: the improved program is run on each permutation separately and compared with the original program.
+*/
+
+const NUMBERS: usize = 3;
+// const NUMBERS: usize = 4;
+const SWAPS: usize = 1;
+const MAX_WINDOW: usize = 4;
+const REGS: usize = NUMBERS + SWAPS;
+const CMP: usize = 0;
+const MOV: usize = 1;
+const CMOVG: usize = 2;
+const CMOVL: usize = 3;
+const NUMBERS_U8: u8 = NUMBERS as u8;
+
+type Command = (usize, usize, usize);
+#[derive(Clone, Eq, PartialEq, Hash, Ord, PartialOrd, Debug, Copy)]
+struct Permutation([u8; REGS + 2]);
+type State = Vec<Permutation>;
+
+use std::ops::{Index, IndexMut};
+
+impl Index<usize> for Permutation {
+    type Output = u8;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+impl IndexMut<usize> for Permutation {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.0[index]
+    }
+}
+
+impl Index<Range<usize>> for Permutation {
+    type Output = [u8];
+
+    fn index(&self, index: Range<usize>) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+impl IndexMut<Range<usize>> for Permutation {
+    fn index_mut(&mut self, index: Range<usize>) -> &mut Self::Output {
+        &mut self.0[index]
+    }
+}
+
+fn possible_commands() -> Vec<Command> {
+    let mut commands = vec![];
+    for instr in &[MOV, CMOVG, CMOVL] {
+        for to in 0..REGS {
+            for from in 0..REGS {
+                if to != from {
+                    commands.push((*instr, to, from));
+                }
+            }
+        }
+    }
+    for i in 0..REGS {
+        for j in (i + 1)..REGS {
+            commands.push((CMP, i, j));
+        }
+    }
+    commands
+}
+
+// transform a permutation according to a command
+fn apply(cmd: &Command, perm: &mut Permutation) {
+    let (instr, to, from) = *cmd;
+    match instr {
+        CMP => {
+            perm[REGS + 0] = (perm[to] < perm[from]) as u8;
+            perm[REGS + 1] = (perm[to] > perm[from]) as u8;
+        }
+        MOV => perm[to] = perm[from],
+        CMOVG => {
+            if perm[REGS + 1] == 1 {
+                perm[to] = perm[from];
+            }
+        }
+        CMOVL => {
+            if perm[REGS + 0] == 1 {
+                perm[to] = perm[from];
+            }
+        }
+        _ => panic!("Unknown instruction"),
+    }
+}
+
+// map a command over all permutations in a state
+fn apply_all(cmd: &Command, state: &State) -> State {
+    let mut new_state = Vec::new();
+    for perm in state {
+        let mut new_perm = perm.clone();
+        apply(cmd, &mut new_perm);
+        new_state.push(new_perm);
+    }
+    new_state.sort();
+    new_state.dedup();
+    new_state
+}
+
+// check if the state can never reach a solution
+// corresponds to delete-relaxed planning check
+fn viable(state: &State) -> bool {
+    for perm in state {
+        for n in 1..=NUMBERS_U8 {
+            if !perm[0..REGS].contains(&n) {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+fn show_command(cmd: &Command) -> String {
+    let (instr, to, from) = *cmd;
+    // 1-indexed to stay consistent with minizinc
+    let to = to+1;
+    let from = from+1;
+    match instr {
+        CMP => format!("CMP {} {}", to, from),
+        MOV => format!("MOV {} {}", to, from),
+        CMOVG => format!("CMOVG {} {}", to, from),
+        CMOVL => format!("CMOVL {} {}", to, from),
+        _ => panic!("Unknown instruction"),
+    }
+}
+
+// one instruction per line, e.g. "CMOVG 1 4" (1-indexed registers)
+// empty lines and comments (#) are ignored
+fn read_program(file: &str) -> Vec<Command> {
+    let content = std::fs::read_to_string(file).expect("Could not read program");
+    let mut cmds = vec![];
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let upper = line.replace(',', " ").to_uppercase();
+        let parts = upper.split_whitespace().collect::<Vec<_>>();
+        if parts.len() != 3 {
+            panic!("Expected instruction with two registers: {}", line);
+        }
+        let instr = match parts[0] {
+            "CMP" => CMP,
+            "MOV" => MOV,
+            "CMOVG" => CMOVG,
+            "CMOVL" => CMOVL,
+            _ => panic!("Unknown instruction: {}", line),
+        };
+        let reg = |x: &str| match x.parse::<usize>() {
+            Ok(r) if r >= 1 && r <= REGS => r - 1,
+            _ => panic!("Unknown register: {}", line),
+        };
+        let (to, from) = (reg(parts[1]), reg(parts[2]));
+        if to == from {
+            panic!("Instruction with the same register twice: {}", line);
+        }
+        cmds.push((instr, to, from));
+    }
+    cmds
+}
+
+// linked list to store the commands and pointer to last element
+// shared prefixes (Rc instead of Box) as in main_registers.rs
+#[derive(Clone, Eq, PartialEq, Hash)]
+struct Node {
+    cmd: Command,
+    prev: Option<Rc<Node>>,
+}
+
+fn extract_program(node: &Node) -> Vec<Command> {
+    let mut cmds = vec![];
+    let mut node = node;
+    while let Some(prev) = &node.prev {
+        cmds.push(node.cmd);
+        node = prev;
+    }
+    cmds.reverse();
+    cmds
+}
+
+fn is_sorted(perm: &Permutation) -> bool {
+    perm[0..NUMBERS].iter().copied().eq(1..=NUMBERS_U8)
+}
+
+// each instruction writes at most one register
+// => every register that is wrong in at least one permutation needs one more instruction
+fn admissible_heuristic(state: &State) -> u8 {
+    (0..NUMBERS)
+        .filter(|&i| state.iter().any(|p| p[i] != (i+1) as u8))
+        .count() as u8
+}
+
+// states[k] = state after the first k instructions
+fn prefix_states(cmds: &[Command], initial_state: &State) -> Vec<State> {
+    let mut states = vec![initial_state.clone()];
+    for cmd in cmds {
+        let state = apply_all(cmd, states.last().unwrap());
+        states.push(state);
+    }
+    states
+}
+
+// shortest program of length < max_len from `from` to exactly `to` (breadth-first)
+fn shortest_window(possible_cmds: &[Command], from: &State, to: &State, max_len: usize) -> Option<Vec<Command>> {
+    let mut visited = HashSet::new();
+    visited.insert(from.clone());
+    let mut layer = vec![(from.clone(), vec![])];
+    for _ in 1..max_len {
+        let mut next_layer = vec![];
+        for (state, prg) in &layer {
+            for cmd in possible_cmds {
+                let new_state = apply_all(cmd, state);
+                if !viable(&new_state) || !visited.insert(new_state.clone()) {
+                    continue;
+                }
+                let mut new_prg: Vec<Command> = prg.clone();
+                new_prg.push(*cmd);
+                if &new_state == to {
+                    return Some(new_prg);
+                }
+                next_layer.push((new_state, new_prg));
+            }
+        }
+        layer = next_layer;
+    }
+    None
+}
+
+// A* with admissible heuristic => the first solution is optimal
+// only programs of length <= max_len are considered
+fn shortest_suffix(possible_cmds: &[Command], initial_state: &Rc<State>, max_len: u8) -> Option<Vec<Command>> {
+    let mut length_map: HashMap<State, u8> = HashMap::new();
+    length_map.insert(initial_state.to_vec(), 0);
+
+    let mut queue = PriorityQueue::new();
+    let node0 = Node{cmd: (0,0,0), prev: None};
+    queue.push((node0,Rc::clone(initial_state),0 as u8), Reverse(admissible_heuristic(initial_state)));
+
+    while let Some(((prg,state,length), _)) = queue.pop() {
+        if length_map[state.as_ref()] < length {
+            continue;
+        }
+        if state.iter().all(is_sorted) {
+            return Some(extract_program(&prg));
+        }
+
+        let prev_rc = Some(Rc::new(prg));
+        for cmd in possible_cmds {
+            let new_state = apply_all(cmd, &state);
+            let new_length = length + 1;
+            if !viable(&new_state) {
+                continue;
+            }
+            let new_score = new_length + admissible_heuristic(&new_state);
+            if new_score > max_len {
+                continue;
+            }
+            if let Some(&old_length) = length_map.get(&new_state) {
+                if old_length <= new_length {
+                    continue;
+                }
+            }
+            length_map.insert(new_state.clone(), new_length);
+
+            let prg = Node{cmd: *cmd, prev: prev_rc.clone()};
+            queue.push((prg,Rc::new(new_state),new_length), Reverse(new_score));
+        }
+    }
+    None
+}
+
+fn show_program(cmds: &[Command]) -> String {
+    cmds.iter().map(show_command).join("; ")
+}
+
+// one rewrite step, returns the improved program
+fn improve(possible_cmds: &[Command], cmds: &[Command], initial_state: &State, max_suffix: usize) -> Option<Vec<Command>> {
+    let states = prefix_states(cmds, initial_state);
+    let replace = |i: usize, j: usize, new: &[Command]| {
+        let mut improved = cmds[..i].to_vec();
+        improved.extend_from_slice(new);
+        improved.extend_from_slice(&cmds[j..]);
+        improved
+    };
+
+    // dead code (longest first)
+    for i in 0..cmds.len() {
+        if let Some(j) = (i+1..=cmds.len()).rev().find(|&j| states[i] == states[j]) {
+            println!("Dead code {}..{}: {}", i+1, j, show_program(&cmds[i..j]));
+            return Some(replace(i, j, &[]));
+        }
+    }
+
+    // windows
+    for width in 2..=MAX_WINDOW.min(cmds.len()) {
+        for i in 0..=(cmds.len() - width) {
+            let j = i + width;
+            if let Some(new) = shortest_window(possible_cmds, &states[i], &states[j], width) {
+                println!("Window {}..{}: {} => {}", i+1, j, show_program(&cmds[i..j]), show_program(&new));
+                return Some(replace(i, j, &new));
+            }
+        }
+    }
+
+    // suffixes (short ones first)
+    for i in (0..cmds.len()).rev() {
+        let suffix_len = cmds.len() - i;
+        if suffix_len > max_suffix {
+            break;
+        }
+        // strictly shorter than the original suffix
+        let bound = (suffix_len - 1) as u8;
+        if let Some(new) = shortest_suffix(possible_cmds, &Rc::new(states[i].clone()), bound) {
+            println!("Suffix {}..{}: {} => {}", i+1, cmds.len(), show_program(&cmds[i..]), show_program(&new));
+            return Some(replace(i, cmds.len(), &new));
+        }
+    }
+    None
+}
+
+// registers of the permutation after running the program on it
+fn run(cmds: &[Command], input: &[u8]) -> Permutation {
+    let mut perm = Permutation([0; REGS + 2]);
+    for (i, &x) in input.iter().enumerate() {
+        perm[i] = x;
+    }
+    for cmd in cmds {
+        apply(cmd, &mut perm);
+    }
+    perm
+}
+
+// per permutation: input, output of the original program, output of the improved program
+fn proof(original: &[Command], improved: &[Command], permutations: &[Vec<u8>]) -> (bool, Vec<String>) {
+    let mut equivalent = true;
+    let mut lines = vec![];
+    for input in permutations {
+        let before = run(original, input);
+        let after = run(improved, input);
+        let same = before[0..NUMBERS] == after[0..NUMBERS];
+        equivalent &= same;
+        lines.push(format!("{:?} -> {:?} (original) {:?} (improved){}",
+            input, &before[0..NUMBERS], &after[0..NUMBERS], if same { "" } else { " DIFFERENT" }));
+    }
+    (equivalent, lines)
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let file = args.get(1).expect("Usage: superopt PROGRAM");
+    let original = read_program(file);
+    let max_suffix = std::env::var("MAX_SUFFIX").map(|s| s.parse::<usize>().unwrap()).unwrap_or(usize::MAX);
+
+    let possible_cmds = possible_commands();
+    let permutations: Vec<Vec<u8>> = (1..=NUMBERS_U8).permutations(NUMBERS).collect();
+    let initial_state: State = permutations.iter().map(|p| run(&[], p)).sorted().collect();
+
+    println!("n = {}", NUMBERS);
+    println!("swaps = {}", SWAPS);
+    println!("max window = {}", MAX_WINDOW);
+    println!("Original program of length {}", original.len());
+
+    // windows and suffixes only keep the final state => the original has to be correct
+    if !prefix_states(&original, &initial_state).last().unwrap().iter().all(is_sorted) {
+        println!("The program does not sort all permutations");
+        std::process::exit(1);
+    }
+
+    let start = std::time::Instant::now();
+    let mut cmds = original.clone();
+    while let Some(improved) = improve(&possible_cmds, &cmds, &initial_state, max_suffix) {
+        cmds = improved;
+        println!("Length: {}, Time: {:?}", cmds.len(), start.elapsed());
+    }
+
+    println!("Improved program of length {} (original {}):", cmds.len(), original.len());
+    for cmd in &cmds {
+        println!("{}", show_command(cmd));
+    }
+
+    let (equivalent, lines) = proof(&original, &cmds, &permutations);
+    for line in &lines {
+        println!("{}", line);
+    }
+    if equivalent {
+        println!("Equivalent on all {} permutations", permutations.len());
+    } else {
+        println!("Not equivalent");
+    }
+
+    if let Ok(dir) = std::env::var("SOLUTION_DIR") {
+        let subdir = format!("{}/{}_superopt", dir, NUMBERS);
+        std::fs::create_dir_all(&subdir).unwrap();
+        let mut file = std::fs::File::create(format!("{}/solution.txt", subdir)).unwrap();
+        for cmd in &cmds {
+            writeln!(file, "{}", show_command(cmd)).unwrap();
+        }
+        let mut file = std::fs::File::create(format!("{}/proof.txt", subdir)).unwrap();
+        for line in &lines {
+            writeln!(file, "{}", line).unwrap();
+        }
+        println!("Stored solution and proof in: {}", subdir);
+    }
+    println!("Elapsed: {:?}", start.elapsed());
+}
@@ -0,0 +1,164 @@
+use itertools::Itertools;
+use std::ops::Range;
+use std::io::Write;
+
+mod x86;
+use x86::*;
+
+/*
+Translate a comparator network into a program of one of our ISAs
+
+Usage: translate [NETWORK]
+  NETWORK: comparators (i,j) with 1-indexed wires, e.g. the output of the network binary
+           "(1,2) (3,4)", "1 2" or "[1:2]" (all numbers are read pairwise, # and ; start comments)
+  without NETWORK a known optimal network (size) for n is used
+
+Environment variable ISA = cmov | minmax | mixed (main_astar.rs, main_astar_minmax.rs, main_astar_mixed.rs)
+
+Wire k stays in register k (the input/output registers of the ISA).
+Each comparator (i,j) (min to i, max to j) needs a copy of one wire in a scratch register:
+  cmov:   mov s, i; cmp i, j; cmovg i, j; cmovg j, s
+  minmax: movdqa s, i; pminud i, j; pmaxud j, s
+  mixed:  the input is in both classes => the (shorter) xmm sequence
+The scratch register is the first register of the class that holds no wire
+(the copy is dead after the comparator, one scratch register suffices).
+
+The program is checked on all permutations of 1..n (like our searches)
+and its length is an upper bound for MAX_LEN.
+The output uses the program format of the smt and superopt binaries ("CMOVG 1 4").
+*/
+
+const NUMBERS: usize = 3;
+// const NUMBERS: usize = 8;
+const SWAPS: usize = 1;
+const NUMBERS_U8: u8 = NUMBERS as u8;
+
+// optimal number of comparators (Knuth, TAOCP Vol. 3, 5.3.4), index = n
+const KNOWN_NETWORKS: [&str; 9] = [
+    "",
+    "",
+    "(1,2)",
+    "(1,2)\n(2,3)\n(1,2)",
+    "(1,2) (3,4)\n(1,3) (2,4)\n(2,3)",
+    "(1,2) (4,5)\n(3,5)\n(3,4) (2,5)\n(1,4)\n(1,3) (2,4)\n(2,3)",
+    "(1,2) (3,4)\n(1,3) (2,4) (5,6)\n(1,5) (2,3) (4,6)\n(2,4) (3,5)\n(2,3) (4,5)",
+    "(5,6) (1,7) (2,3)\n(1,5) (2,4) (6,7)\n(1,2) (3,4) (5,6)\n(3,7) (4,6)\n(6,7) (3,5) (2,4)\n(4,5) (2,3)",
+    "(1,2) (3,4) (5,6) (7,8)\n(1,3) (2,4) (5,7) (6,8)\n(2,3) (6,7)\n(1,5) (2,6) (3,7) (4,8)\n(3,5) (4,6)\n(2,3) (4,5) (6,7)",
+];
+
+type Comparator = (usize, usize);
+
+// the wires of a network in the model of x86.rs
+impl Isa {
+    // registers of the wires = the output registers
+    fn wire_regs(&self) -> Range<usize> {
+        match self {
+            Isa::Cmov => 0..NUMBERS,
+            _ => XMMOFFSET..XMMOFFSET+NUMBERS,
+        }
+    }
+
+    // first register of the class of the wires that holds no wire
+    fn scratch_reg(&self) -> usize {
+        let class = if *self == Isa::Cmov { self.gp_regs() } else { self.xmm_regs() };
+        let wires = self.wire_regs();
+        class.into_iter().find(|r| !wires.contains(r)).expect("No scratch register (SWAPS = 0)")
+    }
+}
+
+// all numbers pairwise, comments (# or ;) are ignored
+fn read_network(content: &str) -> Vec<Comparator> {
+    let numbers = content
+        .lines()
+        .map(|line| line.split(|c| c == '#' || c == ';').next().unwrap())
+        .flat_map(|line| line.split(|c: char| !c.is_ascii_digit()).filter(|s| !s.is_empty()).collect::<Vec<_>>())
+        .map(|s| s.parse::<usize>().unwrap())
+        .collect::<Vec<_>>();
+    if numbers.len() % 2 != 0 {
+        panic!("Odd number of wires in the network");
+    }
+    numbers
+        .chunks(2)
+        .map(|pair| {
+            let (i, j) = (pair[0].min(pair[1]), pair[0].max(pair[1]));
+            if i == 0 || j > NUMBERS || i == j {
+                panic!("Invalid comparator ({},{}) for n = {}", pair[0], pair[1], NUMBERS);
+            }
+            // 0-indexed wires
+            (i - 1, j - 1)
+        })
+        .collect()
+}
+
+fn translate(network: &[Comparator], isa: Isa) -> Vec<Command> {
+    let wires = isa.wire_regs();
+    let s = isa.scratch_reg();
+    let mut cmds = vec![];
+    for &(i, j) in network {
+        let (i, j) = (wires.start + i, wires.start + j);
+        match isa {
+            Isa::Cmov => {
+                // flags of i vs j, i > j => swap
+                cmds.push((MOV, s, i));
+                cmds.push((CMP, i, j));
+                cmds.push((CMOVG, i, j));
+                cmds.push((CMOVG, j, s));
+            }
+            Isa::MinMax | Isa::Mixed => {
+                cmds.push((MOVDQA, s, i));
+                cmds.push((MIN, i, j));
+                cmds.push((MAX, j, s));
+            }
+        }
+    }
+    cmds
+}
+
+// the check of our search: all permutations of 1..n (scratch registers 0)
+fn sorts_permutations(cmds: &[Command], isa: Isa) -> bool {
+    let outputs = isa.wire_regs();
+    (1..=NUMBERS_U8).permutations(NUMBERS).all(|input| {
+        let mut perm = initial_perm(isa, &input);
+        for cmd in cmds {
+            apply(cmd, &mut perm);
+        }
+        perm[outputs.clone()].iter().copied().eq(1..=NUMBERS_U8)
+    })
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let isa = Isa::from_env();
+    let content = match args.get(1) {
+        Some(file) => std::fs::read_to_string(file).expect("Could not read network"),
+        None => KNOWN_NETWORKS.get(NUMBERS).expect("No known network for n").to_string(),
+    };
+    let network = read_network(&content);
+    let cmds = translate(&network, isa);
+
+    println!("n = {}", NUMBERS);
+    println!("swaps = {}", SWAPS);
+    println!("isa = {}", isa.name());
+    println!("Network of size {}: {}", network.len(), network.iter().map(|(i, j)| format!("({},{})", i+1, j+1)).join(" "));
+    println!("Program of length {}:", cmds.len());
+    for cmd in &cmds {
+        println!("{}", show_command(cmd, isa));
+    }
+    let correct = sorts_permutations(&cmds, isa);
+    println!("Sorts all permutations of 1..{}: {}", NUMBERS, correct);
+    if !correct {
+        println!("The network does not sort");
+        std::process::exit(1);
+    }
+    println!("Upper bound: MAX_LEN = {}", cmds.len());
+
+    if let Ok(dir) = std::env::var("SOLUTION_DIR") {
+        let subdir = format!("{}/{}_translate_{}", dir, NUMBERS, isa.name());
+        std::fs::create_dir_all(&subdir).unwrap();
+        let mut file = std::fs::File::create(format!("{}/solution.txt", subdir)).unwrap();
+        for cmd in &cmds {
+            writeln!(file, "{}", show_command(cmd, isa)).unwrap();
+        }
+        println!("Stored solution in: {}", subdir);
+    }
+}
@@ -0,0 +1,231 @@
+// x86 register model of the exporters and importers
+// (main_pddl.rs, main_minizinc.rs, main_smt.rs, main_translate.rs, main_asm.rs)
+// ISA = cmov | minmax | mixed   (main_astar.rs, main_astar_minmax.rs, main_astar_mixed.rs)
+// not every binary uses every part of the model
+#![allow(dead_code)]
+use std::ops::Range;
+use crate::{NUMBERS, SWAPS};
+
+pub const REGS: usize = NUMBERS + SWAPS;
+pub const XMMREGS: usize = NUMBERS + SWAPS;
+pub const XMMOFFSET: usize = REGS + 2; // register + flags
+
+pub const CMP: usize = 0; // only normal registers
+pub const MOV: usize = 1; // only normal registers
+pub const CMOVG: usize = 2; // only normal registers
+pub const CMOVL: usize = 3; // only normal registers
+
+pub const MIN: usize = 4; // only xmm register
+pub const MAX: usize = 5; // only xmm register
+pub const MOVD: usize = 6; // normal register <-> xmm register
+pub const MOVDQA: usize = 7; // between xmm registers
+pub const INSTR_NAMES: [&str; 8] = ["CMP", "MOV", "CMOVG", "CMOVL", "MIN", "MAX", "MOVD", "MOVDQA"];
+
+pub type Command = (usize, usize, usize);
+#[derive(Clone, Eq, PartialEq, Hash, Ord, PartialOrd, Debug, Copy)]
+pub struct Permutation([u8; REGS + 2 + XMMREGS]);
+
+use std::ops::{Index, IndexMut};
+
+impl Index<usize> for Permutation {
+    type Output = u8;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+impl IndexMut<usize> for Permutation {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.0[index]
+    }
+}
+
+impl Index<Range<usize>> for Permutation {
+    type Output = [u8];
+
+    fn index(&self, index: Range<usize>) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Isa {
+    // cmp, mov, cmovg, cmovl on normal registers
+    Cmov,
+    // movdqa, pminud, pmaxud on xmm registers
+    MinMax,
+    // both + movd between the register classes
+    Mixed,
+}
+
+impl Isa {
+    pub fn from_env() -> Isa {
+        let isa = std::env::var("ISA").unwrap_or("cmov".to_string());
+        match isa.as_str() {
+            "cmov" => Isa::Cmov,
+            "minmax" => Isa::MinMax,
+            "mixed" => Isa::Mixed,
+            _ => panic!("Unknown ISA: {}", isa),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Isa::Cmov => "cmov",
+            Isa::MinMax => "minmax",
+            Isa::Mixed => "mixed",
+        }
+    }
+
+    pub fn gp_regs(&self) -> Range<usize> {
+        match self {
+            Isa::MinMax => 0..0,
+            _ => 0..REGS,
+        }
+    }
+
+    pub fn xmm_regs(&self) -> Range<usize> {
+        match self {
+            Isa::Cmov => 0..0,
+            _ => XMMOFFSET..XMMOFFSET+XMMREGS,
+        }
+    }
+
+    // registers holding the input
+    // (mixed: in both classes like main_astar_mixed.rs)
+    pub fn input_regs(&self) -> Vec<Range<usize>> {
+        match self {
+            Isa::Cmov => vec![0..NUMBERS],
+            Isa::MinMax => vec![XMMOFFSET..XMMOFFSET+NUMBERS],
+            Isa::Mixed => vec![0..NUMBERS, XMMOFFSET..XMMOFFSET+NUMBERS],
+        }
+    }
+
+    // registers holding the output
+    // (mixed: main_astar_mixed.rs accepts either class,
+    // we use the normal registers to avoid a disjunctive goal)
+    pub fn output_regs(&self) -> Range<usize> {
+        match self {
+            Isa::MinMax => XMMOFFSET..XMMOFFSET+NUMBERS,
+            _ => 0..NUMBERS,
+        }
+    }
+}
+
+pub fn possible_commands(isa: Isa) -> Vec<Command> {
+    let mut commands = vec![];
+    let gp = isa.gp_regs();
+    let xmm = isa.xmm_regs();
+    for instr in &[MOV, CMOVG, CMOVL] {
+        for to in gp.clone() {
+            for from in gp.clone() {
+                if to != from {
+                    commands.push((*instr, to, from));
+                }
+            }
+        }
+    }
+    for i in gp.clone() {
+        for j in (i + 1)..gp.end {
+            commands.push((CMP, i, j));
+        }
+    }
+    for instr in &[MIN, MAX, MOVDQA] {
+        for to in xmm.clone() {
+            for from in xmm.clone() {
+                if to != from {
+                    commands.push((*instr, to, from));
+                }
+            }
+        }
+    }
+    if isa == Isa::Mixed {
+        for reg in gp.clone() {
+            for xmm_reg in xmm.clone() {
+                commands.push((MOVD, reg, xmm_reg));
+                commands.push((MOVD, xmm_reg, reg));
+            }
+        }
+    }
+    commands
+}
+
+// transform a permutation according to a command
+pub fn apply(cmd: &Command, perm: &mut Permutation) {
+    let (instr, to, from) = *cmd;
+    match instr {
+        CMP => {
+            perm[REGS + 0] = (perm[to] < perm[from]) as u8;
+            perm[REGS + 1] = (perm[to] > perm[from]) as u8;
+        }
+        MOV => perm[to] = perm[from],
+        CMOVG => {
+            if perm[REGS + 1] == 1 {
+                perm[to] = perm[from];
+            }
+        }
+        CMOVL => {
+            if perm[REGS + 0] == 1 {
+                perm[to] = perm[from];
+            }
+        }
+        MOVD => {
+            perm[to] = perm[from];
+        }
+        MOVDQA => {
+            perm[to] = perm[from];
+        }
+        MIN => {
+            perm[to] = perm[to].min(perm[from]);
+        }
+        MAX => {
+            perm[to] = perm[to].max(perm[from]);
+        }
+        _ => panic!("Unknown instruction"),
+    }
+}
+
+// registers of the ISA in program order (normal registers first, then xmm registers)
+pub fn registers(isa: Isa) -> Vec<usize> {
+    isa.gp_regs().chain(isa.xmm_regs()).collect()
+}
+
+// 1-indexed to stay consistent with minizinc
+// (mixed: the xmm registers follow the REGS normal registers)
+pub fn program_reg(isa: Isa, reg: usize) -> usize {
+    registers(isa).iter().position(|&r| r == reg).expect("Register not part of the ISA") + 1
+}
+
+// program format of the exporters, e.g. "CMOVG 1 4"
+pub fn show_command(cmd: &Command, isa: Isa) -> String {
+    let (instr, to, from) = *cmd;
+    format!("{} {} {}", INSTR_NAMES[instr], program_reg(isa, to), program_reg(isa, from))
+}
+
+// the input in all input registers, everything else 0
+pub fn initial_perm(isa: Isa, input: &[u8]) -> Permutation {
+    let mut perm = Permutation([0; REGS + 2 + XMMREGS]);
+    for regs in isa.input_regs() {
+        for (i, &x) in input.iter().enumerate() {
+            perm[regs.start + i] = x;
+        }
+    }
+    perm
+}
+
+// inputs on which the program computes a wrong output (with the final registers)
+pub fn verify(cmds: &[Command], isa: Isa, inputs: &[(Vec<u8>, [u8; NUMBERS])]) -> Vec<(Vec<u8>, Permutation)> {
+    let mut wrong = vec![];
+    for (input, target) in inputs {
+        let mut perm = initial_perm(isa, input);
+        for cmd in cmds {
+            apply(cmd, &mut perm);
+        }
+        let ok = isa.output_regs().zip(target.iter()).all(|(reg, &t)| t == 0 || perm[reg] == t);
+        if !ok {
+            wrong.push((input.clone(), perm));
+        }
+    }
+    wrong
+}